@@ -0,0 +1,51 @@
+// Embed the git commit, rustc version, build date and enabled features for --build-info
+// The date honours SOURCE_DATE_EPOCH so reproducible builds produce identical binaries
+use std::{ env, process::Command, time::{ SystemTime, UNIX_EPOCH } };
+
+fn output(cmd: &str, args: &[&str]) -> Option<String> {
+    let out = Command::new(cmd).args(args).output().ok()?;
+    if !out.status.success() { return None; }
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+// Days since the unix epoch to a civil date (Howard Hinnant's days_from_civil, inverted)
+fn date(secs: u64) -> String {
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = match output("git", &["rev-parse", "HEAD"]) {
+        Some(commit) => {
+            let dirty = output("git", &["status", "--porcelain", "--untracked-files=no"]).is_some_and(|s| !s.is_empty());
+            if dirty { format!("{}-dirty", commit) } else { commit }
+        }
+        None => "unknown".to_string(),
+    };
+    let rustc = output(&env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()), &["--version"])
+        .unwrap_or_else(|| "unknown".to_string());
+    let secs = env::var("SOURCE_DATE_EPOCH").ok().and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+
+    println!("cargo:rustc-env=SBH_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=SBH_RUSTC_VERSION={}", rustc);
+    println!("cargo:rustc-env=SBH_BUILD_DATE={}", date(secs));
+    println!("cargo:rustc-env=SBH_FEATURES={}", features.join(","));
+}
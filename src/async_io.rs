@@ -0,0 +1,121 @@
+// Input prefetch and output write-behind for --async-io
+// On network filesystems (Lustre, NFS) a single synchronous reader waits out the latency of every request,
+// so the input is read as many concurrent chunks and outputs are written in the background while the
+// program goes on. Without the async-io feature both fall back to plain synchronous IO
+use std::{ io, path::Path };
+#[cfg(feature = "async-io")]
+use std::path::PathBuf;
+
+// Bytes per request and the number of requests in flight
+#[cfg(feature = "async-io")]
+const CHUNK: usize = 4 << 20;
+#[cfg(feature = "async-io")]
+const CONCURRENCY: usize = 8;
+
+#[cfg(feature = "async-io")]
+fn runtime() -> io::Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+}
+
+// Read a whole file with up to CONCURRENCY chunk reads in flight
+#[cfg(feature = "async-io")]
+pub fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    use std::sync::Arc;
+    use tokio::{ io::{ AsyncReadExt, AsyncSeekExt }, sync::Semaphore, task::JoinSet };
+
+    runtime()?.block_on(async {
+        let len = tokio::fs::metadata(path).await?.len() as usize;
+        let permits = Arc::new(Semaphore::new(CONCURRENCY));
+        let mut reads = JoinSet::new();
+        for start in (0..len).step_by(CHUNK) {
+            let permit = permits.clone().acquire_owned().await.map_err(io::Error::other)?;
+            let path = path.to_path_buf();
+            let n = CHUNK.min(len - start);
+            reads.spawn(async move {
+                let _permit = permit;
+                let mut file = tokio::fs::File::open(&path).await?;
+                file.seek(io::SeekFrom::Start(start as u64)).await?;
+                let mut buf = vec![0; n];
+                file.read_exact(&mut buf).await?;
+                Ok::<_, io::Error>((start, buf))
+            });
+        }
+        let mut bytes = vec![0; len];
+        while let Some(read) = reads.join_next().await {
+            let (start, buf) = read.map_err(io::Error::other)??;
+            bytes[start..start + buf.len()].copy_from_slice(&buf);
+        }
+        Ok(bytes)
+    })
+}
+
+#[cfg(not(feature = "async-io"))]
+pub fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    std::fs::read(path)
+}
+
+// Writes that run in the background until finish is called
+pub struct WriteBehind {
+    #[cfg(feature = "async-io")]
+    runtime: tokio::runtime::Runtime,
+    #[cfg(feature = "async-io")]
+    writes: Vec<(PathBuf, tokio::task::JoinHandle<io::Result<()>>)>,
+    #[cfg(not(feature = "async-io"))]
+    result: io::Result<()>,
+}
+
+#[cfg(feature = "async-io")]
+impl WriteBehind {
+    pub fn new() -> io::Result<Self> {
+        Ok(WriteBehind { runtime: runtime()?, writes: vec![] })
+    }
+
+    // Start writing bytes to path and return right away
+    pub fn write(&mut self, path: &Path, bytes: Vec<u8>) {
+        use tokio::io::AsyncWriteExt;
+
+        let target = path.to_path_buf();
+        let handle = self.runtime.spawn(async move {
+            let mut file = tokio::fs::File::create(&target).await?;
+            for chunk in bytes.chunks(CHUNK) {
+                file.write_all(chunk).await?;
+            }
+            file.flush().await
+        });
+        self.writes.push((path.to_path_buf(), handle));
+    }
+
+    // Wait for every write. The first error names the file it happened on
+    pub fn finish(self) -> io::Result<()> {
+        let writes = self.writes;
+        self.runtime.block_on(async move {
+            for (path, handle) in writes {
+                handle.await.map_err(io::Error::other)?
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(not(feature = "async-io"))]
+impl WriteBehind {
+    pub fn new() -> io::Result<Self> {
+        Ok(WriteBehind { result: Ok(()) })
+    }
+
+    pub fn write(&mut self, path: &Path, bytes: Vec<u8>) {
+        if self.result.is_ok() {
+            self.result = std::fs::write(path, bytes)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)));
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.result
+    }
+}
+
@@ -0,0 +1,53 @@
+// Unaligned BAM and SAM input through noodles. Alignments are ignored, only the reads are kept
+use crate::{ simd, utils::{ self, ParseError, Parsed, ReadOptions } };
+use noodles::{ bam, sam::{ self, alignment::RecordBuf } };
+use std::{ io::{ BufRead, Read }, path::Path };
+
+// Add the read of a record. Secondary and supplementary records repeat a read that has a primary record
+// Reads aligned to the reverse strand are stored reverse complemented, so they are turned back as sequenced
+fn push(parsed: &mut Parsed, record: &RecordBuf, options: &ReadOptions) {
+    let flags = record.flags();
+    if flags.is_secondary() || flags.is_supplementary() { return; }
+    let mut read = record.sequence().as_ref().to_vec();
+    if !parsed.rejected.keep(read.len(), &options.lens) { return; }
+    // Missing quality scores are stored as 0xff, and the quality strings are Phred+33 everywhere else
+    let scores = record.quality_scores().as_ref();
+    let mut qual: Vec<u8> = if scores.contains(&0xff) { vec![] } else { scores.iter().map(|&q| q.min(93) + 33).collect() };
+    if flags.is_reverse_complemented() {
+        read = simd::revcomp(&read);
+        qual.reverse();
+    }
+    parsed.reads.push(read);
+    parsed.quals.push(qual);
+    if options.names {
+        let name = record.name().map(|n| n.to_string()).unwrap_or_default();
+        let mate = if !flags.is_segmented() { "" } else if flags.is_first_segment() { "/1" } else if flags.is_last_segment() { "/2" } else { "" };
+        parsed.names.push(format!("{}{}", name, mate));
+    }
+}
+
+// Read the reads of decompressed BAM data. A broken record leaves the rest of the stream unreadable, so it is always an error
+pub fn bam_reads(reader: impl Read, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let mut reader = bam::io::Reader::from(reader);
+    let error = |record: usize, e: std::io::Error| utils::parse_error(fname, record, 1, format!("invalid BAM record: {}", e));
+    let header = reader.read_header().map_err(|e| utils::parse_error(fname, 0, 1, format!("invalid BAM header: {}", e)))?;
+    let mut parsed = Parsed::default();
+    for (i, record) in reader.record_bufs(&header).enumerate() {
+        push(&mut parsed, &record.map_err(|e| error(i + 1, e))?, options);
+    }
+    Ok(parsed)
+}
+
+// Read the reads of SAM data. Errors are reported with the record number in place of the line
+pub fn sam_reads(reader: impl BufRead, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let mut reader = sam::io::Reader::new(reader);
+    let header = reader.read_header().map_err(|e| utils::parse_error(fname, 0, 1, format!("invalid SAM header: {}", e)))?;
+    let mut parsed = Parsed::default();
+    for (i, record) in reader.record_bufs(&header).enumerate() {
+        match record {
+            Ok(record) => push(&mut parsed, &record, options),
+            Err(e) => parsed.malformed(utils::parse_error(fname, i + 1, 1, format!("invalid SAM record: {}", e)), options.strict)?,
+        }
+    }
+    Ok(parsed)
+}
@@ -0,0 +1,77 @@
+// Bloom filters for --drop-singletons. Sequencing errors make k-mers that are seen once, and there are usually far
+// more of them than real k-mers. A pass over the reads records which k-mers were seen twice in a few bits each, so
+// the singletons never take a slot in a hash table or a node of the graph
+use rustc_hash::FxBuildHasher;
+use std::hash::BuildHasher;
+
+// Bits per k-mer and bit positions set for each. About 2% of k-mers seen once pass for seen before
+const BITS_PER_ITEM: usize = 8;
+const HASHES: u64 = 5;
+
+// `n` positions below `len` for an item, by double hashing one 64 bit hash
+pub fn positions(n: u64, len: u64, item: &[u8]) -> impl Iterator<Item = u64> {
+    let h1 = FxBuildHasher.hash_one(item);
+    let h2 = h1.wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(31) | 1;
+    (0..n).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % len)
+}
+
+pub struct Bloom {
+    bits: Vec<u64>,
+}
+
+impl Bloom {
+    // A filter sized for up to `items` distinct items
+    pub fn new(items: usize) -> Self {
+        Bloom { bits: vec![0; (items * BITS_PER_ITEM).div_ceil(64).max(1)] }
+    }
+
+    // Word and bit of each position of an item in a filter of `words` words
+    fn bits(words: usize, item: &[u8]) -> impl Iterator<Item = (usize, u64)> {
+        positions(HASHES, words as u64 * 64, item).map(|pos| ((pos / 64) as usize, 1 << (pos % 64)))
+    }
+
+    // Add an item and tell whether it may have been added before
+    pub fn insert(&mut self, item: &[u8]) -> bool {
+        let mut seen = true;
+        for (word, bit) in Self::bits(self.bits.len(), item) {
+            seen &= self.bits[word] & bit != 0;
+            self.bits[word] |= bit;
+        }
+        seen
+    }
+
+    // False if the item was never added, true if it was or it collides with items that were
+    pub fn contains(&self, item: &[u8]) -> bool {
+        Self::bits(self.bits.len(), item).all(|(word, bit)| self.bits[word] & bit != 0)
+    }
+}
+
+// Items seen at least twice: every item goes into `once`, and into `twice` if `once` already had it
+pub struct Repeated {
+    once: Bloom,
+    twice: Bloom,
+}
+
+impl Repeated {
+    pub fn new(items: usize) -> Self {
+        Repeated { once: Bloom::new(items), twice: Bloom::new(items) }
+    }
+
+    // Record an item that stands for `weight` copies
+    pub fn add(&mut self, item: &[u8], weight: usize) {
+        if self.once.insert(item) || weight > 1 {
+            self.twice.insert(item);
+        }
+    }
+
+    // Whether the item may have been seen at all
+    pub fn seen(&self, item: &[u8]) -> bool {
+        self.once.contains(item)
+    }
+
+    // Whether the item was seen at least twice. Some items seen once also pass, so counts that are kept exactly
+    // should still be checked
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.twice.contains(item)
+    }
+}
@@ -0,0 +1,395 @@
+// A succinct de Bruijn graph for --boss, as described by Bowe, Onodera, Sadakane and Shibuya (2012). The edges are
+// sorted by the reversed label of the node they leave, and the graph is kept as the last base of every edge (W), a
+// bit telling whether it is the last edge of its node (L) and the number of nodes ending in each base (F). Nodes are
+// only numbers, their k-mers are spelled by walking backwards, so the finished graph takes about 8 bits an edge
+// instead of a hash table of k-mers. Building it still sorts the (k+1)-mers in memory, packed in a word each
+use crate::simd;
+use std::{ collections::HashSet, ops::Range };
+
+// Bases are 1 to 4, 0 is the $ padding the dummy nodes and edges start and end with. W adds 4 to a base when an
+// earlier edge with it goes to the same node
+const DOLLAR: u8 = 0;
+const FLAGGED: u8 = 4;
+const SYMBOLS: usize = 9;
+
+fn base(code: u8) -> u8 {
+    b"$ACGT"[code as usize]
+}
+
+// A node packed two bits a base, see simd::pack, as three bits a base with the last base highest, so keys sort in
+// the colexicographic order of the nodes
+fn widen(code: u64, len: usize) -> u128 {
+    (0..len).fold(0, |key, i| key | (u128::from(code >> (2 * i) & 3) + 1) << (3 * i))
+}
+
+// Bits with the count of ones before every block of 8 words, for rank and select
+struct RankBits {
+    words: Vec<u64>,
+    blocks: Vec<u64>,
+}
+
+impl RankBits {
+    fn new(bits: impl Iterator<Item = bool>) -> Self {
+        let mut words: Vec<u64> = vec![];
+        for (i, bit) in bits.enumerate() {
+            if i / 64 == words.len() { words.push(0); }
+            if bit { *words.last_mut().expect("pushed") |= 1 << (i % 64); }
+        }
+        let mut ones = 0;
+        let mut blocks: Vec<u64> = words.chunks(8).map(|block| {
+            let before = ones;
+            ones += block.iter().map(|w| u64::from(w.count_ones())).sum::<u64>();
+            before
+        }).collect();
+        // The total, so ranks can be asked up to the end
+        blocks.push(ones);
+        RankBits { words, blocks }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    // Ones before position i
+    fn rank(&self, i: usize) -> usize {
+        let word = i / 64;
+        let mut ones = self.blocks[word / 8];
+        ones += self.words[word / 8 * 8..word].iter().map(|w| u64::from(w.count_ones())).sum::<u64>();
+        let bits = i % 64;
+        if bits > 0 { ones += u64::from((self.words[word] & (u64::MAX >> (64 - bits))).count_ones()); }
+        ones as usize
+    }
+
+    // Position of the one with r ones before it
+    fn select(&self, r: usize) -> usize {
+        let block = self.blocks.partition_point(|&ones| ones as usize <= r) - 1;
+        let mut left = r - self.blocks[block] as usize;
+        for (w, &word) in self.words.iter().enumerate().skip(block * 8) {
+            let ones = word.count_ones() as usize;
+            if left < ones {
+                let mut word = word;
+                for _ in 0..left { word &= word - 1; }
+                return w * 64 + word.trailing_zeros() as usize;
+            }
+            left -= ones;
+        }
+        unreachable!("select past the last one")
+    }
+
+    fn bytes(&self) -> usize {
+        8 * (self.words.len() + self.blocks.len())
+    }
+}
+
+// Symbols of four bits, two a byte, with the count of each before every block, for rank and select over W
+const SYMBOL_BLOCK: usize = 512;
+
+struct Symbols {
+    packed: Vec<u8>,
+    blocks: Vec<[u64; SYMBOLS]>,
+    len: usize,
+}
+
+impl Symbols {
+    fn new(symbols: &[u8]) -> Self {
+        let packed = symbols.chunks(2).map(|pair| pair[0] | pair.get(1).map_or(0, |s| s << 4)).collect();
+        let mut counts = [0u64; SYMBOLS];
+        let mut blocks: Vec<[u64; SYMBOLS]> = symbols.chunks(SYMBOL_BLOCK).map(|block| {
+            let before = counts;
+            for &s in block { counts[s as usize] += 1; }
+            before
+        }).collect();
+        blocks.push(counts);
+        Symbols { packed, blocks, len: symbols.len() }
+    }
+
+    fn get(&self, i: usize) -> u8 {
+        self.packed[i / 2] >> (4 * (i % 2)) & 0xf
+    }
+
+    // Times s comes before position i
+    fn rank(&self, s: u8, i: usize) -> usize {
+        let block = i / SYMBOL_BLOCK;
+        let before = self.blocks[block][s as usize] as usize;
+        before + (block * SYMBOL_BLOCK..i).filter(|&j| self.get(j) == s).count()
+    }
+
+    // Position of the s with r of them before it
+    fn select(&self, s: u8, r: usize) -> Option<usize> {
+        let block = self.blocks.partition_point(|counts| counts[s as usize] as usize <= r).checked_sub(1)?;
+        let mut left = r - self.blocks[block][s as usize] as usize;
+        for j in block * SYMBOL_BLOCK..self.len {
+            if self.get(j) == s {
+                if left == 0 { return Some(j); }
+                left -= 1;
+            }
+        }
+        None
+    }
+
+    fn bytes(&self) -> usize {
+        self.packed.len() + 8 * SYMBOLS * self.blocks.len()
+    }
+}
+
+pub struct Boss {
+    pub k: usize,
+    // The number of edges, dummy ones included
+    pub edges: usize,
+    pub nodes: usize,
+    // The last base of every edge, 0 for the edges out of nodes with no successor
+    labels: Symbols,
+    // Whether an edge is the last one out of its node
+    last: RankBits,
+    // Nodes that start with $, added so every node can be reached by going backwards
+    dummy: RankBits,
+    // The first node ending in each symbol, and the number of nodes after the last one
+    first: [usize; 6],
+}
+
+impl Boss {
+    // Build the graph of the distinct (k+1)-mers among `windows`, adding their reverse complements with `canonical`.
+    // Windows with other bases than ACGT are left out
+    pub fn new<R: AsRef<[u8]>>(windows: &[(R, usize)], k: usize, canonical: bool) -> Self {
+        // The windows packed two bits a base, so the distinct ones are sorted out in a word each
+        let mut codes: Vec<u64> = windows.iter()
+            .filter(|(w, _)| w.as_ref().len() == k + 1)
+            .filter_map(|(w, _)| simd::rolling(w.as_ref(), k + 1).next().flatten())
+            .flat_map(|(fwd, rev)| [Some(fwd), canonical.then_some(rev)].into_iter().flatten())
+            .collect();
+        codes.sort_unstable();
+        codes.dedup();
+        let mask = u64::MAX >> (64 - 2 * k);
+        // An edge is the key of the node it leaves and three bits of its last base
+        let mut edges: Vec<u128> = codes.iter().map(|&w| widen(w & mask, k) << 3 | (u128::from(w >> (2 * k)) + 1)).collect();
+        let mut targets: Vec<u64> = codes.iter().map(|&w| w >> 2).collect();
+        targets.sort_unstable();
+        targets.dedup();
+        // A node no edge goes to is reached from $$..$ through dummy nodes that spell it one base at a time
+        let mut sources = HashSet::new();
+        for &w in &codes {
+            let from = w & mask;
+            if targets.binary_search(&from).is_ok() || !sources.insert(from) { continue; }
+            let from = widen(from, k);
+            for i in 0..k {
+                let padded = (from & ((1 << (3 * i)) - 1)) << (3 * (k - i));
+                edges.push(padded << 3 | from >> (3 * i) & 7);
+            }
+        }
+        drop(codes);
+        edges.sort_unstable();
+        edges.dedup();
+        // A node no edge leaves gets one edge labelled $
+        let ends: Vec<u128> = targets.into_iter()
+            .map(|t| widen(t, k))
+            .filter(|&t| edges.binary_search_by_key(&t, |&e| e >> 3).is_err())
+            .collect();
+        edges.extend(ends.into_iter().map(|t| t << 3 | u128::from(DOLLAR)));
+        edges.sort_unstable();
+
+        let shift = 3 * (k - 1);
+        let mut first = [0; 6];
+        let mut previous = [None; 5];
+        let labels: Vec<u8> = edges.iter().map(|&e| {
+            let (from, c) = (e >> 3, (e & 7) as u8);
+            if c == DOLLAR { return DOLLAR; }
+            let to = from >> 3 | u128::from(c) << shift;
+            if previous[c as usize] == Some(to) { return c + FLAGGED; }
+            previous[c as usize] = Some(to);
+            c
+        }).collect();
+        let last = RankBits::new((0..edges.len()).map(|i| i + 1 == edges.len() || edges[i] >> 3 != edges[i + 1] >> 3));
+        let mut nodes: Vec<u128> = edges.iter().map(|&e| e >> 3).collect();
+        nodes.dedup();
+        for &node in &nodes { first[(node >> shift) as usize + 1] += 1; }
+        for c in 1..6 { first[c] += first[c - 1]; }
+        let dummy = RankBits::new(nodes.iter().map(|&node| node & 7 == u128::from(DOLLAR)));
+        Boss { k, edges: edges.len(), nodes: nodes.len(), labels: Symbols::new(&labels), last, dummy, first }
+    }
+
+    // Bytes taken by the arrays of the graph
+    pub fn bytes(&self) -> usize {
+        self.labels.bytes() + self.last.bytes() + self.dummy.bytes() + size_of::<Self>()
+    }
+
+    // The edges leaving a node
+    fn out_edges(&self, node: usize) -> Range<usize> {
+        let start = if node == 0 { 0 } else { self.last.select(node - 1) + 1 };
+        start..self.last.select(node) + 1
+    }
+
+    // The node an edge leaves
+    fn from(&self, edge: usize) -> usize {
+        self.last.rank(edge)
+    }
+
+    // The node an edge goes to, None for the $ edges of nodes without successors
+    fn forward(&self, edge: usize) -> Option<usize> {
+        let s = self.labels.get(edge);
+        if s == DOLLAR { return None; }
+        let c = if s > FLAGGED { s - FLAGGED } else { s };
+        // A flagged edge goes where the last plain one with its base before it does
+        let rank = self.labels.rank(c, if s > FLAGGED { edge } else { edge + 1 }) - 1;
+        Some(self.first[c as usize] + rank)
+    }
+
+    // The symbol a node ends with
+    fn last_symbol(&self, node: usize) -> u8 {
+        (self.first.partition_point(|&f| f <= node) - 1) as u8
+    }
+
+    // The edges going to a node, the plain one first, or None for $$..$
+    fn in_edges(&self, node: usize) -> Option<(usize, usize)> {
+        let c = self.last_symbol(node);
+        if c == DOLLAR { return None; }
+        let rank = node - self.first[c as usize];
+        let edge = self.labels.select(c, rank).expect("every node but $$..$ has an edge to it");
+        let next = self.labels.select(c, rank + 1).unwrap_or(self.edges);
+        let flagged = self.labels.rank(c + FLAGGED, next) - self.labels.rank(c + FLAGGED, edge);
+        Some((edge, 1 + flagged))
+    }
+
+    // Edges into a node from nodes of the reads, leaving out the dummy one of a node nothing else goes to
+    fn indegree(&self, node: usize) -> usize {
+        match self.in_edges(node) {
+            Some((edge, 1)) if self.dummy.get(self.from(edge)) => 0,
+            Some((_, n)) => n,
+            None => 0,
+        }
+    }
+
+    fn outdegree(&self, node: usize) -> usize {
+        self.out_edges(node).filter(|&e| self.labels.get(e) != DOLLAR).count()
+    }
+
+    // The k-mer of a node, spelled from its last base backwards along the plain edges into it
+    pub fn label(&self, mut node: usize) -> Vec<u8> {
+        let mut seq = vec![base(DOLLAR); self.k];
+        for i in (0..self.k).rev() {
+            let Some((edge, _)) = self.in_edges(node) else { break };
+            seq[i] = base(self.last_symbol(node));
+            node = self.from(edge);
+        }
+        seq
+    }
+
+    // The node a node is joined to in a unitig: its only successor, when nothing else goes there
+    fn next(&self, node: usize) -> Option<usize> {
+        if self.outdegree(node) != 1 { return None; }
+        let next = self.out_edges(node).find_map(|e| self.forward(e))?;
+        (next != node && self.indegree(next) == 1).then_some(next)
+    }
+
+    // Whether some node is joined to a node
+    fn joined(&self, node: usize) -> bool {
+        self.indegree(node) == 1 && self.next(self.from(self.in_edges(node).expect("has an edge in").0)) == Some(node)
+    }
+
+    // Spell the maximal chains of nodes of the reads where each one has a single way out, into a node with a single
+    // way in, as unitig::compact does for the hashed graph
+    pub fn unitigs(&self) -> Vec<Vec<u8>> {
+        // Dummy nodes are in no unitig
+        let mut used = self.dummy.words.clone();
+        let is_used = |used: &[u64], node: usize| used[node / 64] >> (node % 64) & 1 == 1;
+        let mut unitigs = vec![];
+        // Chains start at nodes nothing is joined to. What is left after them are cycles of joined nodes
+        let starts = (0..self.nodes).filter(|&n| !self.dummy.get(n) && !self.joined(n)).chain(0..self.nodes);
+        for start in starts {
+            if is_used(&used, start) { continue; }
+            used[start / 64] |= 1 << (start % 64);
+            let mut seq = self.label(start);
+            let mut current = start;
+            while let Some(next) = self.next(current) {
+                if is_used(&used, next) { break; }
+                used[next / 64] |= 1 << (next % 64);
+                seq.push(base(self.last_symbol(next)));
+                current = next;
+            }
+            unitigs.push(seq);
+        }
+        unitigs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ Boss, RankBits, Symbols, SYMBOLS };
+    use crate::{ sbh_assembler::Assembler, unitig, utils::{ self, SplitMix64 } };
+
+    // Lengths on both sides of the word and block boundaries
+    const LENS: [usize; 12] = [0, 1, 63, 64, 65, 511, 512, 513, 1023, 1024, 4095, 4097];
+
+    #[test]
+    fn rank_and_select_bits() {
+        let mut rng = SplitMix64::new(1);
+        for len in LENS {
+            for density in [1, 2, 64] {
+                let bits: Vec<bool> = (0..len).map(|_| rng.next_u64().is_multiple_of(density)).collect();
+                let rank = RankBits::new(bits.iter().copied());
+                let mut ones = 0;
+                for (i, &bit) in bits.iter().enumerate() {
+                    assert_eq!(rank.rank(i), ones, "rank({}) of {} bits", i, len);
+                    assert_eq!(rank.get(i), bit);
+                    if bit {
+                        assert_eq!(rank.select(ones), i, "select({}) of {} bits", ones, len);
+                        ones += 1;
+                    }
+                }
+                assert_eq!(rank.rank(len), ones);
+            }
+        }
+    }
+
+    #[test]
+    fn rank_and_select_symbols() {
+        let mut rng = SplitMix64::new(2);
+        for len in LENS {
+            let symbols: Vec<u8> = (0..len).map(|_| (rng.next_u64() % SYMBOLS as u64) as u8).collect();
+            let packed = Symbols::new(&symbols);
+            let mut seen = [0; SYMBOLS];
+            for (i, &s) in symbols.iter().enumerate() {
+                assert_eq!(packed.get(i), s);
+                for c in 0..SYMBOLS as u8 {
+                    assert_eq!(packed.rank(c, i), seen[c as usize], "rank({}, {}) of {} symbols", c, i, len);
+                }
+                assert_eq!(packed.select(s, seen[s as usize]), Some(i));
+                seen[s as usize] += 1;
+            }
+            for c in 0..SYMBOLS as u8 {
+                assert_eq!(packed.rank(c, len), seen[c as usize]);
+                assert_eq!(packed.select(c, seen[c as usize]), None);
+            }
+        }
+    }
+
+    // A unitig as its k-mers, sorted, so a cycle is the same whichever node it is spelled from
+    fn kmer_sets(unitigs: impl Iterator<Item = Vec<u8>>, k: usize) -> Vec<Vec<Vec<u8>>> {
+        let mut sets: Vec<Vec<Vec<u8>>> = unitigs.map(|seq| {
+            let mut kmers: Vec<Vec<u8>> = seq.windows(k).map(|w| w.to_vec()).collect();
+            kmers.sort_unstable();
+            kmers
+        }).collect();
+        sets.sort_unstable();
+        sets
+    }
+
+    #[test]
+    fn unitigs_match_hashed_compaction() {
+        let mut rng = SplitMix64::new(3);
+        let k = 11;
+        // A random genome with a repeat, so the graph has branches, and a short circular plasmid
+        let mut genome: Vec<u8> = (0..3000).map(|_| b"ACGT"[(rng.next_u64() % 4) as usize]).collect();
+        let repeat = genome[100..140].to_vec();
+        genome.splice(2000..2000, repeat);
+        let plasmid: Vec<u8> = (0..200).map(|_| b"ACGT"[(rng.next_u64() % 4) as usize]).collect();
+        let reads = [genome, [plasmid.as_slice(), &plasmid[..k]].concat()];
+        let windows = utils::kmerize(reads.iter().map(|r| (r, 1)), k);
+        for canonical in [false, true] {
+            let boss = Boss::new(&windows, k, canonical);
+            let mut ass: Assembler = Assembler::from_weighted(windows.clone(), k);
+            if canonical { ass.make_canonical().unwrap(); }
+            let hashed = unitig::compact(&ass).unitigs.into_iter().map(|u| u.seq);
+            assert_eq!(kmer_sets(boss.unitigs().into_iter(), k), kmer_sets(hashed, k), "canonical {}", canonical);
+        }
+    }
+}
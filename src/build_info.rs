@@ -0,0 +1,32 @@
+// Build metadata embedded by build.rs, so archived results can be tied to the binary that made them
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("SBH_GIT_COMMIT");
+pub const RUSTC_VERSION: &str = env!("SBH_RUSTC_VERSION");
+pub const BUILD_DATE: &str = env!("SBH_BUILD_DATE");
+pub const LICENSE: &str = env!("CARGO_PKG_LICENSE");
+
+pub fn features() -> Vec<&'static str> {
+    env!("SBH_FEATURES").split(',').filter(|f| !f.is_empty() && *f != "default").collect()
+}
+
+fn license() -> &'static str {
+    if LICENSE.is_empty() { "unspecified" } else { LICENSE }
+}
+
+// The --build-info text
+pub fn summary() -> String {
+    format!("sbh_assembler {}\ncommit:   {}\nrustc:    {}\nbuilt:    {}\nfeatures: {}\nlicense:  {}",
+        VERSION, GIT_COMMIT, RUSTC_VERSION, BUILD_DATE, if features().is_empty() { "none".to_string() } else { features().join(",") }, license())
+}
+
+#[cfg(any(feature = "runs", feature = "explain", feature = "stats"))]
+pub fn to_json() -> serde_json::Value {
+    serde_json::json!({
+        "version": VERSION,
+        "git_commit": GIT_COMMIT,
+        "rustc": RUSTC_VERSION,
+        "build_date": BUILD_DATE,
+        "features": features(),
+        "license": license(),
+    })
+}
@@ -0,0 +1,75 @@
+// A bitmap of the links walks have taken, shared by the walks of --parallel-walks. A walk sets the bit of a link
+// before it takes it, and the atomic update tells exactly one of the walks racing for a link that it won
+use std::{ cell::RefCell, collections::HashSet, sync::atomic::{ AtomicU64, Ordering } };
+
+// Where a walk of --parallel-walks claims the links it takes, indexed by their position in Graph storage
+pub trait Claims {
+    // Returns false if the link was already claimed
+    fn claim(&self, i: usize) -> bool;
+    fn release(&self, i: usize);
+    fn is_claimed(&self, i: usize) -> bool;
+}
+
+pub struct EdgeClaims {
+    bits: Vec<AtomicU64>,
+}
+
+impl EdgeClaims {
+    // Room for the edges of a graph
+    pub fn new(edges: usize) -> Self {
+        EdgeClaims { bits: (0..edges.div_ceil(64)).map(|_| AtomicU64::new(0)).collect() }
+    }
+
+    fn bit(i: usize) -> (usize, u64) {
+        (i / 64, 1 << (i % 64))
+    }
+}
+
+impl Claims for EdgeClaims {
+    fn claim(&self, i: usize) -> bool {
+        let (word, bit) = Self::bit(i);
+        self.bits[word].fetch_or(bit, Ordering::AcqRel) & bit == 0
+    }
+
+    fn release(&self, i: usize) {
+        let (word, bit) = Self::bit(i);
+        self.bits[word].fetch_and(!bit, Ordering::AcqRel);
+    }
+
+    fn is_claimed(&self, i: usize) -> bool {
+        let (word, bit) = Self::bit(i);
+        self.bits[word].load(Ordering::Acquire) & bit != 0
+    }
+}
+
+// The links one walk of --deterministic-walks proposes to take on top of those committed in earlier rounds. Nothing
+// is shared with the other walks of the round, so what a walk proposes does not depend on how fast they are
+pub struct Proposal<'a> {
+    committed: &'a EdgeClaims,
+    taken: RefCell<HashSet<usize>>,
+}
+
+impl<'a> Proposal<'a> {
+    pub fn new(committed: &'a EdgeClaims) -> Self {
+        Proposal { committed, taken: RefCell::default() }
+    }
+
+    // The links proposed, in no particular order
+    pub fn into_links(self) -> Vec<usize> {
+        self.taken.into_inner().into_iter().collect()
+    }
+}
+
+impl Claims for Proposal<'_> {
+    fn claim(&self, i: usize) -> bool {
+        !self.committed.is_claimed(i) && self.taken.borrow_mut().insert(i)
+    }
+
+    fn release(&self, i: usize) {
+        self.taken.borrow_mut().remove(&i);
+    }
+
+    fn is_claimed(&self, i: usize) -> bool {
+        self.committed.is_claimed(i) || self.taken.borrow().contains(&i)
+    }
+}
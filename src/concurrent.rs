@@ -0,0 +1,78 @@
+// Graph construction on maps shared by all rayon workers, used by Assembler::from_slices_par with the concurrent
+// feature in place of sorting read ends. Nodes are still numbered in the order they first appear in the reads
+use crate::{ kmer::KmerRepr, sbh_assembler::{ Edge, Node, NodeHasher, NodeId, Nodes } };
+use dashmap::{ DashMap, mapref::one::Ref };
+use rayon::prelude::*;
+use std::sync::atomic::{ AtomicU32, AtomicUsize, Ordering };
+
+// A node while the graph is being built. A k-mer that is already in the map only takes a read lock on its shard,
+// so threads that share a node update it through atomics
+struct Counters {
+    ideg: AtomicUsize,
+    odeg: AtomicUsize,
+    cov: AtomicUsize,
+    // Position of the first read end with this k-mer, twice the index of the read plus one for a suffix
+    first: AtomicUsize,
+    id: AtomicU32,
+}
+
+// Copies of one read, folded the way Graph::collapse does
+#[derive(Default)]
+struct Copies {
+    count: AtomicU32,
+    reads: AtomicUsize,
+}
+
+fn counters<'a, K: KmerRepr>(map: &'a DashMap<K, Counters, NodeHasher>, kmer: &K) -> Ref<'a, K, Counters> {
+    if let Some(counters) = map.get(kmer) {
+        return counters;
+    }
+    map.entry(kmer.clone()).or_insert_with(|| Counters {
+        ideg: AtomicUsize::new(0),
+        odeg: AtomicUsize::new(0),
+        cov: AtomicUsize::new(0),
+        first: AtomicUsize::new(usize::MAX),
+        id: AtomicU32::new(0),
+    }).downgrade()
+}
+
+// Add a node for every read end to `nodes` and return the edges. Reads are given as their prefix, suffix, length
+// and weight. The maps hash with the hasher of `nodes`
+pub fn build<K: KmerRepr>(nodes: &mut Nodes<K>, reads: &[(K, K, usize, usize)]) -> Vec<Edge> {
+    let map: DashMap<K, Counters, NodeHasher> = DashMap::with_hasher(nodes.hasher().clone());
+    reads.par_iter().enumerate().for_each(|(i, (p, s, _, weight))| {
+        let prefix = counters(&map, p);
+        prefix.odeg.fetch_add(1, Ordering::Relaxed);
+        prefix.cov.fetch_add(*weight, Ordering::Relaxed);
+        prefix.first.fetch_min(2 * i, Ordering::Relaxed);
+        drop(prefix);
+        let suffix = counters(&map, s);
+        suffix.ideg.fetch_add(1, Ordering::Relaxed);
+        suffix.cov.fetch_add(*weight, Ordering::Relaxed);
+        suffix.first.fetch_min(2 * i + 1, Ordering::Relaxed);
+    });
+    let mut firsts: Vec<(usize, K)> = map.par_iter().map(|entry| (entry.first.load(Ordering::Relaxed), entry.key().clone())).collect();
+    firsts.par_sort_unstable_by_key(|(first, _)| *first);
+    for (_, kmer) in firsts {
+        let counters = map.get(&kmer).expect("node of a read end");
+        let node = Node::new(kmer.clone(), counters.ideg.load(Ordering::Relaxed), counters.odeg.load(Ordering::Relaxed), counters.cov.load(Ordering::Relaxed));
+        counters.id.store(nodes.insert(node).0, Ordering::Relaxed);
+    }
+    let id = |kmer: &K| NodeId(map.get(kmer).expect("node of a read end").id.load(Ordering::Relaxed));
+    let edges: DashMap<(NodeId, NodeId, usize), Copies, NodeHasher> = DashMap::with_hasher(nodes.hasher().clone());
+    reads.par_iter().for_each(|(p, s, span, weight)| {
+        let key = (id(p), id(s), *span);
+        let copies = match edges.get(&key) {
+            Some(copies) => copies,
+            None => edges.entry(key).or_default().downgrade(),
+        };
+        copies.count.fetch_add(1, Ordering::Relaxed);
+        copies.reads.fetch_add(*weight, Ordering::Relaxed);
+    });
+    edges.into_par_iter().map(|((prefix, suffix, span), copies)| {
+        let mut edge = Edge::new(prefix, suffix, span);
+        edge.count = copies.count.into_inner();
+        edge.reads = copies.reads.into_inner();
+        edge
+    }).collect()
+}
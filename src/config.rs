@@ -0,0 +1,752 @@
+use crate::{ bloom::Repeated, counting::{ CountMin, KmerCounter }, kmer::KmerRepr, sbh_assembler::{ DENSE_MAX_K, LOOKAHEAD_MAX_DEPTH, TraversalStrategy }, utils };
+use std::{ collections::HashMap, env, ffi::OsString, ops::RangeInclusive, path::PathBuf, str::FromStr };
+
+// What to do with reads containing N or other IUPAC ambiguity codes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NPolicy {
+    // Drop the read
+    Skip,
+    // Fill a single ambiguous base with up to this many of the bases seen most often at that k-mer in unambiguous reads
+    Expand(usize),
+    // Break the read at ambiguous bases and keep the pieces that are long enough
+    Split,
+    // Replace every ambiguous base with a random base it stands for
+    Random,
+    // Stop with an error
+    Error,
+}
+
+impl FromStr for NPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.split_once(':') {
+            None if s == "skip" => Ok(NPolicy::Skip),
+            None if s == "expand" => Ok(NPolicy::Expand(1)),
+            None if s == "split" => Ok(NPolicy::Split),
+            None if s == "random" => Ok(NPolicy::Random),
+            None if s == "error" => Ok(NPolicy::Error),
+            Some(("expand", max)) => match max.parse() {
+                Ok(max @ 1..=4) => Ok(NPolicy::Expand(max)),
+                _ => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+// What to do with soft-masked (lowercase) bases
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SoftMask {
+    // Use them like any other base
+    Keep,
+    // Drop reads with any masked base
+    Drop,
+}
+
+impl FromStr for SoftMask {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "keep" => Ok(SoftMask::Keep),
+            "drop" => Ok(SoftMask::Drop),
+            _ => Err(()),
+        }
+    }
+}
+
+// Quality trimming like Trimmomatic's SLIDINGWINDOW, given as <size>:<min_qual>
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SlidingWindow {
+    pub size: usize,
+    // Lowest mean Phred quality of a window
+    pub min_qual: u8,
+}
+
+impl FromStr for SlidingWindow {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let (size, min_qual) = s.split_once(':').ok_or(())?;
+        match (size.parse(), min_qual.parse()) {
+            (Ok(size @ 1..), Ok(min_qual @ 0..=93)) => Ok(SlidingWindow { size, min_qual }),
+            _ => Err(()),
+        }
+    }
+}
+
+// Flags and their descriptions, printed at startup
+pub const OPTIONS: &[(&str, &str)] = &[
+    ("-o, --output <file>", "write the contigs here; every positional argument is then an infile"),
+    ("--kmer-size <k>", "node length; reads must be k+1 to 2k bp long (default 15), nodes over 31 bp take a word per 32 bp"),
+    ("--debruijn", "split reads of any length into overlapping (k+1)-mers instead of reading k+1 to 2k bp reads"),
+    ("--sparse <w>", "with --debruijn, only make nodes of the (w, k) minimizers, w at most k, with edges spanning the k-mers between"),
+    ("--boss", "with --debruijn, keep the graph as a succinct BOSS graph and spell its unitigs as contigs (boss feature)"),
+    ("--canonical", "join every k-mer with its reverse complement so reads from both strands build one graph"),
+    ("--dense-nodes", "look nodes up in a table of all 4^k k-mers instead of hashing them, for large inputs with k up to 14"),
+    ("--drop-singletons", "leave out k-mers seen only once, found with Bloom filters in a pass before the graph is built"),
+    ("--min-kmer-count <n>", "leave out k-mers seen fewer than n times, counted in a pass before the graph is built"),
+    ("--count-sketch <MB>", "count k-mers for --min-kmer-count in a count-min sketch of this size instead of exactly"),
+    ("--memory-limit <MB>", "with --debruijn, count (k+1)-mers in sorted runs on disk, holding at most this much of them at once"),
+    ("--tmp-dir <dir>", "write the runs of --memory-limit and the files of --disk-graph here (default the system temporary directory)"),
+    ("--disk-graph", "keep the graph's edges in memory mapped files under --tmp-dir so the kernel can page them out (mmap feature)"),
+    ("--siphash", "hash nodes with randomly keyed SipHash instead of the faster Fx, for input that may be crafted to collide"),
+    ("--min-read-len <n>", "drop reads shorter than n bp (default and lowest k+1)"),
+    ("--max-read-len <n>", "drop reads longer than n bp (default and highest 2k, no limit with --debruijn)"),
+    ("--format <f>", "read the infiles as fasta, fastq, sam or bam instead of detecting it from their content"),
+    ("--hpc", "collapse homopolymer runs in the reads before building the graph and restore them in the contigs"),
+    ("--strict", "stop at the first malformed input record instead of skipping it"),
+    ("--interleaved", "pair x/1 and x/2 records that follow each other as mates and keep the pairs in the graph"),
+    ("--pe1 <file>", "first mates of paired-end reads, used with --pe2 in addition to any infiles"),
+    ("--pe2 <file>", "second mates, in the same order as --pe1"),
+    ("--http-retries <n>", "resume a dropped download of a URL infile up to n times (default 3, http feature)"),
+    ("--mmap", "read uncompressed FASTA infiles through a memory map without copying each read (mmap feature)"),
+    ("--spectrum", "the infiles list k-mers, one per line with an optional tab and count; k is their length - 1"),
+    ("--from-gfa <gfa>", "load a graph exported as GFA instead of reading reads; k is the segment length"),
+    ("--save-graph <file>", "write the built graph to a compact binary file for --load-graph"),
+    ("--load-graph <file>", "start from a graph written by --save-graph, adding the reads of any infiles to it; k must match"),
+    ("--subsample <f>", "assemble a random fraction of the reads, drawn with --seed, to try settings on a large dataset"),
+    ("--max-reads <n>", "assemble at most n reads, drawn at random with --seed"),
+    ("--sample-edges <f>", "preview: build the graph from a fraction of the reads, report topology and exit"),
+    ("--seed <n>", "seed for random sampling (default 42)"),
+    ("--record-run", "record parameters and metrics in the run registry (see `sbh runs`)"),
+    ("--taxonomy-db <tsv>", "annotate contigs using a kmer<TAB>taxon database"),
+    ("--paranoid", "run expensive internal consistency checks and panic on the first violation"),
+    ("--async-io", "prefetch the input and write the contigs in the background (async-io feature)"),
+    ("--n-policy <p>", "reads with N or other ambiguity codes: skip (default), expand[:max] a single one into up to max (1-4) likely bases, split, random or error"),
+    ("--split-n-runs <n>", "break reads at runs of n or more Ns and keep the pieces that are long enough"),
+    ("--adapters <fasta>", "remove these adapters (exact, or with one mismatch over 10+ bp) from read ends before the length check"),
+    ("--sliding-window <w:q>", "cut FASTQ reads where the mean quality of w bases first drops below q, before the length check"),
+    ("--soft-masked <p>", "reads with lowercase bases: keep (default, used like uppercase) or drop"),
+    ("--dedup", "collapse exact duplicate reads into one edge that counts them all"),
+    ("--collapse-duplicates <n>", "fold reads within n mismatches into quality-weighted consensus reads"),
+    ("--min-overlap <n>", "minimum overlap in nucleotides to merge two contigs (default 15)"),
+    ("--min-overlap-frac <f>", "minimum overlap as a fraction of the shorter contig (default 0, off)"),
+    ("--string-graph", "merge contigs along the chains of their transitively reduced overlap graph instead of greedily"),
+    ("--build-info", "print the commit, compiler, build date and features of this binary and exit"),
+    ("--stages <a,b,..>", "run these named stages instead of paths,cycles,contigs,condense; a stage takes its flag, such as --clip-tips, as its setting"),
+    ("--fai", "write a samtools style .fai index next to the output"),
+    ("--stats-json <file>", "write assembly stats, k-mer QV and completeness against the reads as JSON"),
+    ("--copy-number", "estimate copy number from coverage, add cov= and cn= to contig headers"),
+    ("--split-output-by-length <a,b>", "also write contigs into small/medium/large.fasta next to the output"),
+    ("--provenance", "write how every contig was built to <outfile>.provenance.jsonl (see `sbh explain`)"),
+    ("--break-points <tsv>", "write the graph node and stop reason of each contig end"),
+    ("--near-miss-report <tsv>", "write joins that were almost merged for manual curation"),
+    ("--merge-metrics <csv>", "write per-thread merge counters for every merge pass"),
+    ("--unitig-gfa <gfa>", "write the graph as GFA with its non-branching chains compacted into unitigs, after cleaning"),
+    ("--export-graph-tables <dir>", "write nodes and edges tables (CSV, or Parquet with the parquet feature)"),
+    ("--min-edge-coverage <n>", "delete links between two nodes that fewer than n reads support, before tips and bubbles"),
+    ("--clip-tips <bp>", "remove dead-end branches up to this long before cleaning, repeated until none is left"),
+    ("--tip-max-cov <f>", "only clip tips whose mean node coverage is at most f (default any)"),
+    ("--pop-bubbles <bp>", "keep only the best covered of parallel branches up to this long between the same two nodes"),
+    ("--bubble-report <tsv>", "write each popped branch and the branch kept in its place"),
+    ("--colors", "tag edges with the infiles that have them and write the infiles sharing each contig to <outfile>.colors.tsv"),
+    ("--read-provenance", "record the reads that have each edge and write where each read is along the contigs to <outfile>.reads.tsv"),
+    ("--eulerian-report <tsv>", "before traversal, report whether the graph has an Eulerian path or circuit and write the degree balance of each component"),
+    ("--traversal <s>", "how walks pick among successors: first (default), coverage, match (closest to the coverage before the branch), lookahead[:depth] (default 4, at most 16) or random (with --seed)"),
+    ("--backtrack <n>", "let a walk that dead-ends take back up to n steps to try the successors it passed over (default 0, off)"),
+    ("--attempts <n>", "walk from every start node n times, all but the first picking successors at random with --seed, and keep the longest (default 1)"),
+    ("--parallel-walks", "find paths and cycles from all start nodes at once, each claiming the links it takes; not with --backtrack or --attempts"),
+    ("--deterministic-walks", "with --parallel-walks, settle contested links by start node so every run gives the same contigs"),
+    ("--components", "find paths, cycles and contigs one weakly connected component at a time, largest first"),
+    ("--parallel-components", "like --components, with every component assembled and condensed in a graph of its own at once"),
+    ("--clean-iterations <n>", "experimental: iteratively decay and drop unused edges (default 0, off)"),
+    ("--clean-decay <f>", "weight multiplier for edges unused in an iteration (default 0.5)"),
+    ("--clean-min-weight <f>", "edges below this weight are removed (default 0.2)"),
+];
+
+// Runtime options for the assembler
+// Positional arguments are the infiles (- for stdin) and, without -o, the outfile last; everything else is a --flag
+// With --pe1/--pe2 or --from-gfa a single positional is the outfile
+#[derive(Clone)]
+pub struct Config {
+    pub infiles: Vec<PathBuf>,
+    pub outfile: PathBuf,
+    pub sample_edges: Option<f64>,
+    // Fraction and largest number of the reads to assemble
+    pub subsample: Option<f64>,
+    pub max_reads: Option<usize>,
+    pub seed: u64,
+    pub record_run: bool,
+    pub taxonomy_db: Option<PathBuf>,
+    pub paranoid: bool,
+    pub async_io: bool,
+    pub n_policy: NPolicy,
+    pub soft_masked: SoftMask,
+    // FASTA file of adapter sequences to trim off the reads
+    pub adapters: Option<PathBuf>,
+    pub sliding_window: Option<SlidingWindow>,
+    pub split_n_runs: Option<usize>,
+    pub dedup: bool,
+    pub collapse_duplicates: Option<usize>,
+    pub k: usize,
+    pub debruijn: bool,
+    // Minimizer window of the sparse graph, see utils::sparse_windows
+    pub sparse: Option<usize>,
+    // Build a succinct graph instead, see boss::Boss
+    pub boss: bool,
+    // Assemble both strands as one graph keyed by canonical k-mers
+    pub canonical: bool,
+    // Keep the nodes in a table indexed by k-mer, see Nodes::dense
+    pub dense_nodes: bool,
+    // Leave sequencing errors out of the graph, see utils::kmerize_repeated and utils::drop_singleton_ends
+    pub drop_singletons: bool,
+    // Same for k-mers seen fewer than this many times, counted exactly or in a sketch of this many MB
+    pub min_kmer_count: Option<usize>,
+    pub count_sketch: Option<usize>,
+    // Count k-mers on disk with this many MB of memory, in runs under tmp_dir, see external::kmerize
+    pub memory_limit: Option<usize>,
+    pub tmp_dir: Option<PathBuf>,
+    // Keep the graph in mapped files under tmp_dir, see disk_graph::DiskGraph
+    pub disk_graph: bool,
+    // Hash nodes with SipHash, see NodeHasher
+    pub siphash: bool,
+    // Override the lengths of reads to keep, which default to what the graph can use
+    pub min_read_len: Option<usize>,
+    pub max_read_len: Option<usize>,
+    pub format: Option<utils::Format>,
+    // Homopolymer compressed assembly
+    pub hpc: bool,
+    pub strict: bool,
+    pub interleaved: bool,
+    // Paired-end mates in two files, record n of one being the mate of record n of the other
+    pub pe1: Option<PathBuf>,
+    pub pe2: Option<PathBuf>,
+    // Times a dropped URL download is resumed
+    pub http_retries: u32,
+    // Read the FASTA infiles through a memory map, keeping reads as slices of it
+    pub mmap: bool,
+    // The infiles are k-mer spectra rather than reads
+    pub spectrum: bool,
+    // Load the graph from GFA instead of building it from reads
+    pub from_gfa: Option<PathBuf>,
+    // Write the built graph to a file, or load it from one, see Assembler::save_graph
+    pub save_graph: Option<PathBuf>,
+    pub load_graph: Option<PathBuf>,
+    pub min_overlap: usize,
+    pub min_overlap_frac: f32,
+    // Merge contigs with Assembler::merge_string_graph instead of merge_contigs
+    pub string_graph: bool,
+    pub stages: Option<Vec<String>>,
+    pub fai: bool,
+    pub stats_json: Option<PathBuf>,
+    pub copy_number: bool,
+    pub split_output_by_length: Option<Vec<usize>>,
+    pub provenance: bool,
+    pub break_points: Option<PathBuf>,
+    pub near_miss_report: Option<PathBuf>,
+    pub merge_metrics: Option<PathBuf>,
+    pub export_graph_tables: Option<PathBuf>,
+    // Write the unitigs of the graph as GFA, see unitig::compact
+    pub unitig_gfa: Option<PathBuf>,
+    // Remove links supported by fewer reads, see Assembler::remove_weak_links
+    pub min_edge_coverage: Option<usize>,
+    // Remove tips up to this many bp with at most this coverage, see Assembler::clip_tips
+    pub clip_tips: Option<usize>,
+    pub tip_max_cov: Option<f32>,
+    // Pop bubbles with branches up to this many bp, see Assembler::pop_bubbles
+    pub pop_bubbles: Option<usize>,
+    pub bubble_report: Option<PathBuf>,
+    // Every infile is a sample whose edges are tagged, see Assembler::add_colors
+    pub colors: bool,
+    // Record the reads of every edge, see Assembler::add_read_ids
+    pub read_provenance: bool,
+    // Write the degree balance of every component before traversal, see Assembler::eulerian
+    pub eulerian_report: Option<PathBuf>,
+    // How a walk picks the next edge at a branch, see Assembler::traversal
+    pub traversal: TraversalStrategy,
+    // Steps a dead-ended walk may take back, see Assembler::backtrack
+    pub backtrack: usize,
+    // Walks tried from every start node, see Assembler::attempts
+    pub attempts: usize,
+    // Walk from all start nodes at once, see Assembler::parallel
+    pub parallel_walks: bool,
+    // Commit parallel walks in start node order, so the output is the same for any thread count, see
+    // Assembler::deterministic
+    pub deterministic_walks: bool,
+    // Walk the graph one component at a time, see Assembler::components
+    pub components: bool,
+    // Assemble the components at once, see Assembler::assemble_components_par
+    pub parallel_components: bool,
+    pub clean_iterations: usize,
+    pub clean_decay: f32,
+    pub clean_min_weight: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            infiles: vec![PathBuf::from("data/YeastReads.fasta")],
+            outfile: PathBuf::from("cont.fasta"),
+            sample_edges: None,
+            subsample: None,
+            max_reads: None,
+            seed: 42,
+            record_run: false,
+            taxonomy_db: None,
+            paranoid: false,
+            async_io: false,
+            n_policy: NPolicy::Skip,
+            soft_masked: SoftMask::Keep,
+            adapters: None,
+            sliding_window: None,
+            split_n_runs: None,
+            dedup: false,
+            collapse_duplicates: None,
+            k: 15,
+            debruijn: false,
+            sparse: None,
+            boss: false,
+            canonical: false,
+            dense_nodes: false,
+            drop_singletons: false,
+            min_kmer_count: None,
+            count_sketch: None,
+            memory_limit: None,
+            tmp_dir: None,
+            disk_graph: false,
+            siphash: false,
+            min_read_len: None,
+            max_read_len: None,
+            format: None,
+            hpc: false,
+            strict: false,
+            interleaved: false,
+            pe1: None,
+            pe2: None,
+            http_retries: 3,
+            mmap: false,
+            spectrum: false,
+            from_gfa: None,
+            save_graph: None,
+            load_graph: None,
+            min_overlap: 15,
+            min_overlap_frac: 0.0,
+            string_graph: false,
+            stages: None,
+            fai: false,
+            stats_json: None,
+            copy_number: false,
+            split_output_by_length: None,
+            provenance: false,
+            break_points: None,
+            near_miss_report: None,
+            merge_metrics: None,
+            export_graph_tables: None,
+            unitig_gfa: None,
+            min_edge_coverage: None,
+            clip_tips: None,
+            tip_max_cov: None,
+            pop_bubbles: None,
+            bubble_report: None,
+            colors: false,
+            read_provenance: false,
+            eulerian_report: None,
+            traversal: TraversalStrategy::FirstAvailable,
+            backtrack: 0,
+            attempts: 1,
+            parallel_walks: false,
+            deterministic_walks: false,
+            components: false,
+            parallel_components: false,
+            clean_iterations: 0,
+            clean_decay: 0.5,
+            clean_min_weight: 0.2,
+        }
+    }
+}
+
+impl Config {
+    // Parse the command line arguments
+    // Arguments are read as OsStrings so file names do not have to be valid UTF-8
+    pub fn from_args() -> Self {
+        let mut config = Config::default();
+        let mut positional: Vec<PathBuf> = Vec::new();
+        let mut outfile = None;
+        let mut args = env::args_os().skip(1).peekable();
+        // `sbh assemble ...` is the same as `sbh ...`
+        if args.peek().is_some_and(|a| a == "assemble") { args.next(); }
+        while let Some(os_arg) = args.next() {
+            let arg = os_arg.to_string_lossy().into_owned();
+            match arg.as_str() {
+                "-o" | "--output" => outfile = Some(Self::value(&arg, args.next()).into()),
+                "--sample-edges" => config.sample_edges = Some(Self::parse(&arg, args.next())),
+                "--subsample" => config.subsample = Some(Self::parse(&arg, args.next())),
+                "--max-reads" => config.max_reads = Some(Self::parse(&arg, args.next())),
+                "--seed" => config.seed = Self::parse(&arg, args.next()),
+                "--record-run" if cfg!(feature = "runs") => config.record_run = true,
+                "--record-run" => Self::fatal("--record-run needs a build with the runs feature."),
+                "--taxonomy-db" => config.taxonomy_db = Some(Self::value(&arg, args.next()).into()),
+                "--paranoid" => config.paranoid = true,
+                "--async-io" if cfg!(feature = "async-io") => config.async_io = true,
+                "--async-io" => Self::fatal("--async-io needs a build with the async-io feature: cargo build --release --features async-io"),
+                "--n-policy" => config.n_policy = Self::parse(&arg, args.next()),
+                "--soft-masked" => config.soft_masked = Self::parse(&arg, args.next()),
+                "--adapters" => config.adapters = Some(Self::value(&arg, args.next()).into()),
+                "--sliding-window" => config.sliding_window = Some(Self::parse(&arg, args.next())),
+                "--split-n-runs" => config.split_n_runs = Some(Self::parse(&arg, args.next())),
+                "--dedup" => config.dedup = true,
+                "--collapse-duplicates" => config.collapse_duplicates = Some(Self::parse(&arg, args.next())),
+                "--kmer-size" => config.k = Self::parse(&arg, args.next()),
+                "--debruijn" => config.debruijn = true,
+                "--sparse" => config.sparse = Some(Self::parse(&arg, args.next())),
+                "--canonical" => config.canonical = true,
+                "--dense-nodes" => config.dense_nodes = true,
+                "--drop-singletons" => config.drop_singletons = true,
+                "--min-kmer-count" => config.min_kmer_count = Some(Self::parse(&arg, args.next())),
+                "--count-sketch" => config.count_sketch = Some(Self::parse(&arg, args.next())),
+                "--memory-limit" => config.memory_limit = Some(Self::parse(&arg, args.next())),
+                "--tmp-dir" => config.tmp_dir = Some(Self::value(&arg, args.next()).into()),
+                "--siphash" => config.siphash = true,
+                "--min-read-len" => config.min_read_len = Some(Self::parse(&arg, args.next())),
+                "--max-read-len" => config.max_read_len = Some(Self::parse(&arg, args.next())),
+                "--format" => config.format = Some(Self::parse(&arg, args.next())),
+                "--hpc" => config.hpc = true,
+                "--strict" => config.strict = true,
+                "--interleaved" => config.interleaved = true,
+                "--pe1" => config.pe1 = Some(Self::value(&arg, args.next()).into()),
+                "--pe2" => config.pe2 = Some(Self::value(&arg, args.next()).into()),
+                "--http-retries" if cfg!(feature = "http") => config.http_retries = Self::parse(&arg, args.next()),
+                "--http-retries" => Self::fatal("--http-retries needs a build with the http feature: cargo build --release --features http"),
+                "--mmap" if cfg!(feature = "mmap") => config.mmap = true,
+                "--disk-graph" if cfg!(feature = "mmap") => config.disk_graph = true,
+                "--disk-graph" => Self::fatal("--disk-graph needs a build with the mmap feature: cargo build --release --features mmap"),
+                "--boss" if cfg!(feature = "boss") => config.boss = true,
+                "--boss" => Self::fatal("--boss needs a build with the boss feature: cargo build --release --features boss"),
+                "--mmap" => Self::fatal("--mmap needs a build with the mmap feature: cargo build --release --features mmap"),
+                "--spectrum" => config.spectrum = true,
+                "--from-gfa" => config.from_gfa = Some(Self::value(&arg, args.next()).into()),
+                "--save-graph" => config.save_graph = Some(Self::value(&arg, args.next()).into()),
+                "--load-graph" => config.load_graph = Some(Self::value(&arg, args.next()).into()),
+                "--min-overlap" => config.min_overlap = Self::parse(&arg, args.next()),
+                "--min-overlap-frac" => config.min_overlap_frac = Self::parse(&arg, args.next()),
+                "--string-graph" => config.string_graph = true,
+                "--stages" => config.stages = Some(Self::value(&arg, args.next()).to_string_lossy()
+                    .split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()),
+                "--fai" => config.fai = true,
+                "--stats-json" if cfg!(feature = "stats") => config.stats_json = Some(Self::value(&arg, args.next()).into()),
+                "--stats-json" => Self::fatal("--stats-json needs a build with the stats feature."),
+                "--copy-number" => config.copy_number = true,
+                "--split-output-by-length" => {
+                    let val = Self::value(&arg, args.next()).to_string_lossy().into_owned();
+                    let bounds = val.split(',').map(|b| b.trim().parse().ok()).collect::<Option<Vec<usize>>>();
+                    match bounds {
+                        Some(b) if !b.is_empty() && b.windows(2).all(|w| w[0] < w[1]) => config.split_output_by_length = Some(b),
+                        _ => Self::fatal(&format!("Invalid value for '{}': '{}'. Expected ascending lengths like 1000,10000.", arg, val)),
+                    }
+                }
+                "--provenance" if cfg!(feature = "explain") => config.provenance = true,
+                "--provenance" => Self::fatal("--provenance needs a build with the explain feature."),
+                "--break-points" => config.break_points = Some(Self::value(&arg, args.next()).into()),
+                "--near-miss-report" => config.near_miss_report = Some(Self::value(&arg, args.next()).into()),
+                "--merge-metrics" => config.merge_metrics = Some(Self::value(&arg, args.next()).into()),
+                "--export-graph-tables" => config.export_graph_tables = Some(Self::value(&arg, args.next()).into()),
+                "--unitig-gfa" => config.unitig_gfa = Some(Self::value(&arg, args.next()).into()),
+                "--min-edge-coverage" => config.min_edge_coverage = Some(Self::parse(&arg, args.next())),
+                "--clip-tips" => config.clip_tips = Some(Self::parse(&arg, args.next())),
+                "--tip-max-cov" => config.tip_max_cov = Some(Self::parse(&arg, args.next())),
+                "--pop-bubbles" => config.pop_bubbles = Some(Self::parse(&arg, args.next())),
+                "--bubble-report" => config.bubble_report = Some(Self::value(&arg, args.next()).into()),
+                "--colors" => config.colors = true,
+                "--read-provenance" => config.read_provenance = true,
+                "--eulerian-report" => config.eulerian_report = Some(Self::value(&arg, args.next()).into()),
+                "--traversal" => config.traversal = Self::parse(&arg, args.next()),
+                "--backtrack" => config.backtrack = Self::parse(&arg, args.next()),
+                "--attempts" => config.attempts = Self::parse(&arg, args.next()),
+                "--parallel-walks" => config.parallel_walks = true,
+                "--deterministic-walks" => config.deterministic_walks = true,
+                "--components" => config.components = true,
+                "--parallel-components" => config.parallel_components = true,
+                "--clean-iterations" => config.clean_iterations = Self::parse(&arg, args.next()),
+                "--clean-decay" => config.clean_decay = Self::parse(&arg, args.next()),
+                "--clean-min-weight" => config.clean_min_weight = Self::parse(&arg, args.next()),
+                _ if arg.starts_with("--") => Self::fatal(&format!("Unknown option: '{}'.", arg)),
+                _ => positional.push(os_arg.into()),
+            }
+        }
+        if config.pe1.is_some() != config.pe2.is_some() {
+            Self::fatal("--pe1 and --pe2 must be given together.");
+        }
+        if config.from_gfa.is_some() && (config.pe1.is_some() || config.interleaved || config.sample_edges.is_some() || config.stats_json.is_some()) {
+            Self::fatal("--from-gfa has no reads, so it cannot be used with --pe1, --interleaved, --sample-edges or --stats-json.");
+        }
+        // Reads added to a loaded graph only go through the steps that treat each read on its own
+        if config.load_graph.is_some() && (config.from_gfa.is_some() || config.spectrum || config.mmap || config.sample_edges.is_some()
+            || config.stats_json.is_some() || config.hpc || config.drop_singletons || config.min_kmer_count.is_some()
+            || config.memory_limit.is_some() || config.dense_nodes || config.siphash) {
+            Self::fatal("--load-graph does not have the reads the graph was built from, so it cannot be used with --from-gfa, --spectrum, \
+                --mmap, --sample-edges, --stats-json, --hpc, --drop-singletons, --min-kmer-count, --memory-limit, --dense-nodes or --siphash.");
+        }
+        if config.save_graph.is_some() && config.sample_edges.is_some() {
+            Self::fatal("--sample-edges only previews the graph and writes it as GFA, so it cannot be used with --save-graph.");
+        }
+        // Without -o the last of two or more positionals is the outfile, as in `sbh <infile> <outfile>`
+        let other_input = config.pe1.is_some() || config.from_gfa.is_some() || config.load_graph.is_some();
+        if outfile.is_none() && positional.len() >= if other_input { 1 } else { 2 } {
+            outfile = positional.pop();
+        }
+        if let Some(outfile) = outfile { config.outfile = outfile; }
+        if !positional.is_empty() || other_input { config.infiles = positional; }
+        if config.from_gfa.is_some() && !config.infiles.is_empty() {
+            Self::fatal("--from-gfa replaces the infiles, give only the outfile.");
+        }
+        // The mapped reads are used as they are, so nothing may rewrite or pair them
+        if config.mmap && (config.from_gfa.is_some() || config.spectrum || config.pe1.is_some() || config.interleaved
+            || config.sample_edges.is_some() || config.stats_json.is_some() || config.collapse_duplicates.is_some()
+            || config.split_n_runs.is_some() || config.n_policy != NPolicy::Skip || config.soft_masked != SoftMask::Keep || config.async_io
+            || config.adapters.is_some() || config.sliding_window.is_some() || config.subsample.is_some() || config.max_reads.is_some()
+            || config.hpc || config.drop_singletons || config.min_kmer_count.is_some() || config.memory_limit.is_some()) {
+            Self::fatal("--mmap uses the reads as they are in the file, so it cannot be used with --from-gfa, --spectrum, --pe1, --interleaved, \
+                --sample-edges, --stats-json, --collapse-duplicates, --split-n-runs, --n-policy, --soft-masked drop, --async-io, --adapters, \
+                --sliding-window, --subsample, --max-reads, --hpc, --drop-singletons, --min-kmer-count or --memory-limit.");
+        }
+        if config.spectrum && (config.from_gfa.is_some() || config.pe1.is_some() || config.interleaved || config.sample_edges.is_some() || config.stats_json.is_some()) {
+            Self::fatal("--spectrum has no reads, so it cannot be used with --from-gfa, --pe1, --interleaved, --sample-edges or --stats-json.");
+        }
+        if (config.drop_singletons || config.min_kmer_count.is_some()) && (config.from_gfa.is_some() || config.spectrum) {
+            Self::fatal("--drop-singletons and --min-kmer-count count k-mers in the reads, so they cannot be used with --from-gfa or --spectrum.");
+        }
+        if config.drop_singletons && config.min_kmer_count.is_some() {
+            Self::fatal("--drop-singletons is --min-kmer-count 2 counted in Bloom filters, give only one of them.");
+        }
+        if config.min_kmer_count.is_some_and(|n| n < 2) {
+            Self::fatal("--min-kmer-count must be at least 2.");
+        }
+        if config.count_sketch.is_some() && config.min_kmer_count.is_none() {
+            Self::fatal("--count-sketch only sizes the counts of --min-kmer-count.");
+        }
+        if config.count_sketch == Some(0) {
+            Self::fatal("--count-sketch must be at least 1 MB.");
+        }
+        if config.memory_limit.is_some() && !config.debruijn {
+            Self::fatal("--memory-limit bounds the (k+1)-mer counting of --debruijn.");
+        }
+        if config.colors && (config.from_gfa.is_some() || config.spectrum || config.load_graph.is_some() || config.pe1.is_some()
+            || config.hpc || config.subsample.is_some() || config.max_reads.is_some() || config.collapse_duplicates.is_some()
+            || config.soft_masked != SoftMask::Keep || config.split_n_runs.is_some() || config.n_policy != NPolicy::Skip) {
+            Self::fatal("--colors reads every infile again to tag its edges, so it cannot be used with --from-gfa, --spectrum, \
+                --load-graph, --pe1, or --hpc, --subsample, --max-reads, --collapse-duplicates, --soft-masked drop, --split-n-runs \
+                and --n-policy, which change the reads.");
+        }
+        if config.colors && config.infiles.iter().any(|f| utils::is_stdin(f)) {
+            Self::fatal("--colors reads every infile again, which stdin cannot be.");
+        }
+        if config.colors && config.infiles.len() > 64 {
+            Self::fatal("--colors tells at most 64 infiles apart.");
+        }
+        if config.read_provenance && (config.from_gfa.is_some() || config.spectrum || config.load_graph.is_some() || config.pe1.is_some()
+            || config.hpc || config.subsample.is_some() || config.max_reads.is_some() || config.collapse_duplicates.is_some() || config.boss
+            || config.soft_masked != SoftMask::Keep || config.split_n_runs.is_some() || config.n_policy != NPolicy::Skip) {
+            Self::fatal("--read-provenance reads every infile again to find the edges of each read, so it cannot be used with --from-gfa, \
+                --spectrum, --load-graph, --pe1, --boss, or --hpc, --subsample, --max-reads, --collapse-duplicates, --soft-masked drop, \
+                --split-n-runs and --n-policy, which change the reads.");
+        }
+        if config.read_provenance && config.infiles.iter().any(|f| utils::is_stdin(f)) {
+            Self::fatal("--read-provenance reads every infile again, which stdin cannot be.");
+        }
+        if config.sparse.is_some() && (!config.debruijn || config.spectrum || config.from_gfa.is_some()) {
+            Self::fatal("--sparse picks minimizers in the reads, so it needs --debruijn and cannot be used with --spectrum or --from-gfa.");
+        }
+        if config.sparse.is_some_and(|w| w == 0 || w > config.k) {
+            Self::fatal(&format!("--sparse must be between 1 and k = {}, so that no base falls between two nodes.", config.k));
+        }
+        if config.sparse.is_some() && config.memory_limit.is_some() {
+            Self::fatal("--memory-limit counts (k+1)-mers, so it cannot be used with --sparse.");
+        }
+        if config.boss && (!config.debruijn || config.spectrum || config.from_gfa.is_some() || config.sparse.is_some()) {
+            Self::fatal("--boss is built from (k+1)-mers, so it needs --debruijn and cannot be used with --spectrum, --from-gfa or --sparse.");
+        }
+        if config.boss && (config.load_graph.is_some() || config.save_graph.is_some() || config.disk_graph || config.colors
+            || config.interleaved || config.pe1.is_some() || config.hpc || config.sample_edges.is_some()) {
+            Self::fatal("--boss keeps no hashed graph, so it cannot be used with --load-graph, --save-graph, --disk-graph, --colors, \
+                --interleaved, --pe1, --hpc or --sample-edges.");
+        }
+        if config.boss && (config.min_edge_coverage.is_some() || config.clip_tips.is_some() || config.pop_bubbles.is_some()
+            || config.clean_iterations > 0 || config.unitig_gfa.is_some() || config.export_graph_tables.is_some()
+            || config.eulerian_report.is_some() || config.traversal != TraversalStrategy::FirstAvailable || config.backtrack > 0
+            || config.attempts > 1 || config.parallel_walks || config.components || config.parallel_components
+            || config.stages.is_some()) {
+            Self::fatal("--boss spells the unitigs of the graph as they are, so it cannot be used with --min-edge-coverage, \
+                --clip-tips, --pop-bubbles, --clean-iterations, --unitig-gfa, --export-graph-tables, --eulerian-report, \
+                --traversal, --backtrack, --attempts, --parallel-walks, --components, --parallel-components or --stages.");
+        }
+        if config.memory_limit.is_some() && config.count_sketch.is_some() {
+            Self::fatal("--memory-limit counts exactly on disk, so it cannot be used with --count-sketch.");
+        }
+        if config.memory_limit == Some(0) {
+            Self::fatal("--memory-limit must be at least 1 MB.");
+        }
+        if config.tmp_dir.is_some() && config.memory_limit.is_none() && !config.disk_graph {
+            Self::fatal("--tmp-dir is where --memory-limit writes its runs and --disk-graph its graph.");
+        }
+        if !cfg!(feature = "http") && config.inputs().iter().any(|f| utils::is_url(f)) {
+            Self::fatal("URL infiles need a build with the http feature: cargo build --release --features http");
+        }
+        if config.inputs().iter().filter(|f| utils::is_stdin(f)).count() > 1 {
+            Self::fatal("stdin (-) can only be given once as an infile.");
+        }
+        if config.sample_edges.is_some_and(|f| !(0.0..=1.0).contains(&f)) {
+            Self::fatal("--sample-edges must be between 0 and 1.");
+        }
+        if config.dedup && config.collapse_duplicates.is_some() {
+            Self::fatal("--collapse-duplicates already collapses exact duplicates, leave out --dedup.");
+        }
+        if config.subsample.is_some_and(|f| !(f > 0.0 && f <= 1.0)) {
+            Self::fatal("--subsample must be above 0 and at most 1.");
+        }
+        if config.max_reads == Some(0) {
+            Self::fatal("--max-reads must be at least 1.");
+        }
+        if config.hpc && (config.from_gfa.is_some() || config.spectrum) {
+            Self::fatal("--hpc compresses the reads and restores the runs from them, so it cannot be used with --from-gfa or --spectrum.");
+        }
+        if config.hpc && config.stats_json.is_some() {
+            Self::fatal("--stats-json compares the contigs with the reads, which --hpc compresses, so they cannot be used together.");
+        }
+        if config.split_n_runs == Some(0) {
+            Self::fatal("--split-n-runs must be at least 1.");
+        }
+        if config.debruijn && matches!(config.n_policy, NPolicy::Expand(_)) {
+            Self::fatal("--n-policy expand needs whole k+1 to 2k bp reads and does not work with --debruijn.");
+        }
+        if config.k == 0 {
+            Self::fatal("--kmer-size must be at least 1.");
+        }
+        // Above 31 bp the nodes no longer fit a u64, which these options pack them or their (k+1)-mers into
+        let packed = [(config.from_gfa.is_some(), "--from-gfa"), (config.boss, "--boss"), (config.memory_limit.is_some(), "--memory-limit"),
+            (config.hpc, "--hpc"), (config.sample_edges.is_some(), "--sample-edges"), (config.provenance, "--provenance"),
+            (config.stages.is_some(), "--stages")];
+        if let Some((_, flag)) = packed.iter().find(|(given, _)| *given).filter(|_| config.k > u64::MAX_K) {
+            Self::fatal(&format!("{} packs the nodes into a u64, so it needs a --kmer-size of at most {}.", flag, u64::MAX_K));
+        }
+        // The k of a spectrum is only known once it is read
+        if config.dense_nodes && !config.spectrum && config.k > DENSE_MAX_K {
+            Self::fatal(&format!("--dense-nodes needs a --kmer-size of at most {}.", DENSE_MAX_K));
+        }
+        if config.dense_nodes && config.from_gfa.is_some() {
+            Self::fatal("--dense-nodes only applies to graphs built from reads, not to --from-gfa.");
+        }
+        if config.siphash && config.dense_nodes {
+            Self::fatal("--siphash and --dense-nodes cannot be combined, as --dense-nodes does not hash nodes.");
+        }
+        if config.siphash && config.from_gfa.is_some() {
+            Self::fatal("--siphash only applies to graphs built from reads, not to --from-gfa.");
+        }
+        // A read needs a prefix and a suffix node, and without --debruijn the bases between them would be lost
+        if config.min_read_len.is_some_and(|n| n <= config.k) {
+            Self::fatal(&format!("--min-read-len must be at least k+1 = {}.", config.k + 1));
+        }
+        if !config.debruijn && config.max_read_len.is_some_and(|n| n > 2 * config.k) {
+            Self::fatal(&format!("--max-read-len can be at most 2k = {} without --debruijn.", 2 * config.k));
+        }
+        if let TraversalStrategy::Random(seed) = &mut config.traversal { *seed = config.seed; }
+        if matches!(config.traversal, TraversalStrategy::LongestExtensionLookahead(depth) if depth > LOOKAHEAD_MAX_DEPTH) {
+            Self::fatal(&format!("--traversal lookahead:<depth> must be at most {}, as a pick takes up to 4^depth steps.", LOOKAHEAD_MAX_DEPTH));
+        }
+        if config.read_lens().is_empty() {
+            Self::fatal("--min-read-len must not be above --max-read-len.");
+        }
+        if config.tip_max_cov.is_some() && config.clip_tips.is_none() && !config.runs_stage("clip-tips") {
+            Self::fatal("--tip-max-cov only applies to --clip-tips or the clip-tips stage.");
+        }
+        if config.min_edge_coverage.is_some_and(|n| n < 2) {
+            Self::fatal("--min-edge-coverage must be at least 2, as every link has a read.");
+        }
+        if config.parallel_walks && (config.backtrack > 0 || config.attempts > 1) {
+            Self::fatal("--backtrack and --attempts take back the steps of a walk one at a time, so they cannot be used with --parallel-walks.");
+        }
+        if config.deterministic_walks && !config.parallel_walks {
+            Self::fatal("--deterministic-walks orders the links --parallel-walks claims, and serial walks are deterministic already.");
+        }
+        if config.components && config.parallel_components {
+            Self::fatal("--parallel-components already assembles one component at a time, leave out --components.");
+        }
+        if config.components && config.runs_stage("components") {
+            Self::fatal("--components and the components stage both assemble one component at a time, leave out --components.");
+        }
+        if config.parallel_components && config.runs_stage("parallel-components") {
+            Self::fatal("--parallel-components and the parallel-components stage both assemble the components at once, \
+                leave out --parallel-components.");
+        }
+        if config.attempts == 0 {
+            Self::fatal("--attempts must be at least 1.");
+        }
+        if config.clip_tips == Some(0) {
+            Self::fatal("--clip-tips must be at least 1 bp.");
+        }
+        if config.bubble_report.is_some() && config.pop_bubbles.is_none() && !config.runs_stage("pop-bubbles") {
+            Self::fatal("--bubble-report lists what --pop-bubbles or the pop-bubbles stage pops.");
+        }
+        if config.pop_bubbles == Some(0) {
+            Self::fatal("--pop-bubbles must be at least 1 bp.");
+        }
+        if !(0.0..=1.0).contains(&config.min_overlap_frac) {
+            Self::fatal("--min-overlap-frac must be between 0 and 1.");
+        }
+        config
+    }
+
+    // Lengths of the reads to keep. In de Bruijn mode every read with at least one window is used
+    // unless --min-read-len or --max-read-len narrow it down
+    pub fn read_lens(&self) -> RangeInclusive<usize> {
+        let lens = if self.debruijn { self.k + 1..=usize::MAX } else { utils::read_lens(self.k) };
+        self.min_read_len.unwrap_or(*lens.start())..=self.max_read_len.unwrap_or(*lens.end())
+    }
+
+    // Every file the input comes from: the GFA graph, or the graph file of --load-graph, the infiles and then the paired-end files
+    pub fn inputs(&self) -> Vec<PathBuf> {
+        if let Some(gfa) = &self.from_gfa { return vec![gfa.clone()]; }
+        self.load_graph.iter().chain(&self.infiles).chain(&self.pe1).chain(&self.pe2).cloned().collect()
+    }
+
+    // With --hpc the reads get shorter after they are read, so the longest length is only checked then
+    pub fn read_options(&self) -> utils::ReadOptions {
+        let lens = if self.hpc { *self.read_lens().start()..=usize::MAX } else { self.read_lens() };
+        utils::ReadOptions { lens, strict: self.strict, names: self.interleaved, format: self.format }
+    }
+
+    // How often a k-mer has to be seen to be kept, with --drop-singletons or --min-kmer-count
+    pub fn kmer_threshold(&self) -> Option<usize> {
+        if self.drop_singletons { Some(2) } else { self.min_kmer_count }
+    }
+
+    // What counts k-mers for --drop-singletons or --min-kmer-count, sized for `items` k-mers, and the count a k-mer
+    // needs to be kept. None if neither is given
+    pub fn kmer_counter(&self, items: usize) -> Option<(Box<dyn KmerCounter>, usize)> {
+        if self.drop_singletons { return Some((Box::new(Repeated::new(items)), 2)); }
+        let min = self.kmer_threshold()?;
+        let counter: Box<dyn KmerCounter> = match self.count_sketch {
+            Some(mb) => Box::new(CountMin::new(mb << 20)),
+            None => Box::new(HashMap::<Vec<u8>, usize>::new()),
+        };
+        Some((counter, min))
+    }
+
+    // True if --stages names this stage
+    pub fn runs_stage(&self, name: &str) -> bool {
+        self.stages.as_ref().is_some_and(|stages| stages.iter().any(|s| s == name))
+    }
+
+    // Get the value following a flag or exit if there is none
+    fn value(flag: &str, val: Option<OsString>) -> OsString {
+        match val {
+            Some(v) => v,
+            None => Self::fatal(&format!("Option '{}' requires a value.", flag)),
+        }
+    }
+
+    // Get the value following a flag and parse it or exit if it is invalid
+    fn parse<T: FromStr>(flag: &str, val: Option<OsString>) -> T {
+        let val = Self::value(flag, val).to_string_lossy().into_owned();
+        match val.parse() {
+            Ok(v) => v,
+            Err(_) => Self::fatal(&format!("Invalid value for '{}': '{}'.", flag, val)),
+        }
+    }
+
+    fn fatal(msg: &str) -> ! {
+        eprintln!("\x1b[31mFATAL: {}\x1b[0m", msg);
+        std::process::exit(1);
+    }
+}
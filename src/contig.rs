@@ -0,0 +1,176 @@
+use crate::simd;
+
+// Where a contig's sequence came from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Path(usize),
+    Cycle(usize),
+    // A cycle walk that never returned to its start node
+    Abandoned(usize),
+}
+
+impl Source {
+    // The same walk once the given numbers of paths, cycles and abandoned walks are put before those of its kind
+    pub fn offset(self, [paths, cycles, abandoned]: [usize; 3]) -> Self {
+        match self {
+            Source::Path(i) => Source::Path(paths + i),
+            Source::Cycle(i) => Source::Cycle(cycles + i),
+            Source::Abandoned(i) => Source::Abandoned(abandoned + i),
+        }
+    }
+}
+
+// A merge of two contigs. left_len and right_len are the lengths before merging
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Join {
+    pub overlap: usize,
+    pub left_len: usize,
+    pub right_len: usize,
+}
+
+// Why a walk started or stopped at a node
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum EndReason {
+    // Not spelled from a walk
+    #[default]
+    Unknown,
+    // A path start, the node has more outgoing than incoming reads
+    Source,
+    // A cycle walk start, the node has both incoming and outgoing reads
+    CycleStart,
+    // The node has no outgoing edges
+    DeadEnd,
+    // Every successor was already consumed by another walk
+    Exhausted,
+    // The walk got back to its start node
+    Closed,
+}
+
+impl EndReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndReason::Unknown => "unknown",
+            EndReason::Source => "source",
+            EndReason::CycleStart => "cycle_start",
+            EndReason::DeadEnd => "dead_end",
+            EndReason::Exhausted => "exhausted",
+            EndReason::Closed => "closed",
+        }
+    }
+}
+
+// The graph node a contig end was spelled from and why the walk ended there
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BreakPoint {
+    pub node: u64,
+    pub reason: EndReason,
+}
+
+// Bases start..end of a contig spelled from edges of the read with this index, see Assembler::read_spans
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReadSpan {
+    pub read: u32,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ReadSpan {
+    // Join the spans of each read that overlap or touch, and sort them by where they start
+    pub fn join(mut spans: Vec<ReadSpan>) -> Vec<ReadSpan> {
+        spans.sort_unstable_by_key(|s| (s.read, s.start));
+        let mut joined: Vec<ReadSpan> = vec![];
+        for span in spans {
+            match joined.last_mut() {
+                Some(last) if last.read == span.read && span.start <= last.end => last.end = last.end.max(span.end),
+                _ => joined.push(span),
+            }
+        }
+        joined.sort_by_key(|s| (s.start, s.read));
+        joined
+    }
+}
+
+// A contig and everything known about how it was built
+#[derive(Clone, Debug, Default)]
+pub struct Contig {
+    pub seq: Vec<u8>,
+    // Mean number of reads supporting each node the contig was spelled from
+    pub coverage: f32,
+    // Mean and lowest number of reads supporting each edge it was spelled from. A single weakly supported edge is
+    // enough to join two unrelated walks
+    pub edge_coverage: f32,
+    pub min_edge_coverage: usize,
+    pub circular: bool,
+    // The paths and cycles that were spelled into this contig, in sequence order
+    pub provenance: Vec<Source>,
+    // Every merge that went into this contig, oldest first
+    pub joins: Vec<Join>,
+    // The left and right ends of the contig. Merging keeps the outer ends
+    pub ends: [BreakPoint; 2],
+    // The samples that have every edge the contig was spelled from and those that have any, with --colors
+    pub colors: u64,
+    pub any_colors: u64,
+    // Where the reads that have its edges are along it, with --read-provenance
+    pub reads: Vec<ReadSpan>,
+}
+
+impl Contig {
+    pub fn new(seq: Vec<u8>, coverage: f32, circular: bool, source: Source, ends: [BreakPoint; 2]) -> Self {
+        Contig { seq, coverage, circular, provenance: vec![source], ends, ..Contig::default() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.seq.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seq.is_empty()
+    }
+
+    // Estimated number of copies in the genome given the coverage of single copy sequence
+    pub fn copy_number(&self, modal_coverage: f32) -> f32 {
+        if modal_coverage > 0.0 { self.coverage / modal_coverage } else { 0.0 }
+    }
+
+    // The same contig read from the other strand
+    pub fn revcomp(&self) -> Self {
+        let joins = self.joins.iter().rev().map(|j| Join { overlap: j.overlap, left_len: j.right_len, right_len: j.left_len }).collect();
+        Contig {
+            seq: simd::revcomp(&self.seq),
+            coverage: self.coverage,
+            edge_coverage: self.edge_coverage,
+            min_edge_coverage: self.min_edge_coverage,
+            circular: self.circular,
+            provenance: self.provenance.iter().rev().copied().collect(),
+            joins,
+            ends: [self.ends[1], self.ends[0]],
+            colors: self.colors,
+            any_colors: self.any_colors,
+            reads: ReadSpan::join(self.reads.iter().map(|s| ReadSpan { read: s.read, start: self.len() - s.end, end: self.len() - s.start }).collect()),
+        }
+    }
+
+    // Combine left and right, which overlap by `overlap` nucleotides, into a single contig
+    pub fn merge(left: &Contig, right: &Contig, overlap: usize) -> Self {
+        let seq = [&left.seq[..left.len() - overlap], &right.seq[..]].concat();
+        let (l, r) = (left.len() as f32, right.len() as f32);
+        let mut joins = [&left.joins[..], &right.joins[..]].concat();
+        joins.push(Join { overlap, left_len: left.len(), right_len: right.len() });
+        Contig {
+            seq,
+            coverage: (left.coverage * l + right.coverage * r) / (l + r).max(1.0),
+            edge_coverage: (left.edge_coverage * l + right.edge_coverage * r) / (l + r).max(1.0),
+            min_edge_coverage: left.min_edge_coverage.min(right.min_edge_coverage),
+            circular: false,
+            provenance: [&left.provenance[..], &right.provenance[..]].concat(),
+            joins,
+            ends: [left.ends[0], right.ends[1]],
+            colors: left.colors & right.colors,
+            any_colors: left.any_colors | right.any_colors,
+            // The left contig is where it was, the right one starts where the overlap does
+            reads: ReadSpan::join(left.reads.iter().copied().chain(right.reads.iter().map(|s| {
+                ReadSpan { read: s.read, start: s.start + left.len() - overlap, end: s.end + left.len() - overlap }
+            })).collect()),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// How often k-mers were seen, for --min-kmer-count and --drop-singletons. Counting exactly keeps every distinct
+// k-mer in a table, which on deep data is mostly sequencing errors. The approximate counters take a fixed amount of
+// memory instead and never count a k-mer lower than it was seen, so whatever passes them can still be counted exactly
+use crate::bloom::{ self, Repeated };
+use std::collections::HashMap;
+
+pub trait KmerCounter {
+    // Record a k-mer that stands for `weight` copies
+    fn add(&mut self, kmer: &[u8], weight: usize);
+
+    // How often the k-mer was seen, or more for an approximate counter
+    fn count(&self, kmer: &[u8]) -> usize;
+}
+
+impl KmerCounter for HashMap<Vec<u8>, usize> {
+    fn add(&mut self, kmer: &[u8], weight: usize) {
+        match self.get_mut(kmer) {
+            Some(n) => *n += weight,
+            None => { self.insert(kmer.to_vec(), weight); }
+        }
+    }
+
+    fn count(&self, kmer: &[u8]) -> usize {
+        self.get(kmer).copied().unwrap_or(0)
+    }
+}
+
+// Only tells 0, 1 and 2 or more apart, in two Bloom filters
+impl KmerCounter for Repeated {
+    fn add(&mut self, kmer: &[u8], weight: usize) {
+        Repeated::add(self, kmer, weight);
+    }
+
+    fn count(&self, kmer: &[u8]) -> usize {
+        if self.contains(kmer) { 2 } else { usize::from(self.seen(kmer)) }
+    }
+}
+
+// Rows of a count-min sketch. Each k-mer has a counter in every row and its count is the lowest of them
+const ROWS: usize = 4;
+
+// A count-min sketch with conservative update: only the counters that hold the lowest count are raised, which keeps
+// the overcount from k-mers that share counters low. Counters stop at u32::MAX
+pub struct CountMin {
+    counters: Vec<u32>,
+    width: usize,
+}
+
+impl CountMin {
+    // A sketch that takes about `bytes` of memory
+    pub fn new(bytes: usize) -> Self {
+        let width = (bytes / (ROWS * size_of::<u32>())).max(1);
+        CountMin { counters: vec![0; ROWS * width], width }
+    }
+
+    // The counter of the k-mer in each row
+    fn cells(&self, kmer: &[u8]) -> [usize; ROWS] {
+        let mut cells = [0; ROWS];
+        for (row, col) in bloom::positions(ROWS as u64, self.width as u64, kmer).enumerate() {
+            cells[row] = row * self.width + col as usize;
+        }
+        cells
+    }
+}
+
+impl KmerCounter for CountMin {
+    fn add(&mut self, kmer: &[u8], weight: usize) {
+        let cells = self.cells(kmer);
+        let count = cells.iter().map(|&cell| self.counters[cell]).min().unwrap_or(0);
+        let raised = count.saturating_add(u32::try_from(weight).unwrap_or(u32::MAX));
+        for cell in cells {
+            self.counters[cell] = self.counters[cell].max(raised);
+        }
+    }
+
+    fn count(&self, kmer: &[u8]) -> usize {
+        self.cells(kmer).into_iter().map(|cell| self.counters[cell] as usize).min().unwrap_or(0)
+    }
+}
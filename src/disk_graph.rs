@@ -0,0 +1,120 @@
+// Graph storage in memory mapped files for --disk-graph. The arrays are written to files in --tmp-dir and mapped back,
+// so the kernel can page out the parts of a large graph that are not being walked instead of the run going out of memory
+use crate::{ sbh_assembler::{ Edge, GraphStorage }, utils };
+use memmap2::MmapMut;
+use std::{
+    fs::{ self, OpenOptions },
+    io,
+    marker::PhantomData,
+    path::{ Path, PathBuf },
+    ptr,
+};
+
+// Types that can be written to a file as they are in memory and read back from it. Only implement it for types
+// that have no pointers and need no drop, and whose every bit pattern is a valid value
+trait Plain {}
+
+impl Plain for usize {}
+impl Plain for Edge {}
+
+// A slice of T kept in a mapped file, which is deleted when it is dropped
+struct Mapped<T: Plain> {
+    map: MmapMut,
+    len: usize,
+    path: PathBuf,
+    items: PhantomData<T>,
+}
+
+impl<T: Plain> Mapped<T> {
+    fn write(path: PathBuf, items: Vec<T>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        // A map cannot be empty
+        file.set_len((items.len() * size_of::<T>()).max(1) as u64)?;
+        // Safety: the file was just created for this map and nothing else opens it
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        // Safety: the map is page aligned and holds items.len() values of T. Plain types need no drop, so the Vec
+        // can free its buffer once the values are copied
+        unsafe { ptr::copy_nonoverlapping(items.as_ptr(), map.as_mut_ptr() as *mut T, items.len()) };
+        Ok(Mapped { map, len: items.len(), path, items: PhantomData })
+    }
+
+    fn as_slice(&self) -> &[T] {
+        // Safety: the map holds len values of T, written by write
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr() as *const T, self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        // Safety: as in as_slice, and the map is borrowed mutably
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_mut_ptr() as *mut T, self.len) }
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        let mut items = Vec::with_capacity(self.len);
+        // Safety: copies len values of a Plain type into a buffer of that capacity
+        unsafe {
+            ptr::copy_nonoverlapping(self.as_slice().as_ptr(), items.as_mut_ptr(), self.len);
+            items.set_len(self.len);
+        }
+        items
+    }
+}
+
+impl<T: Plain> Drop for Mapped<T> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub struct DiskGraph {
+    dir: PathBuf,
+    offsets: Option<Mapped<usize>>,
+    edges: Option<Mapped<Edge>>,
+    // Times the graph was stored, so the files of each store get new names
+    generation: usize,
+}
+
+impl DiskGraph {
+    // An empty storage writing its files in a new directory under `dir`
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        let dir = utils::scratch_dir(dir, "sbh-graph");
+        fs::create_dir_all(&dir)?;
+        Ok(DiskGraph { dir, offsets: None, edges: None, generation: 0 })
+    }
+}
+
+impl Drop for DiskGraph {
+    fn drop(&mut self) {
+        self.offsets = None;
+        self.edges = None;
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+impl GraphStorage for DiskGraph {
+    fn offsets(&self) -> &[usize] {
+        self.offsets.as_ref().map_or(&[0], |m| m.as_slice())
+    }
+
+    fn edges(&self) -> &[Edge] {
+        self.edges.as_ref().map_or(&[], |m| m.as_slice())
+    }
+
+    fn edges_mut(&mut self) -> &mut [Edge] {
+        self.edges.as_mut().map_or(&mut [], |m| m.as_mut_slice())
+    }
+
+    fn store(&mut self, offsets: Vec<usize>, edges: Vec<Edge>) -> io::Result<()> {
+        self.generation += 1;
+        let offsets = Mapped::write(self.dir.join(format!("offsets-{}.bin", self.generation)), offsets)?;
+        let edges = Mapped::write(self.dir.join(format!("edges-{}.bin", self.generation)), edges)?;
+        self.offsets = Some(offsets);
+        self.edges = Some(edges);
+        Ok(())
+    }
+
+    fn take(&mut self) -> (Vec<usize>, Vec<Edge>) {
+        let offsets = self.offsets.take().map_or(vec![0], |m| m.to_vec());
+        let edges = self.edges.take().map_or(vec![], |m| m.to_vec());
+        (offsets, edges)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{ contig::Source, sbh_assembler::Assembler, utils };
+use serde_json::{ json, Value };
+use std::{
+    ffi::OsString,
+    fs::{ self, File },
+    io::{ BufRead, BufReader, BufWriter, Write },
+    path::{ Path, PathBuf },
+};
+
+pub const PROVENANCE_SUFFIX: &str = ".provenance.jsonl";
+
+pub const USAGE: &str = "Usage: sbh explain --contig <name> (--run-dir <dir> | --provenance <file>)
+    --contig <name>         contig to explain, e.g. sequence12, contig_12 or 12
+    --run-dir <dir>         directory holding the <outfile>.provenance.jsonl of a run made with --provenance
+    --provenance <file>     provenance file to read directly";
+
+// Write one JSON line per contig describing how it was built. Contig names match the ones written by cont2file
+// Must be called before the paths and cycles are cleared since the coverage profiles are read from them
+pub fn write_provenance(fname: &Path, ass: &Assembler) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(fname)?);
+    for (i, contig) in ass.contigs.iter().enumerate() {
+        let sources: Vec<Value> = contig.provenance.iter().map(|source| {
+            let (kind, index, walk) = match *source {
+                Source::Path(i) => ("path", i, ass.paths.get(i)),
+                Source::Cycle(i) => ("cycle", i, ass.cycles.get(i)),
+                Source::Abandoned(i) => ("abandoned", i, ass.abandoned.get(i)),
+            };
+            let profile: Vec<usize> = walk.map(|w| w.iter().map(|&id| ass.nodes[id].cov).collect()).unwrap_or_default();
+            let edges = walk.map(|w| ass.walk_coverage(w)).unwrap_or_default();
+            json!({ "kind": kind, "index": index, "nodes": profile.len(), "coverage": profile, "edge_coverage": edges })
+        }).collect();
+        let joins: Vec<Value> = contig.joins.iter()
+            .map(|j| json!({ "overlap": j.overlap, "left_len": j.left_len, "right_len": j.right_len }))
+            .collect();
+        let ends: Vec<Value> = contig.ends.iter().map(|e| json!({
+            "node": e.node,
+            "kmer": String::from_utf8_lossy(&utils::idx2vec(e.node, ass.k)),
+            "reason": e.reason.as_str(),
+        })).collect();
+        writeln!(writer, "{}", json!({
+            "name": format!("sequence{}", i + 1),
+            "length": contig.len(),
+            "coverage": contig.coverage,
+            "edge_coverage": contig.edge_coverage,
+            "min_edge_coverage": contig.min_edge_coverage,
+            "circular": contig.circular,
+            "sources": sources,
+            "joins": joins,
+            "ends": ends,
+        }))?;
+    }
+    writer.flush()
+}
+
+// Entry point for `sbh explain`
+pub fn explain_command(args: &[OsString]) -> Result<(), String> {
+    let mut contig = None;
+    let mut file: Option<PathBuf> = None;
+    let mut run_dir: Option<PathBuf> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or(format!("Option '{}' requires a value.", arg.to_string_lossy()));
+        match arg.to_str() {
+            Some("--contig") => contig = Some(value()?.to_string_lossy().into_owned()),
+            Some("--provenance") => file = Some(value()?.into()),
+            Some("--run-dir") => run_dir = Some(value()?.into()),
+            _ => return Err(format!("Unexpected argument: '{}'.\n{}", arg.to_string_lossy(), USAGE)),
+        }
+    }
+    let contig = contig.ok_or(USAGE)?;
+    let file = match (file, run_dir) {
+        (Some(file), _) => file,
+        (None, Some(dir)) => find_provenance(&dir)?,
+        (None, None) => return Err(USAGE.to_string()),
+    };
+    let number: usize = contig.trim_start_matches(|c: char| !c.is_ascii_digit()).parse()
+        .map_err(|_| format!("Invalid contig name: '{}'.", contig))?;
+    let name = format!("sequence{}", number);
+    let reader = BufReader::new(File::open(&file).map_err(|e| format!("Failed to open {}: {}", file.display(), e))?);
+    let record = reader.lines().map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<Value>(&line).ok())
+        .find(|r| r["name"].as_str() == Some(name.as_str()))
+        .ok_or(format!("No contig named '{}' in {}.", name, file.display()))?;
+    print_history(&record);
+    Ok(())
+}
+
+// The single provenance file in a run directory
+fn find_provenance(dir: &Path) -> Result<PathBuf, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut found: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.to_string_lossy().ends_with(PROVENANCE_SUFFIX))
+        .collect();
+    match found.len() {
+        0 => Err(format!("No *{} in {}. Was the run made with --provenance?", PROVENANCE_SUFFIX, dir.display())),
+        1 => Ok(found.remove(0)),
+        _ => Err(format!("Several *{} in {}. Pick one with --provenance.", PROVENANCE_SUFFIX, dir.display())),
+    }
+}
+
+fn print_history(record: &Value) {
+    let int = |v: &Value| v.as_u64().unwrap_or(0);
+    println!("\x1b[32m{}\x1b[0m: {} nucleotides, mean coverage {:.2}, edge coverage mean {:.2} / min {}{}",
+        record["name"].as_str().unwrap_or("?"), int(&record["length"]),
+        record["coverage"].as_f64().unwrap_or(0.0),
+        record["edge_coverage"].as_f64().unwrap_or(0.0), int(&record["min_edge_coverage"]),
+        if record["circular"].as_bool() == Some(true) { ", circular" } else { "" });
+    let empty = vec![];
+    println!("Sources, in sequence order:");
+    for source in record["sources"].as_array().unwrap_or(&empty) {
+        let profile: Vec<u64> = source["coverage"].as_array().unwrap_or(&empty).iter().map(int).collect();
+        let mean = profile.iter().sum::<u64>() as f64 / profile.len().max(1) as f64;
+        println!("\t{} {}: {} nodes, coverage min {} / mean {:.2} / max {}",
+            source["kind"].as_str().unwrap_or("?"), int(&source["index"]), profile.len(),
+            profile.iter().min().unwrap_or(&0), mean, profile.iter().max().unwrap_or(&0));
+        let profile: Vec<String> = profile.iter().map(|c| c.to_string()).collect();
+        println!("\t\tprofile: {}", profile.join(" "));
+        let edges: Vec<u64> = source["edge_coverage"].as_array().unwrap_or(&empty).iter().map(int).collect();
+        if !edges.is_empty() {
+            let edges: Vec<String> = edges.iter().map(|c| c.to_string()).collect();
+            println!("\t\tedges: {}", edges.join(" "));
+        }
+    }
+    let joins = record["joins"].as_array().unwrap_or(&empty);
+    if joins.is_empty() {
+        println!("Merges: none");
+    } else {
+        println!("Merges, oldest first:");
+        for (i, join) in joins.iter().enumerate() {
+            let (left, right, overlap) = (int(&join["left_len"]), int(&join["right_len"]), int(&join["overlap"]));
+            println!("\t{}. {} + {} overlapping by {} -> {}", i + 1, left, right, overlap, left + right - overlap);
+        }
+    }
+    println!("Ends:");
+    for (side, end) in ["left", "right"].iter().zip(record["ends"].as_array().unwrap_or(&empty)) {
+        println!("\t{:<6} node {} ({}): {}", side, int(&end["node"]),
+            end["kmer"].as_str().unwrap_or(""), end["reason"].as_str().unwrap_or("?"));
+    }
+}
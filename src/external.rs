@@ -0,0 +1,136 @@
+// Disk-backed (k+1)-mer counting for --memory-limit. Windows are packed two bits a base and collected until the
+// limit, then sorted, summed and written to a run file in --tmp-dir. The runs are merged at the end, one record of
+// each in memory at a time, so the counting takes the limit however many distinct windows the reads have
+use crate::{ simd, utils };
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{ self, File },
+    io::{ self, BufReader, BufWriter, Read, Write },
+    path::{ Path, PathBuf },
+};
+
+// A window and how often it was seen on each strand. Without --canonical only the first count is used
+type Record = (u64, [u64; 2]);
+
+// Bytes a collected window takes: its code and its weight, with the lowest bit telling the strand
+const ENTRY_BYTES: usize = 16;
+
+// The directory the runs are written to, removed with them when counting is done or fails
+struct RunDir(PathBuf);
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Sort collected windows and sum the weights of each
+fn fold(entries: &mut [(u64, u64)]) -> Vec<Record> {
+    entries.sort_unstable_by_key(|&(code, _)| code);
+    let mut records: Vec<Record> = vec![];
+    for &(code, weight) in entries.iter() {
+        if records.last().is_none_or(|&(last, _)| last != code) {
+            records.push((code, [0, 0]));
+        }
+        records.last_mut().expect("just pushed").1[(weight & 1) as usize] += weight >> 1;
+    }
+    records
+}
+
+fn write_run(path: &Path, records: &[Record]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for (code, [fwd, rev]) in records {
+        out.write_all(&code.to_le_bytes())?;
+        out.write_all(&fwd.to_le_bytes())?;
+        out.write_all(&rev.to_le_bytes())?;
+    }
+    out.flush()
+}
+
+fn read_run(path: &Path) -> io::Result<impl Iterator<Item = io::Result<Record>>> {
+    let mut input = BufReader::new(File::open(path)?);
+    Ok(std::iter::from_fn(move || {
+        let mut buf = [0u8; 24];
+        match input.read_exact(&mut buf) {
+            Ok(()) => {
+                let word = |i: usize| u64::from_le_bytes(buf[8 * i..8 * i + 8].try_into().expect("8 bytes"));
+                Some(Ok((word(0), [word(1), word(2)])))
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e)),
+        }
+    }))
+}
+
+// Same as utils::kmerize_solid with the counts kept on disk: split reads into (k+1)-mers, leaving out those seen
+// fewer than `min` times, with at most `memory` bytes of windows collected at once and the runs written under `dir`
+// With `canonical` a window and its reverse complement are counted together. Returns the windows and how many
+// occurrences were left out
+pub fn kmerize<R: AsRef<[u8]>>(reads: &[(R, usize)], k: usize, canonical: bool, min: usize, dir: &Path, memory: usize)
+    -> io::Result<utils::Filtered> {
+    let runs = RunDir(utils::scratch_dir(dir, "sbh-kmers"));
+    fs::create_dir_all(&runs.0)?;
+    let limit = (memory / ENTRY_BYTES).max(1);
+    let mut entries: Vec<(u64, u64)> = Vec::with_capacity(limit);
+    let mut files: Vec<PathBuf> = vec![];
+    for (read, weight) in reads {
+        for (code, rc) in simd::rolling(read.as_ref(), k + 1).flatten() {
+            let rc = if canonical { rc } else { code };
+            let entry = if rc < code { (rc, (*weight as u64) << 1 | 1) } else { (code, (*weight as u64) << 1) };
+            entries.push(entry);
+            if entries.len() == limit {
+                let path = runs.0.join(format!("run-{}.bin", files.len()));
+                write_run(&path, &fold(&mut entries))?;
+                files.push(path);
+                entries.clear();
+            }
+        }
+    }
+    // The last run never goes to disk
+    let mut sources: Vec<Box<dyn Iterator<Item = io::Result<Record>>>> = vec![Box::new(fold(&mut entries).into_iter().map(Ok))];
+    drop(entries);
+    for path in &files {
+        sources.push(Box::new(read_run(path)?));
+    }
+    let mut heads = BinaryHeap::new();
+    for (i, source) in sources.iter_mut().enumerate() {
+        if let Some(record) = source.next() {
+            let (code, counts) = record?;
+            heads.push(Reverse((code, i, counts)));
+        }
+    }
+    let mut kept = vec![];
+    let mut dropped = 0;
+    let mut current: Option<Record> = None;
+    while let Some(Reverse((code, i, counts))) = heads.pop() {
+        if let Some(record) = sources[i].next() {
+            let (next, next_counts) = record?;
+            heads.push(Reverse((next, i, next_counts)));
+        }
+        match &mut current {
+            Some((last, sum)) if *last == code => {
+                sum[0] += counts[0];
+                sum[1] += counts[1];
+            }
+            _ => {
+                if let Some(record) = current.replace((code, counts)) {
+                    dropped += emit(record, k, min, &mut kept);
+                }
+            }
+        }
+    }
+    if let Some(record) = current {
+        dropped += emit(record, k, min, &mut kept);
+    }
+    Ok((kept, dropped))
+}
+
+// Keep the windows of a merged record if they were seen `min` times together, or return how often they were seen
+fn emit((code, [fwd, rev]): Record, k: usize, min: usize, kept: &mut Vec<(Vec<u8>, usize)>) -> usize {
+    let total = (fwd + rev) as usize;
+    if total < min { return total; }
+    if fwd > 0 { kept.push((simd::unpack(code, k + 1), fwd as usize)); }
+    if rev > 0 { kept.push((simd::unpack(simd::revcomp_code(code, k + 1), k + 1), rev as usize)); }
+    0
+}
@@ -0,0 +1,60 @@
+// FASTA and FASTQ parsing through needletail for the fast-parse feature. The records are checked with the same rules
+// as the built-in reader, but needletail cannot find the next record after a broken one, so that ends the input
+use crate::utils::{ self, ParseError, Parsed, ReadOptions };
+use needletail::{ errors::ParseErrorKind, parser::Format };
+use std::{ io::Read, path::Path };
+
+// A needletail error with the message the built-in reader gives for the same problem
+fn error(fname: &Path, e: needletail::errors::ParseError) -> ParseError {
+    let message = match (&e.kind, e.format) {
+        (ParseErrorKind::UnexpectedEnd, Some(Format::Fasta)) => "record has no sequence".to_string(),
+        (ParseErrorKind::UnexpectedEnd, _) => "record is truncated".to_string(),
+        _ => e.msg,
+    };
+    utils::parse_error(fname, e.position.line as usize, 1, message)
+}
+
+// Read the reads of decompressed FASTA or FASTQ data, which needletail tells apart by the first byte
+pub fn fastx_reads(reader: impl Read + Send, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let mut parsed = Parsed::default();
+    let mut records = match needletail::parse_fastx_reader(reader) {
+        Ok(records) => records,
+        Err(e) if e.kind == ParseErrorKind::EmptyFile => return Ok(parsed),
+        Err(e) => return Err(error(fname, e)),
+    };
+    while let Some(record) = records.next() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                parsed.malformed(error(fname, e), options.strict)?;
+                break;
+            }
+        };
+        let line = record.start_line_number() as usize;
+        let seq = record.seq();
+        if seq.is_empty() && record.qual().is_none() {
+            parsed.malformed(utils::parse_error(fname, line, 1, "record has no sequence".to_string()), options.strict)?;
+            continue;
+        }
+        // The raw sequence keeps the line breaks of wrapped FASTA, so the error can point at the right line
+        let raw = record.raw_seq();
+        if let Some(i) = raw.iter().position(|&c| c != b'\n' && c != b'\r' && !utils::is_sequence_char(c)) {
+            let lines = raw[..i].iter().filter(|&&c| c == b'\n').count();
+            let column = i - raw[..i].iter().rposition(|&c| c == b'\n').map_or(0, |n| n + 1);
+            let message = format!("unexpected character {:?} in sequence", raw[i] as char);
+            parsed.malformed(utils::parse_error(fname, line + 1 + lines, column + 1, message), options.strict)?;
+            continue;
+        }
+        if let Some(column) = record.qual().and_then(|q| q.iter().position(|c| !(b'!'..=b'~').contains(c))) {
+            let message = "quality characters must be between '!' and '~'".to_string();
+            parsed.malformed(utils::parse_error(fname, line + 3, column + 1, message), options.strict)?;
+            continue;
+        }
+        if !parsed.rejected.keep(seq.len(), &options.lens) { continue; }
+        parsed.reads.push(seq.into_owned());
+        if let Some(qual) = record.qual() { parsed.quals.push(qual.to_vec()); }
+        if options.names { parsed.names.push(utils::read_name(&String::from_utf8_lossy(record.id()))); }
+    }
+    parsed.detect_phred();
+    Ok(parsed)
+}
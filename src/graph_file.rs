@@ -0,0 +1,158 @@
+// A compact binary file of a built graph for Assembler::save_graph and load_graph, so the graph can be built once
+// and traversed with different settings. Nodes are kept in id order, so edges and mates refer to them by id. K-mers
+// take two bits a base and counts are LEB128 varints, which keeps most of them to a byte
+use crate::{ kmer::KmerRepr, sbh_assembler::{ Assembler, Edge, Graph, MatePair, Node, NodeId, Nodes }, simd };
+use std::{
+    fs::File,
+    io::{ self, BufReader, BufWriter, Read, Write },
+    path::Path,
+};
+
+const MAGIC: &[u8; 8] = b"SBHGRAPH";
+// Version 2 added the colors of the edges. Files of version 1 are still read, with no colors
+const VERSION: u64 = 2;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_varint(out: &mut impl Write, mut n: u64) -> io::Result<()> {
+    while n >= 0x80 {
+        out.write_all(&[n as u8 | 0x80])?;
+        n >>= 7;
+    }
+    out.write_all(&[n as u8])
+}
+
+fn read_varint(input: &mut impl Read) -> io::Result<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        input.read_exact(&mut byte)?;
+        n |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] < 0x80 { return Ok(n); }
+    }
+    Err(invalid("varint longer than 64 bits".to_string()))
+}
+
+fn read_usize(input: &mut impl Read) -> io::Result<usize> {
+    usize::try_from(read_varint(input)?).map_err(|_| invalid("count does not fit this platform".to_string()))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    u32::try_from(read_varint(input)?).map_err(|_| invalid("count above u32::MAX".to_string()))
+}
+
+// A k-mer as a word of up to 32 bases at a time
+fn write_kmer<K: KmerRepr>(out: &mut impl Write, kmer: &K, k: usize) -> io::Result<()> {
+    for chunk in kmer.unpack(k).chunks(32) {
+        out.write_all(&simd::pack(chunk).expect("nodes are ACGT").to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_kmer<K: KmerRepr>(input: &mut impl Read, k: usize) -> io::Result<K> {
+    let mut bases = Vec::with_capacity(k);
+    for start in (0..k).step_by(32) {
+        let mut word = [0u8; 8];
+        input.read_exact(&mut word)?;
+        bases.extend(simd::unpack(u64::from_le_bytes(word), (k - start).min(32)));
+    }
+    K::pack(&bases).ok_or_else(|| invalid("k-mer is not ACGT".to_string()))
+}
+
+fn read_id(input: &mut impl Read, nodes: usize) -> io::Result<NodeId> {
+    let id = read_u32(input)?;
+    if id as usize >= nodes { return Err(invalid(format!("edge to node {} of {}", id, nodes))); }
+    Ok(NodeId(id))
+}
+
+// Write the nodes, edges and mates of the graph with their degrees, weights and use, as they are at the time
+pub fn write<K: KmerRepr>(path: &Path, ass: &Assembler<K>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    write_varint(&mut out, VERSION)?;
+    write_varint(&mut out, ass.k as u64)?;
+    out.write_all(&[u8::from(ass.canonical)])?;
+    write_varint(&mut out, ass.nodes.len() as u64)?;
+    for (_, node) in ass.nodes.iter() {
+        write_kmer(&mut out, &node.idx, ass.k)?;
+        write_varint(&mut out, node.ideg as u64)?;
+        write_varint(&mut out, node.odeg as u64)?;
+        write_varint(&mut out, node.cov as u64)?;
+    }
+    write_varint(&mut out, ass.graph.len() as u64)?;
+    for edge in ass.graph.iter() {
+        write_varint(&mut out, u64::from(edge.prefix.0))?;
+        write_varint(&mut out, u64::from(edge.suffix.0))?;
+        write_varint(&mut out, u64::from(edge.count))?;
+        write_varint(&mut out, u64::from(edge.used))?;
+        out.write_all(&edge.weight.to_le_bytes())?;
+        write_varint(&mut out, edge.reads as u64)?;
+        write_varint(&mut out, edge.span as u64)?;
+        write_varint(&mut out, edge.colors)?;
+    }
+    write_varint(&mut out, ass.mates.len() as u64)?;
+    for mate in &ass.mates {
+        for kmer in [&mate.first.0, &mate.first.1, &mate.second.0, &mate.second.1] {
+            write_kmer(&mut out, kmer, ass.k)?;
+        }
+    }
+    out.flush()
+}
+
+// Load a graph written by write into `nodes`, which should be empty
+pub fn read<K: KmerRepr>(path: &Path, nodes: Nodes<K>) -> io::Result<Assembler<K>> {
+    let mut input = BufReader::new(File::open(path)?);
+    parse(&mut input, nodes).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => invalid("the file ends in the middle of the graph".to_string()),
+        _ => e,
+    })
+}
+
+fn parse<K: KmerRepr>(input: &mut impl Read, mut nodes: Nodes<K>) -> io::Result<Assembler<K>> {
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC { return Err(invalid("not a graph file written by --save-graph".to_string())); }
+    let version = read_varint(input)?;
+    if !(1..=VERSION).contains(&version) {
+        return Err(invalid(format!("graph file version {} is not between 1 and {}", version, VERSION)));
+    }
+    let k = read_usize(input)?;
+    if !(1..=K::MAX_K).contains(&k) { return Err(invalid(format!("k = {} is not between 1 and {}", k, K::MAX_K))); }
+    let mut canonical = [0u8];
+    input.read_exact(&mut canonical)?;
+    for _ in 0..read_usize(input)? {
+        let idx = read_kmer(input, k)?;
+        let (ideg, odeg, cov) = (read_usize(input)?, read_usize(input)?, read_usize(input)?);
+        if nodes.contains_key(&idx) { return Err(invalid(format!("node {:?} is listed twice", idx))); }
+        nodes.insert(Node::new(idx, ideg, odeg, cov));
+    }
+    let n = nodes.len();
+    let mut edges = vec![];
+    for _ in 0..read_usize(input)? {
+        let (prefix, suffix) = (read_id(input, n)?, read_id(input, n)?);
+        let (count, used) = (read_u32(input)?, read_u32(input)?);
+        let mut weight = [0u8; 4];
+        input.read_exact(&mut weight)?;
+        let mut edge = Edge::new(prefix, suffix, 0);
+        edge.count = count;
+        edge.used = used;
+        edge.weight = f32::from_le_bytes(weight);
+        edge.reads = read_usize(input)?;
+        edge.span = read_usize(input)?;
+        if version >= 2 { edge.colors = read_varint(input)?; }
+        edges.push(edge);
+    }
+    let mut mates = vec![];
+    for _ in 0..read_usize(input)? {
+        let first = (read_kmer(input, k)?, read_kmer(input, k)?);
+        let second = (read_kmer(input, k)?, read_kmer(input, k)?);
+        mates.push(MatePair { first, second });
+    }
+    // The edges were written frozen, so freezing them again keeps their order
+    let mut ass = Assembler::from_graph(Graph::new(edges, n), nodes, k);
+    ass.canonical = canonical[0] != 0;
+    ass.mates = mates;
+    Ok(ass)
+}
@@ -0,0 +1,142 @@
+use crate::{ kmer::KmerRepr, sbh_assembler::{ Assembler, Edge } };
+use std::{
+    fs::{ self, File },
+    path::Path,
+};
+
+// A single column of a node or edge table
+enum Column {
+    Text(Vec<String>),
+    Int(Vec<i64>),
+    Float(Vec<f32>),
+}
+
+struct Table {
+    columns: Vec<(&'static str, Column)>,
+}
+
+// Export the graph as nodes and edges tables in dir
+// Tables are written as CSV, or as Parquet when the parquet feature is enabled
+// Must be called before traversal since traversal consumes the degrees
+pub fn export_graph_tables<K: KmerRepr>(ass: &Assembler<K>, dir: &Path, k: usize) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    write_table(&node_table(ass, k), &dir.join("nodes"))?;
+    write_table(&edge_table(ass, k), &dir.join("edges"))?;
+    Ok(())
+}
+
+fn kmer<K: KmerRepr>(idx: &K, k: usize) -> String {
+    String::from_utf8_lossy(&idx.unpack(k)).into_owned()
+}
+
+fn node_table<K: KmerRepr>(ass: &Assembler<K>, k: usize) -> Table {
+    let mut nodes: Vec<_> = ass.nodes.iter().map(|(_, node)| node).collect();
+    nodes.sort_unstable_by(|a, b| a.idx.cmp(&b.idx));
+    let (mut kmers, mut ideg, mut odeg, mut coverage) = (vec![], vec![], vec![], vec![]);
+    for node in nodes {
+        kmers.push(kmer(&node.idx, k));
+        ideg.push(node.ideg as i64);
+        odeg.push(node.odeg as i64);
+        // Every read containing the k-mer adds to its coverage
+        coverage.push(node.cov as i64);
+    }
+    Table { columns: vec![
+        ("kmer", Column::Text(kmers)),
+        ("in_degree", Column::Int(ideg)),
+        ("out_degree", Column::Int(odeg)),
+        ("coverage", Column::Int(coverage)),
+    ] }
+}
+
+fn edge_table<K: KmerRepr>(ass: &Assembler<K>, k: usize) -> Table {
+    let mut links: Vec<(&K, &K, &[Edge])> = ass.graph.links()
+        .map(|edges| (&ass.nodes[edges[0].prefix].idx, &ass.nodes[edges[0].suffix].idx, edges))
+        .collect();
+    links.sort_unstable_by_key(|&(p, s, _)| (p, s));
+    let (mut prefixes, mut suffixes, mut multiplicity, mut weight) = (vec![], vec![], vec![], vec![]);
+    for (p, s, edges) in links {
+        prefixes.push(kmer(p, k));
+        suffixes.push(kmer(s, k));
+        multiplicity.push(edges.iter().map(|e| e.reads).sum::<usize>() as i64);
+        weight.push(edges.iter().map(|e| e.weight * e.count as f32).sum::<f32>());
+    }
+    Table { columns: vec![
+        ("prefix", Column::Text(prefixes)),
+        ("suffix", Column::Text(suffixes)),
+        ("multiplicity", Column::Int(multiplicity)),
+        ("weight", Column::Float(weight)),
+    ] }
+}
+
+#[cfg(not(feature = "parquet"))]
+impl Table {
+    fn rows(&self) -> usize {
+        match self.columns.first() {
+            Some((_, Column::Text(v))) => v.len(),
+            Some((_, Column::Int(v))) => v.len(),
+            Some((_, Column::Float(v))) => v.len(),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_table(table: &Table, path: &Path) -> std::io::Result<()> {
+    use std::io::{ BufWriter, Write };
+
+    let mut writer = BufWriter::new(File::create(path.with_extension("csv"))?);
+    let header: Vec<&str> = table.columns.iter().map(|(name, _)| *name).collect();
+    writeln!(writer, "{}", header.join(","))?;
+    for row in 0..table.rows() {
+        let fields: Vec<String> = table.columns.iter().map(|(_, col)| match col {
+            Column::Text(v) => v[row].clone(),
+            Column::Int(v) => v[row].to_string(),
+            Column::Float(v) => v[row].to_string(),
+        }).collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    writer.flush()
+}
+
+#[cfg(feature = "parquet")]
+fn write_table(table: &Table, path: &Path) -> std::io::Result<()> {
+    use parquet::{
+        data_type::{ ByteArray, ByteArrayType, FloatType, Int64Type },
+        errors::ParquetError,
+        file::{ properties::WriterProperties, writer::SerializedFileWriter },
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    let to_io = |e: ParquetError| std::io::Error::other(e);
+    let fields: Vec<String> = table.columns.iter().map(|(name, col)| match col {
+        Column::Text(_) => format!("REQUIRED BYTE_ARRAY {} (UTF8);", name),
+        Column::Int(_) => format!("REQUIRED INT64 {};", name),
+        Column::Float(_) => format!("REQUIRED FLOAT {};", name),
+    }).collect();
+    let schema = parse_message_type(&format!("message table {{ {} }}", fields.join(" "))).map_err(to_io)?;
+    let file = File::create(path.with_extension("parquet"))?;
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(file, Arc::new(schema), props).map_err(to_io)?;
+    let mut row_group = writer.next_row_group().map_err(to_io)?;
+    let mut columns = table.columns.iter();
+    while let Some(mut col_writer) = row_group.next_column().map_err(to_io)? {
+        match columns.next() {
+            Some((_, Column::Text(v))) => {
+                let vals: Vec<ByteArray> = v.iter().map(|s| ByteArray::from(s.as_str())).collect();
+                col_writer.typed::<ByteArrayType>().write_batch(&vals, None, None).map_err(to_io)?;
+            }
+            Some((_, Column::Int(v))) => {
+                col_writer.typed::<Int64Type>().write_batch(v, None, None).map_err(to_io)?;
+            }
+            Some((_, Column::Float(v))) => {
+                col_writer.typed::<FloatType>().write_batch(v, None, None).map_err(to_io)?;
+            }
+            None => break,
+        }
+        col_writer.close().map_err(to_io)?;
+    }
+    row_group.close().map_err(to_io)?;
+    writer.close().map_err(to_io)?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+// Homopolymer compression for --hpc. Every run of one base is collapsed to a single base before the graph is built,
+// so the run length errors of noisy long reads do not split it, and the contigs get their run lengths back from the
+// reads that covered each k-mer
+use crate::simd;
+use std::{ collections::HashMap, ops::RangeInclusive };
+
+// Collapse runs of the same base. Returns the compressed read and the length of every run
+pub fn compress(read: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut seq: Vec<u8> = Vec::with_capacity(read.len());
+    let mut runs: Vec<u8> = Vec::with_capacity(read.len());
+    for &c in read {
+        match (seq.last(), runs.last_mut()) {
+            (Some(last), Some(run)) if last.eq_ignore_ascii_case(&c) && *run < u8::MAX => *run += 1,
+            _ => {
+                seq.push(c);
+                runs.push(1);
+            }
+        }
+    }
+    (seq, runs)
+}
+
+// Run lengths seen at each base of each k-mer, summed over the reads it is in
+pub struct RunLengths {
+    k: usize,
+    sums: HashMap<u64, (u32, Vec<u32>)>,
+}
+
+impl RunLengths {
+    pub fn new(k: usize) -> Self {
+        RunLengths { k, sums: HashMap::new() }
+    }
+
+    // Record the runs of a compressed read. k-mers with bases other than ACGT are left out
+    pub fn add(&mut self, read: &[u8], runs: &[u8]) {
+        for (window, runs) in read.windows(self.k).zip(runs.windows(self.k)) {
+            let Some(idx) = simd::pack(window) else { continue };
+            let (n, sums) = self.sums.entry(idx).or_insert_with(|| (0, vec![0; self.k]));
+            *n += 1;
+            for (sum, &run) in sums.iter_mut().zip(runs) { *sum += run as u32; }
+        }
+    }
+
+    // Spell a compressed contig with every base repeated its mean run length, taken from the first k-mer that
+    // covers it and was seen in a read. Bases without one stay single
+    pub fn expand(&self, seq: &[u8]) -> Vec<u8> {
+        let mut lens = vec![0; seq.len()];
+        for (start, window) in seq.windows(self.k).enumerate() {
+            let Some((n, sums)) = simd::pack(window).and_then(|idx| self.sums.get(&idx)) else { continue };
+            for (len, sum) in lens[start..].iter_mut().zip(sums).filter(|(len, _)| **len == 0) {
+                *len = ((sum + n / 2) / n).max(1);
+            }
+        }
+        seq.iter().zip(lens).flat_map(|(&c, len)| std::iter::repeat_n(c, len.max(1) as usize)).collect()
+    }
+}
+
+// Compress reads and their quality strings, which keep the score of the first base of each run, recording the runs
+// in run_lengths. Reads with a length outside lens once compressed are dropped. Returns the reads left and the
+// number dropped
+pub fn compress_reads(reads: Vec<Vec<u8>>, quals: Vec<Vec<u8>>, lens: &RangeInclusive<usize>, run_lengths: &mut RunLengths)
+    -> (Vec<Vec<u8>>, Vec<Vec<u8>>, usize) {
+    let has_quals = !quals.is_empty();
+    let mut quals = quals.into_iter();
+    let (mut kept, mut kept_quals, mut dropped) = (vec![], vec![], 0);
+    for read in reads {
+        let qual = quals.next();
+        let (seq, runs) = compress(&read);
+        if !lens.contains(&seq.len()) {
+            dropped += 1;
+            continue;
+        }
+        run_lengths.add(&seq, &runs);
+        if has_quals {
+            let qual = qual.unwrap_or_default();
+            let mut at = 0;
+            kept_quals.push(if qual.is_empty() { qual } else {
+                runs.iter().map(|&run| { let q = qual[at]; at += run as usize; q }).collect()
+            });
+        }
+        kept.push(seq);
+    }
+    (kept, kept_quals, dropped)
+}
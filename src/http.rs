@@ -0,0 +1,68 @@
+// http:// and https:// infiles for the http feature. The body is streamed into the parser as it arrives, and a
+// connection that drops is picked up again with a Range request from the last byte read
+use std::{ io::{ Error, ErrorKind, Read }, sync::atomic::{ AtomicU32, Ordering } };
+
+// How many times a dropped download is resumed, set from --http-retries. 0 turns resuming off
+pub static RETRIES: AtomicU32 = AtomicU32::new(3);
+
+pub struct Download {
+    url: String,
+    agent: ureq::Agent,
+    body: Box<dyn Read + Send>,
+    // Bytes read so far, where a resumed request starts
+    offset: u64,
+    // Whether the server takes Range requests
+    ranges: bool,
+    retries: u32,
+}
+
+fn get(agent: &ureq::Agent, url: &str, offset: u64) -> std::io::Result<ureq::Response> {
+    let request = agent.get(url);
+    let request = if offset > 0 { request.set("Range", &format!("bytes={}-", offset)) } else { request };
+    request.call().map_err(|e| Error::other(e.to_string()))
+}
+
+// Start downloading a URL
+pub fn open(url: &str) -> std::io::Result<Download> {
+    let agent = ureq::AgentBuilder::new().build();
+    let response = get(&agent, url, 0)?;
+    let ranges = response.header("Accept-Ranges") == Some("bytes");
+    Ok(Download {
+        url: url.to_string(),
+        agent,
+        body: response.into_reader(),
+        offset: 0,
+        ranges,
+        retries: RETRIES.load(Ordering::Relaxed),
+    })
+}
+
+impl Download {
+    // Ask for the rest of the body after a dropped connection. Only a 206 answer continues where the last one stopped
+    fn resume(&mut self, cause: Error) -> std::io::Result<()> {
+        if !self.ranges || self.retries == 0 { return Err(cause); }
+        self.retries -= 1;
+        eprintln!("Download of {} stopped at byte {} ({}), resuming...", self.url, self.offset, cause);
+        let response = get(&self.agent, &self.url, self.offset)?;
+        if response.status() != 206 {
+            return Err(Error::new(ErrorKind::Unsupported, format!("{} did not resume at byte {}", self.url, self.offset)));
+        }
+        self.body = response.into_reader();
+        Ok(())
+    }
+}
+
+impl Read for Download {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.body.read(buf) {
+                Ok(n) => {
+                    self.offset += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => self.resume(e)?,
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    config::Config,
+    contig::Contig,
+    external,
+    sbh_assembler::{ Assembler, NodeHasher, Nodes, PathType },
+    stages::StageRegistry,
+    utils,
+};
+use rayon::prelude::*;
+use std::{
+    fmt,
+    io,
+    sync::{ Arc, atomic::{ AtomicBool, AtomicUsize, Ordering } },
+    thread::{ self, JoinHandle },
+    time::{ Duration, Instant },
+};
+
+// The stages of an assembly in the order they run
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Building,
+    Cleaning,
+    // The stages named with --stages, in place of paths to condensing
+    Stages,
+    Paths,
+    Cycles,
+    Contigs,
+    Condensing,
+    Done,
+}
+
+const STAGES: [Stage; 8] = [
+    Stage::Building,
+    Stage::Cleaning,
+    Stage::Stages,
+    Stage::Paths,
+    Stage::Cycles,
+    Stage::Contigs,
+    Stage::Condensing,
+    Stage::Done,
+];
+
+// A snapshot of a running job
+#[derive(Copy, Clone, Debug)]
+pub struct Progress {
+    pub stage: Stage,
+    // Number of finished condensing passes
+    pub passes: usize,
+    // Number of contigs after the last finished stage or pass
+    pub contigs: usize,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    Cancelled,
+    Panicked,
+    // Counting k-mers on disk for --memory-limit failed
+    Io(io::Error),
+    // A stage named with --stages is unknown or failed
+    Stage(String),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobError::Cancelled => write!(f, "assembly was cancelled"),
+            JobError::Panicked => write!(f, "assembly thread panicked"),
+            JobError::Io(e) => write!(f, "counting k-mers on disk failed: {}", e),
+            JobError::Stage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+// State shared between the handle and the assembly thread
+struct Shared {
+    stage: AtomicUsize,
+    passes: AtomicUsize,
+    contigs: AtomicUsize,
+    cancelled: Arc<AtomicBool>,
+    started: Instant,
+}
+
+impl Shared {
+    fn enter(&self, stage: Stage) -> Result<(), JobError> {
+        if self.cancelled.load(Ordering::Relaxed) { return Err(JobError::Cancelled); }
+        self.stage.store(STAGES.iter().position(|&s| s == stage).unwrap_or(0), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// A handle to an assembly running on a background thread
+// The assembler itself never leaves that thread, only the reads go in and the contigs come out
+pub struct AssemblyJob {
+    shared: Arc<Shared>,
+    handle: JoinHandle<Result<Vec<Contig>, JobError>>,
+}
+
+impl AssemblyJob {
+    // Start assembling reads with the given config
+    pub fn spawn(config: Config, reads: Vec<Vec<u8>>) -> Self {
+        let shared = Arc::new(Shared {
+            stage: AtomicUsize::new(0),
+            passes: AtomicUsize::new(0),
+            contigs: AtomicUsize::new(0),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            started: Instant::now(),
+        });
+        let thread_shared = shared.clone();
+        let handle = thread::spawn(move || Self::run(&config, reads, &thread_shared));
+        AssemblyJob { shared, handle }
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            stage: STAGES[self.shared.stage.load(Ordering::Relaxed)],
+            passes: self.shared.passes.load(Ordering::Relaxed),
+            contigs: self.shared.contigs.load(Ordering::Relaxed),
+            elapsed: self.shared.started.elapsed(),
+        }
+    }
+
+    // Ask the job to stop. Long running stages check for this and return early
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // Wait for the job and get the condensed contigs
+    pub fn join(self) -> Result<Vec<Contig>, JobError> {
+        self.handle.join().unwrap_or(Err(JobError::Panicked))
+    }
+
+    // The same stages the command line runs, minus the reporting
+    fn run(config: &Config, reads: Vec<Vec<u8>>, shared: &Shared) -> Result<Vec<Contig>, JobError> {
+        shared.enter(Stage::Building)?;
+        // Config already refused --dense-nodes for k over DENSE_MAX_K
+        let nodes = if config.dense_nodes {
+            Nodes::dense(config.k).unwrap_or_default()
+        } else if config.siphash {
+            Nodes::with_hasher(NodeHasher::sip())
+        } else {
+            Nodes::default()
+        };
+        let reads: Vec<(Vec<u8>, usize)> = reads.into_iter().map(|r| (r, 1)).collect();
+        let reads = if let Some(mb) = config.memory_limit {
+            let dir = config.tmp_dir.clone().unwrap_or_else(std::env::temp_dir);
+            let min = config.kmer_threshold().unwrap_or(1);
+            external::kmerize(&reads, config.k, config.canonical, min, &dir, mb << 20).map_err(JobError::Io)?.0
+        } else {
+            match config.kmer_counter(utils::kmer_occurrences(&reads, config.k, config.debruijn)) {
+                Some((mut counter, min)) if config.debruijn => utils::kmerize_solid(&reads, config.k, config.canonical, counter.as_mut(), min).0,
+                Some((mut counter, min)) => utils::drop_rare_ends(reads, config.k, config.canonical, counter.as_mut(), min).0,
+                None if config.debruijn => utils::kmerize(reads.iter().map(|(r, n)| (r, *n)), config.k),
+                None => reads,
+            }
+        };
+        let mut ass: Assembler = Assembler::from_slices_par(nodes, reads.par_iter().map(|(r, n)| (r, *n)), config.k);
+        ass.cancelled = shared.cancelled.clone();
+        ass.paranoid = config.paranoid;
+        ass.traversal = config.traversal;
+        ass.backtrack = config.backtrack;
+        ass.attempts = config.attempts;
+        ass.seed = config.seed;
+        ass.parallel = config.parallel_walks;
+        ass.deterministic = config.deterministic_walks;
+        // The graph stays in memory, which always takes the edges
+        if config.canonical { ass.make_canonical().expect("graph in memory"); }
+        shared.enter(Stage::Cleaning)?;
+        // A stage named with --stages takes its flag as its setting and runs in its place among the stages
+        if let Some(min_reads) = config.min_edge_coverage.filter(|_| !config.runs_stage("min-edge-coverage")) {
+            ass.remove_weak_links(min_reads).expect("graph in memory");
+        }
+        if let Some(max_len) = config.clip_tips.filter(|_| !config.runs_stage("clip-tips")) {
+            ass.clip_tips(max_len, config.tip_max_cov.unwrap_or(f32::INFINITY)).expect("graph in memory");
+        }
+        if let Some(max_len) = config.pop_bubbles.filter(|_| !config.runs_stage("pop-bubbles")) {
+            ass.pop_bubbles(max_len).expect("graph in memory");
+        }
+        if config.clean_iterations > 0 && !config.runs_stage("clean") {
+            ass.iterative_clean(config.clean_iterations, config.clean_decay, config.clean_min_weight).expect("graph in memory");
+        }
+        if let Some(names) = &config.stages {
+            shared.enter(Stage::Stages)?;
+            StageRegistry::with_builtins().run(names, &mut ass, config).map_err(JobError::Stage)?;
+            if ass.is_cancelled() { return Err(JobError::Cancelled); }
+            shared.contigs.store(ass.contigs.len(), Ordering::Relaxed);
+            shared.enter(Stage::Done)?;
+            return Ok(ass.contigs);
+        }
+        shared.enter(Stage::Paths)?;
+        if config.parallel_components {
+            // Every component is condensed on its own before they are condensed together below
+            let components = ass.components();
+            ass.assemble_components_par(&components, |sub| sub.condense(config.min_overlap, config.min_overlap_frac, config.string_graph));
+        } else if config.components {
+            for ids in ass.components().nodes {
+                if ass.is_cancelled() { return Err(JobError::Cancelled); }
+                ass.assemble_component(&ids);
+            }
+        } else {
+            ass.populate_paths_or_cycles(PathType::Path);
+            shared.enter(Stage::Cycles)?;
+            ass.populate_paths_or_cycles(PathType::Cycle);
+            shared.enter(Stage::Contigs)?;
+            ass.paths_cycles_to_contigs();
+        }
+        shared.contigs.store(ass.contigs.len(), Ordering::Relaxed);
+        shared.enter(Stage::Condensing)?;
+        let mut prev = usize::MAX;
+        while prev != ass.contigs.len() {
+            prev = ass.contigs.len();
+            ass.remove_contained_contigs();
+            if config.string_graph {
+                ass.merge_string_graph(config.min_overlap, config.min_overlap_frac);
+            } else {
+                ass.merge_contigs(config.min_overlap, config.min_overlap_frac);
+            }
+            if ass.is_cancelled() { return Err(JobError::Cancelled); }
+            shared.passes.fetch_add(1, Ordering::Relaxed);
+            shared.contigs.store(ass.contigs.len(), Ordering::Relaxed);
+        }
+        shared.enter(Stage::Done)?;
+        Ok(ass.contigs)
+    }
+}
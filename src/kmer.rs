@@ -0,0 +1,141 @@
+// Node k-mer representations. Nodes up to 31 bp are packed into a single u64, longer ones, such as the long probes
+// of some SBH chips, into a Kmer of as many u64 words as they need
+use crate::simd;
+use smallvec::SmallVec;
+use std::{ fmt::Debug, hash::{ DefaultHasher, Hash, Hasher } };
+
+// What an Assembler keys its nodes with
+pub trait KmerRepr: Clone + Eq + Ord + Hash + Debug + Send + Sync {
+    // Longest node this representation can hold
+    const MAX_K: usize;
+
+    // Pack a k-mer. None if a base is not ACGT
+    fn pack(bases: &[u8]) -> Option<Self>;
+
+    // Spell the k-mer back, k being the length it was packed from
+    fn unpack(&self, k: usize) -> Vec<u8>;
+
+    // A u64 standing for the k-mer where one is reported, such as in the break points of contigs
+    fn id(&self) -> u64;
+
+    // The position of the k-mer in a table of all 4^k k-mers, for representations that store the bases as a number
+    // below 4^k
+    fn code(&self) -> Option<usize> {
+        None
+    }
+
+    // The reverse complement, k being the length it was packed from
+    fn revcomp(&self, k: usize) -> Self;
+
+    // The lexicographically smaller of the k-mer and its reverse complement, which both strands share
+    fn canonical(&self, k: usize) -> Self;
+}
+
+impl KmerRepr for u64 {
+    const MAX_K: usize = 31;
+
+    fn pack(bases: &[u8]) -> Option<Self> {
+        simd::pack(bases)
+    }
+
+    fn unpack(&self, k: usize) -> Vec<u8> {
+        simd::unpack(*self, k)
+    }
+
+    fn id(&self) -> u64 {
+        *self
+    }
+
+    fn code(&self) -> Option<usize> {
+        usize::try_from(*self).ok()
+    }
+
+    fn revcomp(&self, k: usize) -> Self {
+        simd::revcomp_code(*self, k)
+    }
+
+    fn canonical(&self, k: usize) -> Self {
+        let rc = self.revcomp(k);
+        if simd::reverse_code(rc, k) < simd::reverse_code(*self, k) { rc } else { *self }
+    }
+}
+
+// A k-mer of any length, 32 bases to a word with the first base in the lowest two bits of the first word.
+// Up to 64 bases are kept inline
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Kmer(SmallVec<[u64; 2]>);
+
+impl KmerRepr for Kmer {
+    const MAX_K: usize = usize::MAX;
+
+    fn pack(bases: &[u8]) -> Option<Self> {
+        bases.chunks(32).map(simd::pack).collect::<Option<_>>().map(Kmer)
+    }
+
+    fn unpack(&self, k: usize) -> Vec<u8> {
+        let mut bases = Vec::with_capacity(k);
+        for &word in &self.0 {
+            bases.extend(simd::unpack(word, (k - bases.len()).min(32)));
+        }
+        bases
+    }
+
+    // The packed bases do not fit, so this is a hash. It tells nodes apart but cannot be unpacked
+    fn id(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Reversing every word and their order reverses the bases padded to whole words, with the padding first. It is
+    // shifted out across the words
+    fn revcomp(&self, k: usize) -> Self {
+        let pad = 2 * (32 * self.0.len() - k);
+        let mut rc: SmallVec<[u64; 2]> = self.0.iter().rev().map(|&word| simd::revcomp_code(word, 32)).collect();
+        if pad > 0 {
+            for i in 0..rc.len() {
+                let next = rc.get(i + 1).map_or(0, |&word| word << (64 - pad));
+                rc[i] = rc[i] >> pad | next;
+            }
+        }
+        Kmer(rc)
+    }
+
+    // Words are compared from the first, each with its bases reversed so it sorts like them
+    fn canonical(&self, k: usize) -> Self {
+        fn bases(kmer: &Kmer, k: usize) -> impl Iterator<Item = u64> + '_ {
+            kmer.0.iter().enumerate().map(move |(i, &word)| simd::reverse_code(word, (k - 32 * i).min(32)))
+        }
+        let rc = self.revcomp(k);
+        if bases(&rc, k).lt(bases(self, k)) { rc } else { self.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ Kmer, KmerRepr };
+    use crate::{ simd, utils::SplitMix64 };
+
+    // Both strands of random k-mers of every length, reverse complemented by spelling them out
+    fn check<K: KmerRepr>(max_k: usize) {
+        let mut rng = SplitMix64::new(3);
+        for k in 1..=max_k {
+            for _ in 0..20 {
+                let bases: Vec<u8> = (0..k).map(|_| b"ACGT"[(rng.next_u64() % 4) as usize]).collect();
+                let rc = simd::revcomp(&bases);
+                let kmer = K::pack(&bases).unwrap();
+                assert_eq!(kmer.revcomp(k), K::pack(&rc).unwrap(), "k {}", k);
+                assert_eq!(kmer.revcomp(k).revcomp(k), kmer);
+                let canonical = K::pack(&bases.clone().min(rc)).unwrap();
+                assert_eq!(kmer.canonical(k), canonical, "k {}", k);
+                assert_eq!(kmer.revcomp(k).canonical(k), canonical, "k {}", k);
+            }
+        }
+    }
+
+    #[test]
+    fn packed_revcomp_matches_spelled_revcomp() {
+        check::<u64>(31);
+        check::<Kmer>(100);
+    }
+}
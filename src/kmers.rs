@@ -0,0 +1,78 @@
+use crate::simd;
+use std::collections::{ HashMap, HashSet };
+
+// Canonical k-mer counts of a read set, shared by the assembly QC metrics
+// k-mers are 2-bit packed into a u64, so k can be at most 32
+pub struct KmerCounts {
+    pub k: usize,
+    counts: HashMap<u64, u32>,
+}
+
+impl KmerCounts {
+    pub fn from_reads(reads: &[Vec<u8>], k: usize) -> Self {
+        let mut counts: HashMap<u64, u32> = HashMap::new();
+        for read in reads {
+            for kmer in canonical_kmers(read, k) {
+                *counts.entry(kmer).or_default() += 1;
+            }
+        }
+        KmerCounts { k, counts }
+    }
+
+    pub fn get(&self, kmer: u64) -> u32 {
+        self.counts.get(&kmer).copied().unwrap_or(0)
+    }
+
+    // Number of distinct k-mers
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u32)> + '_ {
+        self.counts.iter().map(|(&kmer, &n)| (kmer, n))
+    }
+}
+
+// Fraction of solid read k-mers, seen at least min_count times, that occur in the sequences
+// Returns (solid k-mers, solid k-mers found)
+pub fn completeness<'a>(reads: &KmerCounts, seqs: impl IntoIterator<Item = &'a [u8]>, min_count: u32) -> (usize, usize) {
+    let assembled: HashSet<u64> = seqs.into_iter().flat_map(|seq| canonical_kmers(seq, reads.k)).collect();
+    let solid = reads.iter().filter(|&(_, n)| n >= min_count);
+    solid.fold((0, 0), |(total, found), (kmer, _)| (total + 1, found + assembled.contains(&kmer) as usize))
+}
+
+// The smaller of the packs of every k-mer of seq and of its reverse complement, see simd::rolling. Windows with
+// bases other than ACGT are skipped
+pub fn canonical_kmers(seq: &[u8], k: usize) -> impl Iterator<Item = u64> + '_ {
+    simd::rolling(seq, k).flatten().map(|(fwd, rev)| fwd.min(rev))
+}
+
+// Merqury style consensus quality of a sequence: k-mers missing from the reads are taken as errors
+pub struct Qv {
+    pub kmers: usize,
+    pub errors: usize,
+}
+
+impl Qv {
+    pub fn of(seq: &[u8], reads: &KmerCounts) -> Self {
+        let (mut kmers, mut errors) = (0, 0);
+        for kmer in canonical_kmers(seq, reads.k) {
+            kmers += 1;
+            if reads.get(kmer) == 0 { errors += 1; }
+        }
+        Qv { kmers, errors }
+    }
+
+    // Phred scaled base error rate estimated from the fraction of error k-mers
+    // None when no k-mer is missing, i.e. no error was detected
+    pub fn qv(&self, k: usize) -> Option<f64> {
+        if self.errors == 0 || self.kmers == 0 { return None; }
+        let shared = 1.0 - self.errors as f64 / self.kmers as f64;
+        let error_rate = 1.0 - shared.powf(1.0 / k as f64);
+        Some(-10.0 * error_rate.log10())
+    }
+}
@@ -0,0 +1,43 @@
+pub mod async_io;
+#[cfg(feature = "bam")]
+pub mod bam;
+pub mod bloom;
+#[cfg(feature = "boss")]
+pub mod boss;
+pub mod build_info;
+pub mod claims;
+#[cfg(feature = "concurrent")]
+pub mod concurrent;
+pub mod config;
+pub mod contig;
+pub mod counting;
+#[cfg(feature = "mmap")]
+pub mod disk_graph;
+#[cfg(feature = "explain")]
+pub mod explain;
+pub mod external;
+#[cfg(feature = "fast-parse")]
+pub mod fast_parse;
+pub mod graph_file;
+pub mod graph_tables;
+pub mod hpc;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod job;
+pub mod kmer;
+pub mod kmers;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "runs")]
+pub mod runs;
+pub mod sbh_assembler;
+#[cfg(feature = "seq")]
+pub mod seq;
+pub mod simd;
+pub mod stages;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod string_graph;
+pub mod taxonomy;
+pub mod unitig;
+pub mod utils;
@@ -1,28 +1,758 @@
-mod sbh_assembler;
-mod utils;
-use sbh_assembler::{ Assembler, PathType };
-use std::{ time::Instant, env };
+use sbh_assembler::{
+    async_io::{ self, WriteBehind },
+    build_info,
+    config::{ self, Config, NPolicy, SoftMask },
+    external,
+    graph_tables,
+    hpc,
+    kmer::{ Kmer, KmerRepr },
+    sbh_assembler::{ Assembler, EulerianWalk, NodeHasher, Nodes, PathType },
+    simd,
+    stages::StageRegistry,
+    taxonomy::KmerTaxonomy,
+    unitig,
+    utils,
+};
+#[cfg(feature = "boss")]
+use sbh_assembler::{ boss::Boss, contig::{ Contig, Source }, sbh_assembler::Graph };
+#[cfg(feature = "mmap")]
+use sbh_assembler::disk_graph::DiskGraph;
+#[cfg(feature = "explain")]
+use sbh_assembler::explain;
+#[cfg(feature = "runs")]
+use sbh_assembler::runs;
+#[cfg(feature = "seq")]
+use sbh_assembler::seq;
+#[cfg(feature = "stats")]
+use sbh_assembler::{ kmers::KmerCounts, stats };
+use rayon::prelude::*;
+use std::{
+    any::Any,
+    collections::HashSet,
+    ffi::OsString,
+    fs::File,
+    io::{ self, BufWriter, Write },
+    ops::RangeInclusive,
+    path::Path,
+    sync::atomic::{ AtomicBool, Ordering },
+    thread,
+    time::{ Duration, Instant },
+};
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+// jemalloc reads its options from this symbol at startup. The graph maps are large and long lived,
+// so transparent hugepages cut down on TLB misses during construction and traversal
+#[cfg(all(feature = "hugepages", not(feature = "mimalloc"), target_os = "linux"))]
+#[export_name = "_rjem_malloc_conf"]
+pub static MALLOC_CONF: &[u8; 29] = b"thp:always,metadata_thp:auto\0";
+
+// Subcommands of the sbh binary. All but assemble and help are behind the feature of the same name
+const SUBCOMMANDS: &[(&str, &str)] = &[
+    ("assemble", "assemble reads into contigs, the default when no subcommand is given"),
+    #[cfg(feature = "runs")]
+    ("runs", "list and show runs recorded with --record-run"),
+    #[cfg(feature = "seq")]
+    ("seq", "reverse complement, translate, stats and length filtering of fasta files"),
+    #[cfg(feature = "explain")]
+    ("explain", "print how a contig was built from a run made with --provenance"),
+    ("help", "print this list and the assembler options"),
+];
+
+// The assembler options, for help and assemble --help
+fn print_options() {
+    println!("Usage: sbh [assemble] <infile_path>... [<outfile_path> | -o <outfile_path>] [options]");
+    println!("Options:");
+    for (flag, help) in config::OPTIONS {
+        println!("\t\x1b[32m{:<28}\x1b[0m{}", flag, help);
+    }
+}
 
 fn main() {
     let time_start = Instant::now();
-    let infile = match env::args().nth(1) {
-        Some(a) => a,
-        None => "data/YeastReads.fasta".to_string(),
-    };
-    let outfile = match env::args().nth(2) {
-        Some(a) => a,
-        None => "cont.fasta".to_string(),
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    if args.iter().any(|a| a == "--build-info") {
+        println!("{}", build_info::summary());
+        return;
+    }
+    // Subcommands that do not assemble anything
+    let subcommand: Option<Result<(), String>> = match args.first().and_then(|a| a.to_str()) {
+        #[cfg(feature = "runs")]
+        Some("runs") => Some(runs::runs_command(&args[1..])),
+        #[cfg(feature = "seq")]
+        Some("seq") => Some(seq::seq_command(&args[1..])),
+        #[cfg(feature = "explain")]
+        Some("explain") => Some(explain::explain_command(&args[1..])),
+        Some("help" | "--help" | "-h") => {
+            println!("Usage: sbh [subcommand] ...");
+            for (name, help) in SUBCOMMANDS {
+                println!("\t\x1b[32m{:<12}\x1b[0m{}", name, help);
+            }
+            print_options();
+            Some(Ok(()))
+        }
+        Some("assemble") if args.get(1).is_some_and(|a| a == "--help" || a == "-h") => {
+            print_options();
+            Some(Ok(()))
+        }
+        Some(name) if ["runs", "seq", "explain"].contains(&name) => Some(Err(format!(
+            "The '{}' subcommand is not part of this build. Rebuild with --features {}.", name, name))),
+        _ => None,
     };
+    if let Some(result) = subcommand {
+        if let Err(e) = result {
+            eprintln!("\x1b[31mFATAL: {}\x1b[0m", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let config = Config::from_args();
+    let infiles = &config.inputs();
 
-    println!("\nRunning the assembler with infile: \x1b[32m{}\x1b[0m and outfile: \x1b[32m{}\x1b[0m", infile, outfile);
+    println!("\nRunning the assembler with infile{}: \x1b[32m{}\x1b[0m and outfile: \x1b[32m{}\x1b[0m",
+        if infiles.len() == 1 { "" } else { "s" },
+        infiles.iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(", "), config.outfile.display());
     println!("If you would like to run with different files, use the program like this");
-    println!("\t\x1b[32msbh <infile_path> <outfile_path>\x1b[0m");
+    println!("\t\x1b[32msbh <infile_path> <outfile_path> [options]\x1b[0m");
+    println!("\tor, with several infiles:");
+    println!("\t\x1b[32msbh <infile_path>... -o <outfile_path> [options]\x1b[0m");
     println!("\tor, if you do not have an executable, you will have to recompile:");
-    println!("\t\x1b[32mcargo run -- <infile_path> <outfile_path>\x1b[0m\n");
+    println!("\t\x1b[32mcargo run -- <infile_path> <outfile_path> [options]\x1b[0m");
+    println!("Run \x1b[32msbh --help\x1b[0m for the options.");
+    println!("Using \x1b[32m{}\x1b[0m base encoding (set SBH_SIMD=scalar to disable).", simd::level().as_str());
+    #[cfg(feature = "http")]
+    sbh_assembler::http::RETRIES.store(config.http_retries, Ordering::Relaxed);
+    println!();
+
+    // Nodes of up to 31 bp are packed into a u64, longer ones into a Kmer of as many words as they need
+    if config.k > u64::MAX_K {
+        assemble::<Kmer>(&config, time_start);
+    } else {
+        assemble::<u64>(&config, time_start);
+    }
+}
+
+// Build the graph with nodes kept as K, assemble it and write the contigs and reports
+fn assemble<K: KmerRepr + 'static>(config: &Config, time_start: Instant) {
+    let outfile = &config.outfile;
+    // Without --from-gfa or --spectrum the graph is built from the reads
+    #[cfg(feature = "stats")]
+    let mut read_kmers = None;
+    // With --hpc, the run lengths to spell the contigs with
+    let mut run_lengths = None;
+    // The read count is only recorded with --record-run
+    #[cfg_attr(not(feature = "runs"), allow(unused_variables))]
+    let (mut ass, read_count, malformed): (Assembler<K>, _, _) = match &config.from_gfa {
+        Some(gfa) => (from_packed(load_gfa(gfa)), 0, vec![]),
+        None if config.infiles.is_empty() && config.pe1.is_none() && config.load_graph.is_some() => {
+            (load_graph(config.load_graph.as_deref().expect("checked")), 0, vec![])
+        }
+        None if config.spectrum => load_spectrum(config),
+        #[cfg(feature = "mmap")]
+        None if config.mmap => load_mmap(config),
+        None => {
+            let mut parsed = read_input(config);
+            // Interleaved mates are paired among the reads the parser kept, so a mate whose partner was outside the read
+            // lengths or malformed is left unpaired, as are pairs with a mate outside them in --pe1 and --pe2. The pairs
+            // are taken before the reads are subsampled, masked or split, and tied to the graph once it is built
+            let mut mates: utils::Mates = if config.interleaved {
+                let (pairs, unpaired) = utils::pair_mates(&parsed.names);
+                println!("Found \x1b[32m{}\x1b[0m mate pairs, \x1b[32m{}\x1b[0m reads were left unpaired.", pairs.len(), unpaired);
+                pairs.into_iter().map(|(a, b)| (parsed.reads[a].clone(), parsed.reads[b].clone())).collect()
+            } else {
+                vec![]
+            };
+            if let (Some(pe1), Some(pe2)) = (&config.pe1, &config.pe2) {
+                let (pe, pe_mates) = read_pairs(config, pe1, pe2);
+                println!("Found \x1b[32m{}\x1b[0m mate pairs in \x1b[32m{}\x1b[0m and \x1b[32m{}\x1b[0m.", pe_mates.len(), pe1.display(), pe2.display());
+                mates.extend(pe_mates);
+                parsed.append(pe);
+            }
+            report_rejected(parsed.rejected, &config.read_lens());
+            let utils::Parsed { reads, quals, malformed, .. } = parsed;
+            let (reads, quals) = if config.subsample.is_some() || config.max_reads.is_some() {
+                let total = reads.len();
+                let (reads, quals) = utils::subsample(reads, quals, config.subsample, config.max_reads, config.seed);
+                println!("Subsampled \x1b[32m{}\x1b[0m of \x1b[32m{}\x1b[0m reads (seed {}).", reads.len(), total, config.seed);
+                (reads, quals)
+            } else {
+                (reads, quals)
+            };
+            let (reads, quals, masked) = utils::soft_mask(reads, quals, config.soft_masked);
+            if masked > 0 {
+                println!("Dropped \x1b[32m{}\x1b[0m reads with soft-masked bases.", masked);
+            }
+            let (reads, quals) = if config.hpc {
+                println!("Compressing homopolymer runs....................");
+                let mut lengths = hpc::RunLengths::new(config.k);
+                let (reads, quals, dropped) = hpc::compress_reads(reads, quals, &config.read_lens(), &mut lengths);
+                if dropped > 0 {
+                    println!("\tDropped \x1b[32m{}\x1b[0m reads that are too short or too long once compressed.", dropped);
+                }
+                for (a, b) in mates.iter_mut() {
+                    *a = hpc::compress(a).0;
+                    *b = hpc::compress(b).0;
+                }
+                run_lengths = Some(lengths);
+                (reads, quals)
+            } else {
+                (reads, quals)
+            };
+            let (reads, quals) = match config.split_n_runs {
+                Some(min_run) => {
+                    let (reads, quals, split) = utils::split_n_runs(reads, quals, min_run, &config.read_lens());
+                    println!("Split \x1b[32m{}\x1b[0m reads at runs of {} or more Ns.", split, min_run);
+                    (reads, quals)
+                }
+                None => (reads, quals),
+            };
+            // In de Bruijn mode windows with ambiguous bases are left out when the reads are split, so long reads
+            // are not dropped whole by the default skip policy
+            let resolved = if config.debruijn && config.n_policy == NPolicy::Skip {
+                Ok((reads, quals, 0, 0))
+            } else {
+                utils::resolve_ns(reads, quals, config.n_policy, config.k, config.read_lens(), config.seed)
+            };
+            let (reads, quals, skipped, resolved) = match resolved {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    eprintln!("\x1b[31mFATAL: {} Choose another --n-policy to assemble reads with ambiguous bases.\x1b[0m", e);
+                    std::process::exit(1);
+                }
+            };
+            if skipped + resolved > 0 {
+                println!("Skipped \x1b[32m{}\x1b[0m and resolved \x1b[32m{}\x1b[0m reads with ambiguous bases.", skipped, resolved);
+            }
+
+            if let Some(frac) = config.sample_edges {
+                preview(config, reads, frac);
+                report_malformed(&malformed);
+                return;
+            }
+
+            // The QC metrics compare the contigs against the reads, which the assembler consumes
+            #[cfg(feature = "stats")]
+            { read_kmers = config.stats_json.as_ref().map(|_| KmerCounts::from_reads(&reads, stats::qc_k(config.k))); }
+
+            let read_count = reads.len();
+            let weighted = match config.collapse_duplicates {
+                Some(mismatches) => {
+                    println!("Collapsing duplicate reads......................");
+                    let weighted = utils::consensus_reads(reads, &quals, mismatches);
+                    println!("\tCollapsed \x1b[32m{}\x1b[0m reads into \x1b[32m{}\x1b[0m consensus reads.", read_count, weighted.len());
+                    weighted
+                }
+                None if config.dedup => {
+                    println!("Collapsing exact duplicate reads................");
+                    let weighted = utils::dedup_reads(reads);
+                    println!("\tCollapsed \x1b[32m{}\x1b[0m reads into \x1b[32m{}\x1b[0m distinct reads.", read_count, weighted.len());
+                    weighted
+                }
+                None => reads.into_iter().map(|r| (r, 1)).collect(),
+            };
+            let weighted = edge_reads(config, weighted);
+            let reads = weighted.par_iter().map(|(r, n)| (r.as_slice(), *n));
+            let mut ass = match &config.load_graph {
+                Some(path) => {
+                    let mut ass = load_graph(path);
+                    if ass.k != config.k {
+                        eprintln!("\x1b[31mFATAL: The graph in {} has k = {}, give the same --kmer-size to add reads to it.\x1b[0m", path.display(), ass.k);
+                        std::process::exit(1);
+                    }
+                    let dropped = stored(ass.add_reads(reads.collect::<Vec<_>>()));
+                    println!("\tAdded \x1b[32m{}\x1b[0m reads, dropping \x1b[32m{}\x1b[0m walks through the nodes they touch.", read_count, dropped);
+                    ass
+                }
+                #[cfg(feature = "boss")]
+                None if config.boss => boss_unitigs(config, &weighted),
+                None => Assembler::from_slices_par(node_table(config, config.k), reads, config.k),
+            };
+            if config.interleaved || config.pe1.is_some() {
+                let kept = ass.add_mates(&mates);
+                println!("Kept \x1b[32m{}\x1b[0m mate pairs whose ends are in the graph.", kept);
+            }
+            (ass, read_count, malformed)
+        }
+    };
+    ass.paranoid = config.paranoid;
+    ass.traversal = config.traversal;
+    ass.backtrack = config.backtrack;
+    ass.attempts = config.attempts;
+    ass.seed = config.seed;
+    ass.parallel = config.parallel_walks;
+    ass.deterministic = config.deterministic_walks;
+    #[cfg(feature = "mmap")]
+    if config.disk_graph {
+        let dir = config.tmp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        if let Err(e) = DiskGraph::new(&dir).and_then(|storage| ass.graph.move_to(Box::new(storage))) {
+            eprintln!("\x1b[31mFATAL: Failed to move the graph to {}: {}\x1b[0m", dir.display(), e);
+            std::process::exit(1);
+        }
+        println!("Moved \x1b[32m{}\x1b[0m edges to mapped files in \x1b[32m{}\x1b[0m", ass.graph.len(), dir.display());
+    }
+    if config.canonical {
+        let nodes = ass.nodes.len();
+        stored(ass.make_canonical());
+        println!("Joined both strands: \x1b[32m{}\x1b[0m nodes became \x1b[32m{}\x1b[0m with their reverse complements.", nodes, ass.nodes.len());
+    }
+    if config.paranoid { ass.check_degrees(); }
+    let samples = if config.colors { color_edges(config, &mut ass) } else { vec![] };
+    let read_names = if config.read_provenance { trace_reads(config, &mut ass) } else { vec![] };
+    if let Some(path) = &config.save_graph {
+        if let Err(e) = ass.save_graph(path) {
+            eprintln!("\x1b[31mFATAL: Failed to write the graph to {}: {}\x1b[0m", path.display(), e);
+            std::process::exit(1);
+        }
+        println!("Wrote the graph to \x1b[32m{}\x1b[0m", path.display());
+    }
+
+    // A stage named with --stages takes its flag as its setting and runs in its place among the stages
+    if let Some(min_reads) = config.min_edge_coverage.filter(|_| !config.runs_stage("min-edge-coverage")) {
+        println!("Removing weak links.............................");
+        let links = ass.graph.links().count();
+        let removed = stored(ass.remove_weak_links(min_reads));
+        println!("\tRemoved \x1b[32m{}\x1b[0m of \x1b[32m{}\x1b[0m links supported by fewer than {} reads.", removed, links, min_reads);
+    }
+
+    if let Some(max_len) = config.clip_tips.filter(|_| !config.runs_stage("clip-tips")) {
+        println!("Clipping tips...................................");
+        let nodes = stored(ass.clip_tips(max_len, config.tip_max_cov.unwrap_or(f32::INFINITY)));
+        println!("\tRemoved \x1b[32m{}\x1b[0m nodes in dead-end branches up to {} bp.", nodes, max_len);
+    }
+
+    if let Some(max_len) = config.pop_bubbles.filter(|_| !config.runs_stage("pop-bubbles")) {
+        println!("Popping bubbles.................................");
+        let bubbles = stored(ass.pop_bubbles(max_len));
+        println!("\tPopped \x1b[32m{}\x1b[0m branches up to {} bp.", bubbles.len(), max_len);
+        if let Some(report) = &config.bubble_report {
+            if let Err(e) = utils::bubbles2file(report, &bubbles) {
+                eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+                std::process::exit(1);
+            }
+            println!("\tWrote them to \x1b[32m{}\x1b[0m", report.display());
+        }
+    }
+
+    if config.clean_iterations > 0 && !config.runs_stage("clean") {
+        println!("Iteratively cleaning the graph..................");
+        let removed = stored(ass.iterative_clean(config.clean_iterations, config.clean_decay, config.clean_min_weight));
+        println!("\tRemoved \x1b[32m{}\x1b[0m unsupported edges.", removed);
+    }
+
+    if let Some(dir) = &config.export_graph_tables {
+        println!("Exporting graph tables to \x1b[32m{}\x1b[0m...", dir.display());
+        if let Err(e) = graph_tables::export_graph_tables(&ass, dir, ass.k) {
+            eprintln!("\x1b[31mFATAL: Failed to export graph tables: {}\x1b[0m", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(gfa) = &config.unitig_gfa {
+        let unitigs = unitig::compact(&ass);
+        println!("Compacted \x1b[32m{}\x1b[0m nodes into \x1b[32m{}\x1b[0m unitigs.", ass.nodes.len(), unitigs.segments());
+        if let Err(e) = unitig::write_gfa(gfa, &ass, &unitigs) {
+            eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", gfa.display(), e);
+            std::process::exit(1);
+        }
+        println!("Wrote the unitig graph to \x1b[32m{}\x1b[0m", gfa.display());
+    }
+
+    if let Some(report) = &config.eulerian_report {
+        eulerian_report(&ass, report);
+    }
+
+    match &config.stages {
+        Some(names) => run_stages(config, packed(&mut ass), names),
+        None => default_pipeline(config, &mut ass),
+    }
+
+    if let Some(lengths) = &run_lengths {
+        println!("Restoring homopolymer runs in the contigs.......");
+        ass.contigs.iter_mut().for_each(|c| c.seq = lengths.expand(&c.seq));
+    }
+
+    let lcont = ass.contigs.iter()
+        .max_by(|a, b| a.len().cmp(&b.len()))
+        .cloned()
+        .unwrap_or_default();
+    println!("Longest generated contig was \x1b[32m{}\x1b[0m nucleotides.", lcont.len());
+
+    // Contigs well above single copy coverage are likely collapsed repeats or multi-copy plasmids
+    let modal_coverage = ass.modal_coverage();
+    if config.copy_number {
+        println!("Modal node coverage is \x1b[32m{}\x1b[0m.", modal_coverage);
+        let multi: Vec<String> = ass.contigs.iter().enumerate()
+            .filter(|(_, c)| c.copy_number(modal_coverage) >= 1.5)
+            .map(|(i, c)| format!("sequence{} ({:.1}x)", i + 1, c.copy_number(modal_coverage)))
+            .collect();
+        println!("\tFound \x1b[32m{}\x1b[0m contigs with an estimated copy number of 1.5 or more.", multi.len());
+        if !multi.is_empty() {
+            println!("\t\t{}", multi.join(", "));
+        }
+    }
+
+    println!("Writing contigs to \x1b[32m{}\x1b[0m...................", outfile.display());
+    // With --async-io the contigs are written in the background while the reports below are generated
+    let mut write_behind: Option<WriteBehind> = None;
+    let written = if config.async_io {
+        let modal = config.copy_number.then_some(modal_coverage);
+        utils::contigs2fasta(&ass.contigs, modal).and_then(|bytes| {
+            let mut writes = WriteBehind::new()?;
+            writes.write(outfile, bytes);
+            write_behind = Some(writes);
+            Ok(())
+        })
+    } else if config.copy_number {
+        utils::cont2file_copy_number(outfile, &ass.contigs, modal_coverage)
+    } else {
+        utils::cont2file(outfile, &ass.contigs)
+    };
+    match written {
+        Ok(_) if config.async_io => println!("Writing contigs to \x1b[32m{}\x1b[0m in the background", outfile.display()),
+        Ok(_) => println!("Successfully wrote contigs to \x1b[32m{}\x1b[0m", outfile.display()),
+        Err(_) => {
+            eprintln!("\x1b[31mThere was an error writing to {}.\n
+                Please email me at masa20@lehigh.edu before you give me a 0.\x1b[0m", outfile.display());
+            std::process::exit(1);
+        }
+    }
+
+    if config.fai {
+        // The index is built from the written file
+        finish_writes(&mut write_behind);
+        match utils::write_fai(outfile) {
+            Ok(_) => println!("Wrote index to \x1b[32m{}.fai\x1b[0m", outfile.display()),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to index {}: {}\x1b[0m", outfile.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(bounds) = &config.split_output_by_length {
+        let dir = outfile.parent().unwrap_or(std::path::Path::new(""));
+        match utils::split_by_length(dir, &ass.contigs, bounds) {
+            Ok(classes) => for (fname, n) in classes {
+                println!("\tWrote \x1b[32m{}\x1b[0m contigs to \x1b[32m{}\x1b[0m", n, fname.display());
+            },
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to split contigs by length: {}\x1b[0m", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(feature = "explain")]
+    if config.provenance {
+        let report = utils::with_suffix(outfile, explain::PROVENANCE_SUFFIX);
+        match explain::write_provenance(&report, packed(&mut ass)) {
+            Ok(_) => println!("Wrote contig provenance to \x1b[32m{}\x1b[0m", report.display()),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if config.colors {
+        let report = utils::with_suffix(outfile, ".colors.tsv");
+        let all = u64::MAX >> (64 - samples.len());
+        let shared = ass.contigs.iter().filter(|c| c.colors == all).count();
+        println!("Found \x1b[32m{}\x1b[0m contigs whole in every sample.", shared);
+        for (color, name) in samples.iter().enumerate() {
+            let unique = ass.contigs.iter().filter(|c| c.colors == 1 << color && c.any_colors == 1 << color).count();
+            println!("\tFound \x1b[32m{}\x1b[0m contigs only in \x1b[32m{}\x1b[0m.", unique, name);
+        }
+        let mosaic = ass.contigs.iter().filter(|c| c.any_colors != c.colors).count();
+        println!("\tFound \x1b[32m{}\x1b[0m contigs with edges that only some of their samples have.", mosaic);
+        match utils::colors2file(&report, &ass.contigs, &samples) {
+            Ok(_) => println!("Wrote the samples of each contig to \x1b[32m{}\x1b[0m", report.display()),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if config.read_provenance {
+        let report = utils::with_suffix(outfile, ".reads.tsv");
+        let traced = ass.contigs.iter().filter(|c| !c.reads.is_empty()).count();
+        let reads: HashSet<u32> = ass.contigs.iter().flat_map(|c| c.reads.iter().map(|s| s.read)).collect();
+        println!("Found \x1b[32m{}\x1b[0m of \x1b[32m{}\x1b[0m reads along \x1b[32m{}\x1b[0m of \x1b[32m{}\x1b[0m contigs.",
+            reads.len(), read_names.len(), traced, ass.contigs.len());
+        match utils::reads2file(&report, &ass.contigs, &read_names) {
+            Ok(_) => println!("Wrote where each read is along the contigs to \x1b[32m{}\x1b[0m", report.display()),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(report) = &config.break_points {
+        match utils::break_points2file(report, &ass.contigs, ass.k) {
+            Ok(_) => println!("Wrote contig break points to \x1b[32m{}\x1b[0m", report.display()),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    if let (Some(report), Some(read_kmers)) = (&config.stats_json, &read_kmers) {
+        match stats::write_stats_json(report, &ass.contigs, read_kmers) {
+            Ok((qv, completeness)) => {
+                match qv {
+                    Some(qv) => println!("Estimated assembly QV is \x1b[32m{:.1}\x1b[0m.", qv),
+                    None => println!("Found no contig k-mers missing from the reads, QV is unbounded."),
+                }
+                println!("Contigs contain \x1b[32m{:.2}%\x1b[0m of the solid read k-mers.", completeness * 100.0);
+                println!("Wrote assembly stats to \x1b[32m{}\x1b[0m", report.display());
+            }
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(report) = &config.near_miss_report {
+        println!("Writing near-miss merges to \x1b[32m{}\x1b[0m...", report.display());
+        let near_misses = ass.near_miss_merges(config.min_overlap, config.min_overlap_frac, 2);
+        match utils::near_misses2file(report, &near_misses) {
+            Ok(_) => println!("\tFound \x1b[32m{}\x1b[0m near-miss merges.", near_misses.len()),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(db) = &config.taxonomy_db {
+        println!("Annotating contigs with taxonomy from \x1b[32m{}\x1b[0m...", db.display());
+        let report = utils::with_suffix(outfile, ".taxonomy.tsv");
+        let composition = KmerTaxonomy::load(db).and_then(|tax| tax.annotate(&report, &ass.contigs));
+        match composition {
+            Ok(composition) => {
+                for (taxon, n, len) in composition {
+                    println!("\t\x1b[32m{}\x1b[0m: {} contigs, {} nucleotides", taxon, n, len);
+                }
+                println!("Wrote taxonomy report to \x1b[32m{}\x1b[0m", report.display());
+            }
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to annotate taxonomy: {}\x1b[0m", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    finish_writes(&mut write_behind);
+    report_malformed(&malformed);
+    let duration = time_start.elapsed();
+    println!("Assembly took \x1b[32m{}\x1b[0m seconds.", duration.as_secs_f32());
+
+    #[cfg(feature = "runs")]
+    if config.record_run {
+        match runs::record_run(config, read_count, &ass.contigs, duration.as_secs_f32()) {
+            Ok(id) => println!("Recorded run \x1b[32m{}\x1b[0m in \x1b[32m{}\x1b[0m", id, runs::registry_path().display()),
+            Err(e) => eprintln!("\x1b[31mFailed to record run: {}\x1b[0m", e),
+        }
+    }
+}
+
+// The reads that become edges: (k+1)-mers with --debruijn, without rare k-mers with --min-kmer-count
+fn edge_reads(config: &Config, weighted: Vec<(Vec<u8>, usize)>) -> Vec<(Vec<u8>, usize)> {
+    let counter = || config.kmer_counter(utils::kmer_occurrences(&weighted, config.k, config.debruijn));
+    if let Some(mb) = config.memory_limit {
+        let dir = config.tmp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        println!("Counting {}-mers on disk in \x1b[32m{}\x1b[0m...", config.k + 1, dir.display());
+        let min = config.kmer_threshold().unwrap_or(1);
+        let (windows, dropped) = match external::kmerize(&weighted, config.k, config.canonical, min, &dir, mb << 20) {
+            Ok(counted) => counted,
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to count k-mers in {}: {}\x1b[0m", dir.display(), e);
+                std::process::exit(1);
+            }
+        };
+        if min > 1 {
+            println!("\tDropped \x1b[32m{}\x1b[0m {}-mers seen fewer than {} times.", dropped, config.k + 1, min);
+        }
+        println!("\tFound \x1b[32m{}\x1b[0m distinct {}-mers.", windows.len(), config.k + 1);
+        windows
+    } else if let Some(w) = config.sparse {
+        println!("Splitting reads between minimizers..............");
+        let windows = match counter() {
+            Some((mut counter, min)) => {
+                let (windows, dropped) = utils::kmerize_sparse(&weighted, config.k, w, config.canonical, Some((counter.as_mut(), min)));
+                println!("\tDropped \x1b[32m{}\x1b[0m windows seen fewer than {} times.", dropped, min);
+                windows
+            }
+            None => utils::kmerize_sparse(&weighted, config.k, w, config.canonical, None).0,
+        };
+        let kmers: usize = weighted.iter().map(|(read, _)| read.len().saturating_sub(config.k)).sum();
+        println!("\tFound \x1b[32m{}\x1b[0m distinct windows between ({}, {}) minimizers, from \x1b[32m{}\x1b[0m {}-mers.",
+            windows.len(), w, config.k, kmers, config.k + 1);
+        windows
+    } else if config.debruijn {
+        println!("Splitting reads into {}-mers.......................", config.k + 1);
+        let windows = match counter() {
+            Some((mut counter, min)) => {
+                let (windows, dropped) = utils::kmerize_solid(&weighted, config.k, config.canonical, counter.as_mut(), min);
+                println!("\tDropped \x1b[32m{}\x1b[0m {}-mers seen fewer than {} times.", dropped, config.k + 1, min);
+                windows
+            }
+            None => utils::kmerize(weighted.iter().map(|(r, n)| (r.as_slice(), *n)), config.k),
+        };
+        println!("\tFound \x1b[32m{}\x1b[0m distinct {}-mers.", windows.len(), config.k + 1);
+        windows
+    } else if let Some((mut counter, min)) = counter() {
+        println!("Dropping reads with rare ends...................");
+        let (kept, dropped) = utils::drop_rare_ends(weighted, config.k, config.canonical, counter.as_mut(), min);
+        println!("\tDropped \x1b[32m{}\x1b[0m reads whose prefix or suffix is seen fewer than {} times.", dropped, min);
+        kept
+    } else {
+        weighted
+    }
+}
+
+// Build the graph from a sample of the reads, report its topology and write it as GFA
+fn preview(config: &Config, reads: Vec<Vec<u8>>, frac: f64) {
+    let total = reads.len();
+    let reads = utils::sample_reads(reads, frac, config.seed);
+    println!("Sampled \x1b[32m{}\x1b[0m of \x1b[32m{}\x1b[0m reads (seed {}).", reads.len(), total, config.seed);
+    let reads = edge_reads(config, reads.into_iter().map(|r| (r, 1)).collect());
+    let mut ass = Assembler::from_slices_par(node_table(config, config.k), reads.par_iter().map(|(r, n)| (r.as_slice(), *n)), config.k);
+    if config.canonical { stored(ass.make_canonical()); }
+    let t = ass.topology();
+    println!("Graph topology preview..........................");
+    println!("\tNodes: \x1b[32m{}\x1b[0m, links: \x1b[32m{}\x1b[0m, edges: \x1b[32m{}\x1b[0m", t.nodes, t.links, t.edges);
+    println!("\tSources: \x1b[32m{}\x1b[0m, sinks: \x1b[32m{}\x1b[0m, balanced: \x1b[32m{}\x1b[0m", t.sources, t.sinks, t.balanced);
+    println!("\tBranching nodes: \x1b[32m{}\x1b[0m, max degree: \x1b[32m{}\x1b[0m", t.branching, t.max_degree);
+    println!("\tWeakly connected components: \x1b[32m{}\x1b[0m", t.components);
+    println!("\tUnitigs: \x1b[32m{}\x1b[0m", unitig::compact(&ass).segments());
+    let gfa = utils::with_suffix(&config.outfile, ".preview.gfa");
+    match utils::graph2gfa(&gfa, &ass) {
+        Ok(_) => println!("Wrote preview graph to \x1b[32m{}\x1b[0m", gfa.display()),
+        Err(e) => {
+            eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", gfa.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Report whether one walk can take every edge, and if not how far the graph is from it, before any is taken
+fn eulerian_report<K: KmerRepr>(ass: &Assembler<K>, report: &Path) {
+    println!("Checking the Eulerian conditions................");
+    let eulerian = ass.eulerian();
+    let connected = eulerian.components.iter().filter(|b| b.edges > 0).count();
+    match eulerian.walk {
+        EulerianWalk::Circuit => println!("\tThe graph has an Eulerian circuit."),
+        EulerianWalk::Path => println!("\tThe graph has an Eulerian path."),
+        EulerianWalk::Neither => println!("\tThe graph has no Eulerian path or circuit, it takes at least \x1b[32m{}\x1b[0m walks to cover its edges.",
+            eulerian.min_walks()),
+    }
+    println!("\tFound \x1b[32m{}\x1b[0m unbalanced nodes and \x1b[32m{}\x1b[0m components with edges, \x1b[32m{}\x1b[0m of them with an Eulerian walk of their own.",
+        eulerian.unbalanced(), connected, eulerian.components.iter().filter(|b| b.walk() != EulerianWalk::Neither).count());
+    if connected > 1 {
+        let cut: Vec<String> = eulerian.components.iter().enumerate().filter(|(_, b)| b.edges > 0).skip(1).take(5)
+            .map(|(i, b)| format!("{} ({} edges)", i, b.edges)).collect();
+        println!("\tDisconnected from the largest are components \x1b[32m{}\x1b[0m{}", cut.join(", "),
+            if connected > 6 { ", ..." } else { "" });
+    }
+    match utils::eulerian2file(report, &eulerian) {
+        Ok(_) => println!("Wrote the balance of each component to \x1b[32m{}\x1b[0m", report.display()),
+        Err(e) => {
+            eprintln!("\x1b[31mFATAL: Failed to write {}: {}\x1b[0m", report.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let reads = utils::fasta_reader(&infile);
-    let mut ass = Assembler::new(reads);
+// Find paths and cycles, spell them into contigs and condense them, reporting every step
+fn default_pipeline<K: KmerRepr>(config: &Config, ass: &mut Assembler<K>) {
+    // With --boss the unitigs are the contigs already
+    if config.parallel_components {
+        assemble_components_par(config, ass);
+    } else if config.components {
+        assemble_components(ass);
+    } else if !config.boss {
+        assemble_graph(ass);
+    }
 
+    println!("Condensing contigs..............................");
+    let mut metrics = config.merge_metrics.as_ref().map(|fname| {
+        let mut writer = match File::create(fname) {
+            Ok(f) => BufWriter::new(f),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to create {}: {}\x1b[0m", fname.display(), e);
+                std::process::exit(1);
+            }
+        };
+        let _ = writeln!(writer, "pass,thread,pairs_tested,overlaps_found,bytes_compared");
+        writer
+    });
+    let mut prev = usize::MAX;
+    let mut pass = 0;
+    loop {
+        pass += 1;
+        println!("\tRemoving Contained Contigs..............");
+        let removed = ass.remove_contained_contigs();
+        println!("\t\tRemoved \x1b[32m{}\x1b[0m contained contigs.", removed);
+        println!("\tMerging contigs. May take some time.....");
+        // Report the merge counters while merging so slow passes can be diagnosed
+        let stats = ass.merge_stats.clone();
+        let done = AtomicBool::new(false);
+        // With --string-graph, the overlaps found and how many of them were transitive
+        let (merged, reduced) = thread::scope(|s| {
+            s.spawn(|| {
+                let mut last = Instant::now();
+                while !done.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(100));
+                    if last.elapsed() < Duration::from_secs(1) { continue; }
+                    last = Instant::now();
+                    let (pairs, overlaps, bytes) = stats.totals();
+                    print!("\r\t\tTested {} pairs, found {} overlaps, compared {} bytes", pairs, overlaps, bytes);
+                    let _ = std::io::stdout().flush();
+                }
+            });
+            let merged = if config.string_graph {
+                let (graph, merged) = ass.merge_string_graph(config.min_overlap, config.min_overlap_frac);
+                (merged, Some((graph.overlaps, graph.transitive)))
+            } else {
+                (ass.merge_contigs(config.min_overlap, config.min_overlap_frac), None)
+            };
+            done.store(true, Ordering::Relaxed);
+            merged
+        });
+        let (pairs, overlaps, bytes) = stats.totals();
+        let busy: Vec<usize> = stats.threads.iter().map(|t| t.get().0).filter(|&p| p > 0).collect();
+        println!("\r\t\tTested \x1b[32m{}\x1b[0m pairs, found \x1b[32m{}\x1b[0m overlaps, compared \x1b[32m{}\x1b[0m bytes on \x1b[32m{}\x1b[0m threads (min {} / max {} pairs per thread).",
+            pairs, overlaps, bytes, busy.len(), busy.iter().min().unwrap_or(&0), busy.iter().max().unwrap_or(&0));
+        if let Some(writer) = metrics.as_mut() {
+            if let Err(e) = utils::merge_stats2csv(writer, pass, &stats) {
+                eprintln!("\x1b[31mFATAL: Failed to write merge metrics: {}\x1b[0m", e);
+                std::process::exit(1);
+            }
+        }
+        if let Some((overlaps, transitive)) = reduced {
+            println!("\t\tDropped \x1b[32m{}\x1b[0m of \x1b[32m{}\x1b[0m overlaps as transitive.", transitive, overlaps);
+        }
+        println!("\t\tMerged \x1b[32m{}\x1b[0m contigs.", merged);
+        if prev == ass.contigs.len() { break; }
+        prev = ass.contigs.len();
+    }
+    println!("Successfully condensed to \x1b[32m{}\x1b[0m contigs.", ass.contigs.len());
+}
+
+// Paths, cycles and contigs of the whole graph
+fn assemble_graph<K: KmerRepr>(ass: &mut Assembler<K>) {
     println!("Populating Paths................................");
     ass.populate_paths_or_cycles(PathType::Path);
     let lpath = ass.paths.iter()
@@ -40,40 +770,380 @@ fn main() {
         .unwrap_or_default();
     println!("Generated \x1b[32m{}\x1b[0m total cycles.", ass.cycles.len());
     println!("\tLongest generated cycle was \x1b[32m{}\x1b[0m nodes.", lcycle.len());
+    println!("\tAbandoned \x1b[32m{}\x1b[0m cycle walks that did not return to their start.", ass.abandoned.len());
 
     println!("Converting the paths and cycles to contigs......");
     ass.paths_cycles_to_contigs();
     println!("Generated \x1b[32m{}\x1b[0m contigs.", ass.contigs.len());
+}
 
-    println!("Condensing contigs..............................");
-    let mut prev = usize::MAX;
-    loop {
-        println!("\tRemoving Contained Contigs..............");
-        let removed = ass.remove_contained_contigs();
-        println!("\t\tRemoved \x1b[32m{}\x1b[0m contained contigs.", removed);
-        println!("\tMerging contigs. May take some time.....");
-        let merged = ass.merge_contigs(15);
-        println!("\t\tMerged \x1b[32m{}\x1b[0m contigs.", merged);
-        if prev == ass.contigs.len() { break; }
-        prev = ass.contigs.len();
+// Paths, cycles and contigs of each component in turn, largest first
+fn assemble_components<K: KmerRepr>(ass: &mut Assembler<K>) {
+    println!("Labelling components............................");
+    let components = ass.components();
+    let sizes: Vec<String> = components.nodes.iter().take(5).map(|ids| ids.len().to_string()).collect();
+    let singletons = components.nodes.iter().filter(|ids| ids.len() == 1).count();
+    println!("Found \x1b[32m{}\x1b[0m weakly connected components, \x1b[32m{}\x1b[0m of them a single node.",
+        components.nodes.len(), singletons);
+    println!("\tThe largest have \x1b[32m{}\x1b[0m nodes.", sizes.join(", "));
+
+    println!("Assembling components...........................");
+    let mut assembled = 0;
+    for ids in &components.nodes {
+        if ass.is_cancelled() { break; }
+        if ass.assemble_component(ids) > 0 { assembled += 1; }
     }
-    println!("Successfully condensed to \x1b[32m{}\x1b[0m contigs.", ass.contigs.len());
+    println!("Generated \x1b[32m{}\x1b[0m total paths and \x1b[32m{}\x1b[0m total cycles.", ass.paths.len(), ass.cycles.len());
+    println!("\tAbandoned \x1b[32m{}\x1b[0m cycle walks that did not return to their start.", ass.abandoned.len());
+    println!("Generated \x1b[32m{}\x1b[0m contigs in \x1b[32m{}\x1b[0m components.", ass.contigs.len(), assembled);
+}
 
-    let lcont = ass.contigs.iter()
-        .max_by(|a, b| a.len().cmp(&b.len()))
-        .cloned()
-        .unwrap_or_default();
-    println!("Longest generated contig was \x1b[32m{}\x1b[0m nucleotides.", lcont.len());
+// Paths, cycles and contigs of all components at once, each condensed on its own before they are condensed together
+fn assemble_components_par<K: KmerRepr>(config: &Config, ass: &mut Assembler<K>) {
+    println!("Labelling components............................");
+    let components = ass.components();
+    println!("Found \x1b[32m{}\x1b[0m weakly connected components.", components.nodes.len());
 
-    println!("Writing contigs to \x1b[32m{}\x1b[0m...................", outfile);
-    match utils::cont2file(&outfile, ass.contigs) {
-        Ok(_) => println!("Successfully wrote contigs to \x1b[32m{}\x1b[0m", outfile),
-        Err(_) => {
-            eprintln!("\x1b[31mThere was an error writing to {}.\n
-                Please email me at masa20@lehigh.edu before you give me a 0.\x1b[0m", outfile);
+    println!("Assembling and condensing components in parallel");
+    let assembled = ass.assemble_components_par(&components,
+        |sub| sub.condense(config.min_overlap, config.min_overlap_frac, config.string_graph));
+    println!("Generated \x1b[32m{}\x1b[0m total paths and \x1b[32m{}\x1b[0m total cycles.", ass.paths.len(), ass.cycles.len());
+    println!("\tAbandoned \x1b[32m{}\x1b[0m cycle walks that did not return to their start.", ass.abandoned.len());
+    println!("Generated \x1b[32m{}\x1b[0m condensed contigs in \x1b[32m{}\x1b[0m components.", ass.contigs.len(), assembled);
+}
+
+// Run the stages given with --stages from the built-in registry
+fn run_stages(config: &Config, ass: &mut Assembler, names: &[String]) {
+    let registry = StageRegistry::with_builtins();
+    println!("Running stages \x1b[32m{}\x1b[0m..........", names.join(","));
+    if let Err(e) = registry.run(names, ass, config) {
+        eprintln!("\x1b[31mFATAL: {}\x1b[0m", e);
+        std::process::exit(1);
+    }
+    println!("Generated \x1b[32m{}\x1b[0m contigs.", ass.contigs.len());
+}
+
+// Read the reads, and the quality strings if the input is FASTQ
+// FASTQ quality strings are only used to collapse duplicate reads
+// Several infiles are read one after the other into the same reads
+fn read_input(config: &Config) -> utils::Parsed {
+    let mut parsed = utils::Parsed::default();
+    for infile in &config.infiles {
+        parsed.append(read_file(config, infile, &config.read_options()));
+    }
+    parsed
+}
+
+// The table to keep the nodes of length k in, indexed by k-mer with --dense-nodes and hashed otherwise
+fn node_table<K: KmerRepr>(config: &Config, k: usize) -> Nodes<K> {
+    if config.siphash { return Nodes::with_hasher(NodeHasher::sip()); }
+    if !config.dense_nodes { return Nodes::default(); }
+    match Nodes::dense(k) {
+        Some(nodes) => nodes,
+        None => {
+            eprintln!("\x1b[31mFATAL: --dense-nodes needs k of at most {}, but k = {}.\x1b[0m", sbh_assembler::sbh_assembler::DENSE_MAX_K, k);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Build the succinct graph of the (k+1)-mers and make its unitigs the contigs of an assembler without a graph, to be
+// condensed like the contigs of walks
+#[cfg(feature = "boss")]
+fn boss_unitigs<K: KmerRepr>(config: &Config, windows: &[(Vec<u8>, usize)]) -> Assembler<K> {
+    println!("Building the succinct BOSS graph................");
+    let boss = Boss::new(windows, config.k, config.canonical);
+    println!("\tBuilt \x1b[32m{}\x1b[0m edges between \x1b[32m{}\x1b[0m nodes in \x1b[32m{}\x1b[0m bytes, {:.1} bits an edge.",
+        boss.edges, boss.nodes, boss.bytes(), 8.0 * boss.bytes() as f64 / boss.edges.max(1) as f64);
+    let unitigs = boss.unitigs();
+    println!("\tSpelled \x1b[32m{}\x1b[0m unitigs.", unitigs.len());
+    let mut ass = Assembler::from_graph(Graph::new(vec![], 0), node_table(config, config.k), config.k);
+    ass.contigs = unitigs.into_iter().enumerate()
+        .map(|(i, seq)| Contig::new(seq, 0.0, false, Source::Path(i), Default::default()))
+        .collect();
+    ass
+}
+
+// The graph of an option that only builds one of nodes packed into a u64, which Config refuses above k = 31
+fn from_packed<K: KmerRepr + 'static>(ass: Assembler) -> Assembler<K> {
+    *(Box::new(ass) as Box<dyn Any>).downcast().expect("Config refuses the option above k = 31")
+}
+
+// The graph for an option that only takes one of nodes packed into a u64, which Config refuses above k = 31
+fn packed<K: KmerRepr + 'static>(ass: &mut Assembler<K>) -> &mut Assembler {
+    (ass as &mut dyn Any).downcast_mut().expect("Config refuses the option above k = 31")
+}
+
+// Load a graph exported as GFA, such as the one written by --sample-edges
+fn load_gfa(gfa: &Path) -> Assembler {
+    println!("Loading the graph from \x1b[32m{}\x1b[0m...", gfa.display());
+    match utils::gfa2graph(gfa) {
+        Ok(ass) => {
+            println!("\tLoaded \x1b[32m{}\x1b[0m nodes and \x1b[32m{}\x1b[0m edges with k = {}.", ass.nodes.len(), ass.graph.count(), ass.k);
+            ass
+        }
+        Err(e) => {
+            eprintln!("\x1b[31mFATAL: Malformed GFA at {}\x1b[0m", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The result of a change to the graph, which fails if --disk-graph cannot write the changed graph
+fn stored<T>(result: io::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("\x1b[31mFATAL: Failed to store the changed graph: {}\x1b[0m", e);
+        std::process::exit(1);
+    })
+}
+
+// Load a graph written by --save-graph
+fn load_graph<K: KmerRepr>(path: &Path) -> Assembler<K> {
+    println!("Loading the graph from \x1b[32m{}\x1b[0m...", path.display());
+    match Assembler::load_graph(path) {
+        Ok(ass) => {
+            println!("\tLoaded \x1b[32m{}\x1b[0m nodes and \x1b[32m{}\x1b[0m edges with k = {}.", ass.nodes.len(), ass.graph.count(), ass.k);
+            ass
+        }
+        Err(e) => {
+            eprintln!("\x1b[31mFATAL: Failed to load the graph from {}: {}\x1b[0m", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Build the graph straight from k-mer spectra. Every k-mer is an edge between its prefix and suffix, weighted by its count
+fn load_spectrum<K: KmerRepr>(config: &Config) -> (Assembler<K>, usize, Vec<utils::ParseError>) {
+    println!("Reading k-mer spectra...........................");
+    let spectrum = match utils::read_spectrum(&config.infiles, config.strict) {
+        Ok(spectrum) => spectrum,
+        Err(e) => {
+            eprintln!("\x1b[31mFATAL: Malformed k-mer at {}\x1b[0m", e);
+            eprintln!("Run without --strict to skip malformed lines.");
+            std::process::exit(1);
+        }
+    };
+    let Some(len) = spectrum.kmers.first().map(|(kmer, _)| kmer.len()) else {
+        eprintln!("\x1b[31mFATAL: The spectrum has no k-mers.\x1b[0m");
+        std::process::exit(1);
+    };
+    if len - 1 > K::MAX_K {
+        eprintln!("\x1b[31mFATAL: The spectrum has {}-mers, give --kmer-size {} for nodes over {} bp.\x1b[0m", len, len - 1, K::MAX_K);
+        std::process::exit(1);
+    }
+    let observations = spectrum.kmers.iter().map(|(_, n)| n).sum();
+    println!("\tFound \x1b[32m{}\x1b[0m distinct {}-mers seen \x1b[32m{}\x1b[0m times, so k = {}.", spectrum.kmers.len(), len, observations, len - 1);
+    let ass = Assembler::from_slices_par(node_table(config, len - 1), spectrum.kmers.par_iter().map(|(r, n)| (r, *n)), len - 1);
+    (ass, observations, spectrum.malformed)
+}
+
+// Build the graph from slices of memory mapped FASTA infiles. Reads with bases other than ACGT are skipped, as with
+// the default --n-policy, and in de Bruijn mode only the windows with them are left out
+#[cfg(feature = "mmap")]
+fn load_mmap<K: KmerRepr>(config: &Config) -> (Assembler<K>, usize, Vec<utils::ParseError>) {
+    let options = config.read_options();
+    let mut files = vec![];
+    for infile in &config.infiles {
+        if utils::is_stdin(infile) || utils::is_url(infile) || config.format.unwrap_or_else(|| utils::format(infile)) != utils::Format::Fasta || utils::is_compressed(infile) {
+            eprintln!("\x1b[31mFATAL: --mmap needs uncompressed FASTA files, '{}' is not one.\x1b[0m", infile.display());
+            std::process::exit(1);
+        }
+        match sbh_assembler::mmap::map_fasta(infile, &options) {
+            Ok(reads) => files.push(reads),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Malformed record at {}\x1b[0m", e);
+                eprintln!("Run without --strict to skip malformed records.");
+                std::process::exit(1);
+            }
+        }
+    }
+    let mut rejected = utils::Rejected::default();
+    files.iter().for_each(|f| rejected.add(f.rejected));
+    report_rejected(rejected, &options.lens);
+    let read_count = files.iter().map(|f| f.len()).sum();
+    if !config.debruijn {
+        let is_base = |c: &u8| matches!(c.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T');
+        files.iter_mut().for_each(|f| f.retain(|read| read.iter().all(is_base)));
+        let skipped = read_count - files.iter().map(|f| f.len()).sum::<usize>();
+        if skipped > 0 {
+            println!("Skipped \x1b[32m{}\x1b[0m reads with ambiguous bases.", skipped);
+        }
+    }
+    let reads = files.iter().flat_map(|f| f.iter());
+    let reads: Box<dyn Iterator<Item = (&[u8], usize)>> = if config.dedup {
+        let kept = files.iter().map(|f| f.len()).sum::<usize>();
+        let weighted = utils::dedup_reads(reads);
+        println!("Collapsed \x1b[32m{}\x1b[0m reads into \x1b[32m{}\x1b[0m distinct reads.", kept, weighted.len());
+        Box::new(weighted.into_iter())
+    } else {
+        Box::new(reads.map(|read| (read, 1)))
+    };
+    let nodes = node_table(config, config.k);
+    let ass = if let Some(w) = config.sparse {
+        let reads: Vec<(&[u8], usize)> = reads.collect();
+        let (windows, _) = utils::kmerize_sparse(&reads, config.k, w, config.canonical, None);
+        Assembler::from_slices_par(nodes, windows.par_iter().map(|(r, n)| (r, *n)), config.k)
+    } else if config.debruijn {
+        let kmers = utils::kmerize(reads, config.k);
+        Assembler::from_slices_par(nodes, kmers.par_iter().map(|(r, n)| (r, *n)), config.k)
+    } else {
+        let reads: Vec<(&[u8], usize)> = reads.collect();
+        Assembler::from_slices_par(nodes, reads.into_par_iter(), config.k)
+    };
+    let malformed = files.iter_mut().flat_map(|f| std::mem::take(&mut f.malformed)).collect();
+    (ass, read_count, malformed)
+}
+
+// Read every infile again, split its reads into edges as the graph was built and tag them with the infile's bit.
+// Returns the sample names, the infile names up to their first dot
+fn color_edges<K: KmerRepr>(config: &Config, ass: &mut Assembler<K>) -> Vec<String> {
+    println!("Tagging the edges of each sample................");
+    let mut samples = vec![];
+    for (color, infile) in config.infiles.iter().enumerate() {
+        // Soft-masked bases are read as uppercase, as they were when the graph was built
+        let (reads, _, _) = utils::soft_mask(read_file(config, infile, &config.read_options()).reads, vec![], SoftMask::Keep);
+        let windows = match config.sparse {
+            Some(w) => {
+                let weighted: Vec<(&[u8], usize)> = reads.iter().map(|r| (r.as_slice(), 1)).collect();
+                utils::kmerize_sparse(&weighted, config.k, w, config.canonical, None).0
+            }
+            None if config.debruijn => utils::kmerize(reads.iter().map(|r| (r, 1)), config.k),
+            None => reads.into_iter().map(|r| (r, 1)).collect(),
+        };
+        let found = ass.add_colors(windows.iter().map(|(w, _)| w), color);
+        let name = infile.file_name().map(|f| f.to_string_lossy().split('.').next().unwrap_or_default().to_string())
+            .unwrap_or_else(|| infile.display().to_string());
+        println!("\t\x1b[32m{}\x1b[0m: found \x1b[32m{}\x1b[0m of its \x1b[32m{}\x1b[0m edges in the graph.", name, found, windows.len());
+        samples.push(name);
+    }
+    samples
+}
+
+// Read the infiles again and record which reads have each edge, see Assembler::add_read_ids. Returns the name of every
+// read, numbered across the infiles in the order they are read
+fn trace_reads<K: KmerRepr>(config: &Config, ass: &mut Assembler<K>) -> Vec<String> {
+    println!("Recording the reads of each edge................");
+    let options = utils::ReadOptions { names: true, ..config.read_options() };
+    let mut names: Vec<String> = vec![];
+    let mut found = 0;
+    for infile in &config.infiles {
+        let mut parsed = read_file(config, infile, &options);
+        let first = names.len();
+        if first + parsed.reads.len() > u32::MAX as usize {
+            eprintln!("\x1b[31mFATAL: --read-provenance numbers the reads in 32 bits, and {} takes them past {}.\x1b[0m", infile.display(), u32::MAX);
+            std::process::exit(1);
+        }
+        // Soft-masked bases are read as uppercase, as they were when the graph was built
+        let (reads, _, _) = utils::soft_mask(std::mem::take(&mut parsed.reads), vec![], SoftMask::Keep);
+        let windows = reads.iter().enumerate().flat_map(|(i, read)| {
+            let windows: Vec<&[u8]> = match config.sparse {
+                Some(w) => utils::sparse_windows(read, config.k, w, config.canonical),
+                None if config.debruijn => read.windows(config.k + 1).collect(),
+                None => vec![read],
+            };
+            windows.into_iter().map(move |w| ((first + i) as u32, w))
+        });
+        found += ass.add_read_ids(windows);
+        // Records without a name, such as bare sequence lines, are named by their number
+        names.extend((first..first + reads.len()).map(|i| {
+            parsed.names.get(i - first).filter(|name| !name.is_empty()).cloned().unwrap_or_else(|| format!("read{}", i + 1))
+        }));
+    }
+    println!("\tFound \x1b[32m{}\x1b[0m edges of \x1b[32m{}\x1b[0m reads in the graph.", found, names.len());
+    names
+}
+
+// Read the --pe1 and --pe2 files and pair their records
+// Both are read whole with their names, so a missing or extra record is caught before any reads are dropped
+fn read_pairs(config: &Config, pe1: &Path, pe2: &Path) -> (utils::Parsed, utils::Mates) {
+    let options = utils::ReadOptions { lens: 0..=usize::MAX, strict: config.strict, names: true, format: config.format };
+    let (first, second) = (read_file(config, pe1, &options), read_file(config, pe2, &options));
+    match utils::pair_files(first, second, &config.read_options().lens) {
+        Ok(paired) => paired,
+        Err(e) => {
+            eprintln!("\x1b[31mFATAL: {} The paired-end files must list the mates in the same order.\x1b[0m", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read_file(config: &Config, infile: &Path, options: &utils::ReadOptions) -> utils::Parsed {
+    // Trimming only shortens reads, so the long ones are read too and the lengths are checked once they are trimmed
+    let trim_options = utils::ReadOptions { lens: *options.lens.start()..=usize::MAX, ..*options };
+    let trims = config.adapters.is_some() || config.sliding_window.is_some();
+    let parse_options = if trims { &trim_options } else { options };
+    // stdin and URLs cannot be read in concurrent chunks
+    let parsed = if !config.async_io || utils::is_stdin(infile) || utils::is_url(infile) {
+        utils::read_reads(infile, parse_options)
+    } else {
+        let bytes = match async_io::read_file(infile).and_then(utils::decompress) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to read {}: {}\x1b[0m", infile.display(), e);
+                std::process::exit(1);
+            }
+        };
+        let format = options.format.unwrap_or_else(|| utils::Format::detect(infile, &bytes));
+        utils::parse_reads(&bytes, format, infile, parse_options)
+    };
+    let mut parsed = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("\x1b[31mFATAL: Malformed record at {}\x1b[0m", e);
+            eprintln!("Run without --strict to skip malformed records.");
+            std::process::exit(1);
+        }
+    };
+    if let Some(phred) = parsed.phred {
+        println!("Detected \x1b[32mPhred+{}\x1b[0m quality scores in {}.", phred, infile.display());
+    }
+    if let Some(adapters) = &config.adapters {
+        let adapters: Vec<Vec<u8>> = match utils::read_fasta(adapters) {
+            Ok(records) => records.into_iter().map(|r| r.seq).filter(|seq| !seq.is_empty()).collect(),
+            Err(e) => {
+                eprintln!("\x1b[31mFATAL: Failed to read the adapters from {}: {}\x1b[0m", adapters.display(), e);
+                std::process::exit(1);
+            }
+        };
+        let trimmed = parsed.trim_adapters(&adapters);
+        println!("Removed adapters from \x1b[32m{}\x1b[0m reads of {}.", trimmed, infile.display());
+    }
+    if let Some(window) = config.sliding_window {
+        let trimmed = parsed.trim_quality(window.size, window.min_qual);
+        println!("Trimmed low-quality tails off \x1b[32m{}\x1b[0m reads of {}.", trimmed, infile.display());
+    }
+    if trims { parsed.keep_lens(&options.lens); }
+    parsed
+}
+
+// Say how many reads were dropped for being too short or too long
+fn report_rejected(rejected: utils::Rejected, lens: &RangeInclusive<usize>) {
+    if rejected.short > 0 {
+        println!("Rejected \x1b[32m{}\x1b[0m reads shorter than {} bp (--min-read-len).", rejected.short, lens.start());
+    }
+    if rejected.long > 0 {
+        println!("Rejected \x1b[32m{}\x1b[0m reads longer than {} bp (--max-read-len).", rejected.long, lens.end());
+    }
+}
+
+// Summarize the malformed records that were skipped, showing the first few
+fn report_malformed(malformed: &[utils::ParseError]) {
+    if malformed.is_empty() { return; }
+    println!("Skipped \x1b[32m{}\x1b[0m malformed input records.", malformed.len());
+    for e in malformed.iter().take(5) {
+        println!("\t{}", e);
+    }
+    if malformed.len() > 5 {
+        println!("\t... and {} more", malformed.len() - 5);
+    }
+}
+
+// Wait for the background writes started with --async-io, if any
+fn finish_writes(write_behind: &mut Option<WriteBehind>) {
+    if let Some(writes) = write_behind.take() {
+        if let Err(e) = writes.finish() {
+            eprintln!("\x1b[31mFATAL: Failed to write {}\x1b[0m", e);
             std::process::exit(1);
         }
     }
-    let duration = time_start.elapsed();
-    println!("Assembly took \x1b[32m{}\x1b[0m seconds.", duration.as_secs_f32());
 }
@@ -0,0 +1,119 @@
+// Zero-copy FASTA reading for --mmap. The file is memory mapped and reads are kept as byte ranges into it,
+// so a multi-GB input does not need an allocation per read before the graph is built
+use crate::utils::{ self, ParseError, ReadOptions, Rejected };
+use memmap2::Mmap;
+use std::{ fs::File, ops::Range, path::Path };
+
+// Reads of a mapped FASTA file. Records wrapped over several lines cannot be one slice of the map, so they
+// are joined into a side buffer. Ranges past the end of the map point into that buffer
+pub struct MappedReads {
+    map: Mmap,
+    joined: Vec<u8>,
+    ranges: Vec<Range<usize>>,
+    pub malformed: Vec<ParseError>,
+    pub rejected: Rejected,
+}
+
+impl MappedReads {
+    pub fn get(&self, i: usize) -> &[u8] {
+        let range = self.ranges[i].clone();
+        if range.start < self.map.len() {
+            &self.map[range]
+        } else {
+            &self.joined[range.start - self.map.len()..range.end - self.map.len()]
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    // Keep only the reads for which keep returns true
+    pub fn retain(&mut self, keep: impl Fn(&[u8]) -> bool) {
+        let keep: Vec<bool> = self.iter().map(keep).collect();
+        let mut keep = keep.into_iter();
+        self.ranges.retain(|_| keep.next().unwrap_or(false));
+    }
+}
+
+// Reads found so far, kept apart from the map while it is being scanned
+#[derive(Default)]
+struct Found {
+    joined: Vec<u8>,
+    ranges: Vec<Range<usize>>,
+    malformed: Vec<ParseError>,
+    rejected: Rejected,
+}
+
+impl Found {
+    fn malformed(&mut self, error: ParseError, strict: bool) -> Result<(), ParseError> {
+        if strict { return Err(error); }
+        self.malformed.push(error);
+        Ok(())
+    }
+
+    // Add a record given as its header line number and sequence lines
+    fn finish(&mut self, map: &[u8], record: (usize, Vec<Range<usize>>), fname: &Path, options: &ReadOptions) -> Result<(), ParseError> {
+        let (line, lines) = record;
+        let len: usize = lines.iter().map(|l| l.len()).sum();
+        if len == 0 {
+            return self.malformed(utils::parse_error(fname, line, 1, "record has no sequence".to_string()), options.strict);
+        }
+        if !self.rejected.keep(len, &options.lens) { return Ok(()); }
+        if let [range] = lines.as_slice() {
+            self.ranges.push(range.clone());
+        } else {
+            let start = map.len() + self.joined.len();
+            for range in lines { self.joined.extend_from_slice(&map[range]); }
+            self.ranges.push(start..start + len);
+        }
+        Ok(())
+    }
+}
+
+// Map an uncompressed FASTA file and find its reads, with the same rules and errors as utils::fasta_reads
+pub fn map_fasta(fname: &Path, options: &ReadOptions) -> Result<MappedReads, ParseError> {
+    let io_error = |e: std::io::Error| utils::parse_error(fname, 0, 1, format!("cannot map the file: {}", e));
+    let file = File::open(fname).map_err(io_error)?;
+    // Safety: the map is read only. Like any mmap reader, this assumes the file is not truncated while it is read
+    let map = unsafe { Mmap::map(&file) }.map_err(io_error)?;
+    let mut found = Found::default();
+    // Header line and sequence lines of the current record, and whether it is malformed
+    let mut record: Option<(usize, Vec<Range<usize>>)> = None;
+    let mut malformed = false;
+    let mut start = 0;
+    for (i, line) in map.split(|&b| b == b'\n').enumerate() {
+        let range = start..start + line.len() - usize::from(line.ends_with(b"\r"));
+        start += line.len() + 1;
+        let line = &map[range.clone()];
+        if line.starts_with(b">") {
+            if let Some(previous) = record.replace((i + 1, vec![])) {
+                if !malformed { found.finish(&map, previous, fname, options)?; }
+            }
+            malformed = false;
+            continue;
+        }
+        if let Some(column) = line.iter().position(|&c| !utils::is_sequence_char(c)) {
+            let message = format!("unexpected character {:?} in sequence", line[column] as char);
+            found.malformed(utils::parse_error(fname, i + 1, column + 1, message), options.strict)?;
+            malformed = true;
+            continue;
+        }
+        match record.as_mut() {
+            Some((_, lines)) => if !line.is_empty() { lines.push(range) },
+            None => if found.rejected.keep(line.len(), &options.lens) { found.ranges.push(range) },
+        }
+    }
+    if let Some(record) = record {
+        if !malformed { found.finish(&map, record, fname, options)?; }
+    }
+    Ok(MappedReads { map, joined: found.joined, ranges: found.ranges, malformed: found.malformed, rejected: found.rejected })
+}
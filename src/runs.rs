@@ -0,0 +1,132 @@
+use crate::{ build_info, config::Config, contig::Contig, utils };
+use serde_json::{ json, Value };
+use std::{
+    env,
+    fs::{ self, File, OpenOptions },
+    ffi::OsString,
+    io::{ BufRead, BufReader, Read, Write },
+    path::PathBuf,
+    time::{ SystemTime, UNIX_EPOCH },
+};
+
+// The registry is a JSONL file with one record per run, in $SBH_HOME/runs or ~/.sbh/runs
+pub fn registry_path() -> PathBuf {
+    let home = env::var_os("SBH_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".sbh")))
+        .unwrap_or_else(|| PathBuf::from(".sbh"));
+    home.join("runs").join("runs.jsonl")
+}
+
+// FNV-1a hash of the input files, in order, so a run can be tied to the exact input it used
+pub fn hash_files(fnames: &[PathBuf]) -> std::io::Result<(String, u64)> {
+    let mut buf = vec![0; 1 << 16];
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut bytes = 0;
+    for fname in fnames {
+        let mut reader = File::open(fname)?;
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 { break; }
+            bytes += n as u64;
+            for &b in &buf[..n] {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+    }
+    Ok((format!("fnv1a64:{:016x}", hash), bytes))
+}
+
+// Append a record describing a finished run and return its id
+pub fn record_run(config: &Config, reads: usize, contigs: &[Contig], seconds: f32) -> std::io::Result<String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let id = format!("{}-{}", now, std::process::id());
+    let inputs = config.inputs();
+    let (input_hash, input_bytes) = if inputs.iter().any(|f| utils::is_stdin(f)) {
+        ("stdin".to_string(), 0)
+    } else if inputs.iter().any(|f| utils::is_url(f)) {
+        ("url".to_string(), 0)
+    } else {
+        hash_files(&inputs)?
+    };
+    let lens: Vec<usize> = contigs.iter().map(|c| c.len()).collect();
+    let record = json!({
+        "id": id,
+        "timestamp": now,
+        "args": env::args_os().skip(1).map(|a| a.to_string_lossy().into_owned()).collect::<Vec<String>>(),
+        "infile": inputs.iter().map(|f| f.to_string_lossy()).collect::<Vec<_>>().join(","),
+        "input_hash": input_hash,
+        "input_bytes": input_bytes,
+        "outfile": config.outfile.to_string_lossy(),
+        "params": {
+            "k": config.k,
+            "min_overlap": config.min_overlap,
+            "min_overlap_frac": config.min_overlap_frac,
+            "clean_iterations": config.clean_iterations,
+            "clean_decay": config.clean_decay,
+            "clean_min_weight": config.clean_min_weight,
+            "seed": config.seed,
+        },
+        "metrics": {
+            "reads": reads,
+            "contigs": lens.len(),
+            "longest": lens.iter().max().copied().unwrap_or(0),
+            "total": lens.iter().sum::<usize>(),
+            "n50": utils::n50(&lens),
+            "seconds": seconds,
+        },
+        "build": build_info::to_json(),
+    });
+    let path = registry_path();
+    if let Some(dir) = path.parent() { fs::create_dir_all(dir)?; }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", record)?;
+    Ok(id)
+}
+
+// Every recorded run, oldest first. Unparsable lines are skipped
+pub fn load_runs() -> std::io::Result<Vec<Value>> {
+    let file = match File::open(registry_path()) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut runs = vec![];
+    for line in BufReader::new(file).lines() {
+        if let Ok(run) = serde_json::from_str::<Value>(&line?) { runs.push(run); }
+    }
+    Ok(runs)
+}
+
+// Entry point for `sbh runs list` and `sbh runs show <id|last>`
+pub fn runs_command(args: &[OsString]) -> Result<(), String> {
+    let args: Vec<String> = args.iter().map(|a| a.to_string_lossy().into_owned()).collect();
+    let runs = load_runs().map_err(|e| format!("Failed to read {}: {}", registry_path().display(), e))?;
+    match args.first().map(|a| a.as_str()) {
+        Some("list") | None => {
+            println!("{:<24}{:<12}{:>10}{:>12}{:>10}{:>10}  infile", "id", "hash", "contigs", "longest", "n50", "seconds");
+            for run in runs.iter() {
+                let m = &run["metrics"];
+                let hash = run["input_hash"].as_str().unwrap_or("");
+                println!("{:<24}{:<12}{:>10}{:>12}{:>10}{:>10.2}  {}",
+                    run["id"].as_str().unwrap_or("?"),
+                    hash.trim_start_matches("fnv1a64:").get(..8).unwrap_or(""),
+                    m["contigs"].as_u64().unwrap_or(0), m["longest"].as_u64().unwrap_or(0),
+                    m["n50"].as_u64().unwrap_or(0), m["seconds"].as_f64().unwrap_or(0.0),
+                    run["infile"].as_str().unwrap_or(""));
+            }
+            Ok(())
+        }
+        Some("show") => {
+            let id = args.get(1).ok_or("Usage: sbh runs show <id|last>")?;
+            let run = match id.as_str() {
+                "last" => runs.last(),
+                _ => runs.iter().find(|r| r["id"].as_str().is_some_and(|r| r.starts_with(id.as_str()))),
+            }.ok_or(format!("No run matching '{}'.", id))?;
+            println!("{}", serde_json::to_string_pretty(run).unwrap_or_default());
+            Ok(())
+        }
+        Some(other) => Err(format!("Unknown runs command: '{}'. Use 'list' or 'show <id|last>'.", other)),
+    }
+}
@@ -1,123 +1,1308 @@
-use crate::utils;
+use crate::{ claims::{ Claims, EdgeClaims, Proposal }, contig::{ BreakPoint, Contig, EndReason, ReadSpan, Source }, graph_file, kmer::KmerRepr, simd, string_graph::StringGraph, unitig, utils };
 use rayon::prelude::*;
+use rustc_hash::FxHasher;
+use smallvec::SmallVec;
 use std::{
-    collections::HashMap,
-    cell::RefCell,
-    rc::Rc,
-    sync::atomic::{ AtomicBool, Ordering }
+    collections::{ HashMap, HashSet, VecDeque },
+    hash::{ BuildHasher, DefaultHasher, Hasher, RandomState },
+    io,
+    ops::{ Index, IndexMut, Range },
+    path::Path,
+    str::FromStr,
+    sync::{ Arc, atomic::{ AtomicBool, AtomicUsize, Ordering } },
 };
 
-pub struct Assembler {
-    pub graph: HashMap<u32, HashMap<u32, Vec<Edge>>>,
-    pub nodes: HashMap<u32, Rc<RefCell<Node>>>,
-    pub paths: Vec<Vec<Rc<RefCell<Node>>>>,
-    pub cycles: Vec<Vec<Rc<RefCell<Node>>>>,
-    pub contigs: Vec<Vec<u8>>,
+// Where Assembler::new and Assembler::debruijn take their reads from. Anything that yields byte strings is one,
+// such as a Vec<Vec<u8>>, slices of a memory map, or an iterator reading from a database, socket or simulator
+pub trait ReadSource {
+    type Read: AsRef<[u8]>;
+
+    fn into_reads(self) -> impl Iterator<Item = Self::Read>;
+}
+
+impl<I> ReadSource for I where I: IntoIterator, I::Item: AsRef<[u8]> {
+    type Read = I::Item;
+
+    fn into_reads(self) -> impl Iterator<Item = Self::Read> {
+        self.into_iter()
+    }
 }
 
-impl Assembler {
-    // Build the graph
-    pub fn new(reads: Vec<Vec<u8>>) -> Self {
-        let mut nodes: HashMap<u32, Rc<RefCell<Node>>> = HashMap::new();
-        let mut graph: HashMap<u32, HashMap<u32, Vec<Edge>>> = HashMap::new();
-        for read in reads.iter() {
+// Most walks a round of det_find_paths_or_cycles proposes
+const DET_MAX_WINDOW: usize = 4096;
+
+// Longest detour find_path_or_cycle walks looking for a sub-cycle to splice in. Most detours dead-end, and on a
+// graph with many nodes left the first ones walk thousands of nodes before they do. A sub-cycle longer than this is
+// not lost, its edges are left for the cycle walks
+const DETOUR_MAX_NODES: usize = 2048;
+
+// Generic over how nodes are keyed: u64 holds nodes up to 31 bp, kmer::Kmer longer ones
+pub struct Assembler<K: KmerRepr = u64> {
+    pub graph: Graph,
+    pub nodes: Nodes<K>,
+    // Walks are the ids of the nodes they pass through
+    pub paths: Vec<Vec<NodeId>>,
+    pub cycles: Vec<Vec<NodeId>>,
+    // Cycle walks that consumed edges but dead-ended before getting back to their start node
+    pub abandoned: Vec<Vec<NodeId>>,
+    pub contigs: Vec<Contig>,
+    pub merge_stats: Arc<MergeStats>,
+    // Long running methods return early once this is set
+    pub cancelled: Arc<AtomicBool>,
+    // Run expensive invariant checks that panic with context on violation
+    pub paranoid: bool,
+    // Node length. Reads are k+1 to 2k long
+    pub k: usize,
+    // Paired-end mates whose ends are nodes of the graph, for scaffolding and repeat resolution
+    pub mates: Vec<MatePair<K>>,
+    // Set by make_canonical. Every edge has a twin on the other strand that is consumed with it
+    pub canonical: bool,
+    // The reads that have each link, keyed by the k-mers of its nodes so it outlives node ids, see add_read_ids
+    pub read_ids: HashMap<(K, K), Vec<u32>>,
+    // How walks pick among the successors they can take
+    pub traversal: TraversalStrategy,
+    // Steps a dead-ended walk may take back to try another successor, see walk. 0 commits to every pick
+    pub backtrack: usize,
+    // Walks tried from every start node, see best_attempt
+    pub attempts: usize,
+    // Seeds the random picks of attempts after the first
+    pub seed: u64,
+    // Walk from all start nodes at once, see par_find_paths_or_cycles
+    pub parallel: bool,
+    // Walk in parallel rounds that do not depend on timing, see det_find_paths_or_cycles
+    pub deterministic: bool,
+    // Seeded on the first random pick, so every walk of a run draws from the same sequence
+    rng: Option<utils::SplitMix64>,
+}
+
+impl<K: KmerRepr> Assembler<K> {
+    // Build the graph with nodes of length k. Reads are taken one at a time, so they can be streamed
+    pub fn new(reads: impl ReadSource, k: usize) -> Self {
+        Self::from_slices(reads.into_reads().map(|r| (r, 1)), k)
+    }
+
+    // Build a de Bruijn graph from reads of any length by splitting them into overlapping (k+1)-mers
+    pub fn debruijn(reads: impl ReadSource, k: usize) -> Self {
+        Self::from_weighted(utils::kmerize(reads.into_reads().map(|r| (r, 1)), k), k)
+    }
+
+    // Build the graph from reads that each stand for `weight` identical input reads
+    // Every read still adds a single edge, but node coverage counts the reads it stands for
+    pub fn from_weighted(reads: Vec<(Vec<u8>, usize)>, k: usize) -> Self {
+        Self::from_slices_par(Nodes::default(), reads.par_iter().map(|(r, w)| (r.as_slice(), *w)), k)
+    }
+
+    // Same as from_weighted for reads that are borrowed, such as slices of a memory mapped file, or streamed
+    pub fn from_slices<R: AsRef<[u8]>>(reads: impl IntoIterator<Item = (R, usize)>, k: usize) -> Self {
+        Self::from_slices_in(Nodes::default(), reads, k)
+    }
+
+    // Same as from_slices, adding the nodes to the given table, such as one from Nodes::dense
+    pub fn from_slices_in<R: AsRef<[u8]>>(mut nodes: Nodes<K>, reads: impl IntoIterator<Item = (R, usize)>, k: usize) -> Self {
+        let mut edges: Vec<Edge> = vec![];
+        // Copies of a read are folded into one edge whenever the edges have doubled since the last time
+        let mut limit = 1 << 16;
+        for (read, weight) in reads {
+            let read = read.as_ref();
             // Get indices from strings
-            let pidx = utils::vec2idx(read, NodeType::Prefix);
-            let sidx = utils::vec2idx(read, NodeType::Suffix);
+            let pidx: K = utils::vec2idx(read, NodeType::Prefix, k);
+            let sidx: K = utils::vec2idx(read, NodeType::Suffix, k);
             // Get nodes from prefixes or create them, setting odeg and ideg accordingly
-            let prefix = nodes.entry(pidx)
-                .and_modify(|n| { n.borrow_mut().odeg+=1; })
-                .or_insert_with(|| { Node::new(pidx, 0, 1) })
-                .clone();
-            let suffix = nodes.entry(sidx)
-                .and_modify(|n| { n.borrow_mut().ideg+=1; })
-                .or_insert_with(|| { Node::new(sidx, 1, 0) })
-                .clone();
+            let prefix = nodes.id_or_insert(&pidx);
+            nodes[prefix].odeg += 1;
+            nodes[prefix].cov += weight;
+            let suffix = nodes.id_or_insert(&sidx);
+            nodes[suffix].ideg += 1;
+            nodes[suffix].cov += weight;
             // Insert edge
-            graph.entry(pidx)
-                .or_insert_with(HashMap::new)
-                .entry(sidx)
-                .or_insert_with(Vec::new)
-                .push(Edge::new(prefix, suffix));
+            let mut edge = Edge::new(prefix, suffix, read.len());
+            edge.reads = weight;
+            edges.push(edge);
+            if edges.len() >= limit {
+                Graph::collapse(&mut edges);
+                limit = limit.max(2 * edges.len());
+            }
+        }
+        Self::from_graph(Graph::new(edges, nodes.len()), nodes, k)
+    }
+
+    // Same as from_slices_in with the work split across the rayon pool. Nodes are numbered in the order they first
+    // appear in the reads, so the graph is the same as the one from_slices_in builds. A single thread, a dense node
+    // table, which is there to avoid hashing, or a table that already has nodes go through from_slices_in. With the
+    // concurrent feature the workers share a DashMap, see concurrent::build
+    pub fn from_slices_par<R: AsRef<[u8]> + Send>(mut nodes: Nodes<K>, reads: impl IndexedParallelIterator<Item = (R, usize)>, k: usize) -> Self {
+        if nodes.is_dense() || !nodes.is_empty() || rayon::current_num_threads() == 1 {
+            let reads: Vec<(R, usize)> = reads.collect();
+            return Self::from_slices_in(nodes, reads, k);
         }
+        let reads: Vec<(K, K, usize, usize)> = reads.map(|(read, weight)| {
+            let read = read.as_ref();
+            (utils::vec2idx(read, NodeType::Prefix, k), utils::vec2idx(read, NodeType::Suffix, k), read.len(), weight)
+        }).collect();
+        #[cfg(feature = "concurrent")]
+        let edges = crate::concurrent::build(&mut nodes, &reads);
+        #[cfg(not(feature = "concurrent"))]
+        let edges = Self::sorted_build(&mut nodes, &reads);
+        Self::from_graph(Graph::new(edges, nodes.len()), nodes, k)
+    }
+
+    // Instead of looking every k-mer up in a map, the ends of all reads are sorted by k-mer, so equal k-mers end up
+    // next to each other and become one node. Reads are given as their prefix, suffix, length and weight. The nodes
+    // are numbered from 0, so the table must be empty
+    #[cfg(not(feature = "concurrent"))]
+    fn sorted_build(nodes: &mut Nodes<K>, reads: &[(K, K, usize, usize)]) -> Vec<Edge> {
+        assert!(nodes.is_empty(), "sorted_build numbers the nodes from 0");
+        // Every read end as its k-mer and position, twice the index of the read plus one for a suffix
+        let mut ends: Vec<(K, usize)> = reads.par_iter().enumerate()
+            .flat_map_iter(|(i, (p, s, ..))| [(p.clone(), 2 * i), (s.clone(), 2 * i + 1)])
+            .collect();
+        ends.par_sort_unstable();
+        let mut vertices: Vec<(usize, Node<K>)> = ends.par_chunk_by(|a, b| a.0 == b.0).map(|group| {
+            let suffixes = group.iter().filter(|(_, pos)| pos % 2 == 1).count();
+            let cov = group.iter().map(|(_, pos)| reads[pos / 2].3).sum();
+            (group[0].1, Node::new(group[0].0.clone(), suffixes, group.len() - suffixes, cov))
+        }).collect();
+        vertices.par_sort_unstable_by_key(|(first, _)| *first);
+        let firsts: Vec<usize> = vertices.iter().map(|(first, _)| *first).collect();
+        // The id of every read end, back in read order
+        let mut ids: Vec<(usize, NodeId)> = ends.par_chunk_by(|a, b| a.0 == b.0).flat_map_iter(|group| {
+            let id = NodeId(firsts.binary_search(&group[0].1).expect("first end of a node") as u32);
+            group.iter().map(move |&(_, pos)| (pos, id))
+        }).collect();
+        ids.par_sort_unstable_by_key(|&(pos, _)| pos);
+        for (_, node) in vertices {
+            nodes.insert(node);
+        }
+        reads.par_iter().zip(ids.par_chunks(2)).map(|((.., span, weight), ends)| {
+            let mut edge = Edge::new(ends[0].1, ends[1].1, *span);
+            edge.reads = *weight;
+            edge
+        }).collect()
+    }
+
+    // Wrap a graph that was built elsewhere, such as one loaded from GFA
+    pub fn from_graph(graph: Graph, nodes: Nodes<K>, k: usize) -> Self {
         Assembler {
             graph,
             nodes,
             paths: Vec::default(),
             cycles: Vec::default(),
+            abandoned: Vec::default(),
             contigs: Vec::default(),
+            merge_stats: Arc::new(MergeStats::new()),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            paranoid: false,
+            k,
+            mates: Vec::default(),
+            canonical: false,
+            read_ids: HashMap::default(),
+            traversal: TraversalStrategy::FirstAvailable,
+            backtrack: 0,
+            attempts: 1,
+            seed: 0,
+            parallel: false,
+            deterministic: false,
+            rng: None,
+        }
+    }
+
+    // Write the graph to a file that load_graph reads back, see graph_file. Paths and contigs are not kept
+    pub fn save_graph(&self, path: &Path) -> io::Result<()> {
+        graph_file::write(path, self)
+    }
+
+    // Load a graph written by save_graph, with its nodes hashed with Fx
+    pub fn load_graph(path: &Path) -> io::Result<Self> {
+        graph_file::read(path, Nodes::default())
+    }
+
+    // Merge the two strands into one graph for double-stranded data. A k-mer and its reverse complement are the two
+    // sides of one vertex, named by the canonical k-mer: every edge gets a twin from the reverse complement of its
+    // suffix to that of its prefix, both sides carry the coverage of the vertex, and a walk along one strand
+    // consumes the twins on the other, so each contig is spelled once in either orientation. Fails only if the storage
+    // of the graph cannot store it again
+    pub fn make_canonical(&mut self) -> io::Result<()> {
+        if self.canonical { return Ok(()); }
+        let k = self.k;
+        let mut twins: Vec<(K, K, usize, usize, u32, u64)> = vec![];
+        for edges in self.graph.links() {
+            let (p, s) = (&self.nodes[edges[0].prefix].idx, &self.nodes[edges[0].suffix].idx);
+            let (rp, rs) = (p.revcomp(k), s.revcomp(k));
+            // A palindromic read is its own twin
+            if rs == *p && rp == *s { continue; }
+            twins.extend(edges.iter().map(|e| (rs.clone(), rp.clone(), e.span, e.reads, e.count, e.colors)));
+        }
+        let mut edges = Vec::with_capacity(twins.len());
+        for (p, s, span, reads, count, colors) in twins {
+            let (prefix, suffix) = (self.nodes.id_or_insert(&p), self.nodes.id_or_insert(&s));
+            self.nodes[prefix].odeg += count as usize;
+            self.nodes[suffix].ideg += count as usize;
+            let mut edge = Edge::new(prefix, suffix, span);
+            edge.reads = reads;
+            edge.count = count;
+            edge.colors = colors;
+            edges.push(edge);
+        }
+        self.graph.extend(edges, self.nodes.len())?;
+        let covs: Vec<usize> = self.nodes.iter().map(|(_, node)| {
+            let rc = node.idx.revcomp(k);
+            if rc == node.idx { 0 } else { self.nodes.get(&rc).map(|n| n.cov).unwrap_or(0) }
+        }).collect();
+        for (node, cov) in self.nodes.iter_mut().zip(covs) { node.cov += cov; }
+        self.canonical = true;
+        Ok(())
+    }
+
+    // Whether the edges from p to s are the twins of a pair whose other half is the one reported, which is the one
+    // whose ids sort first. Always false unless the strands are joined
+    pub fn is_twin(&self, p: &K, s: &K) -> bool {
+        self.canonical && (s.revcomp(self.k).id(), p.revcomp(self.k).id()) < (p.id(), s.id())
+    }
+
+    // Consume an unused twin of an edge from p to s with the given span
+    fn mark_twin_used(&mut self, p: NodeId, s: NodeId, span: usize) {
+        let (rp, rs) = (self.nodes[p].idx.revcomp(self.k), self.nodes[s].idx.revcomp(self.k));
+        let (Some(rp), Some(rs)) = (self.nodes.id(&rp), self.nodes.id(&rs)) else { return };
+        if rs == p && rp == s { return; }
+        let twin = self.graph.link_mut(rs, rp).iter_mut().find(|e| e.unused() > 0 && e.span == span);
+        if let Some(twin) = twin { twin.mark_used(&mut self.nodes); }
+    }
+
+    // Tag the edges of a sample with its bit, given the reads of the sample split into edges as the graph was built
+    // from them. Windows that are not edges of the graph, such as those left out as rare, are skipped, and once the
+    // strands are joined the twin is tagged too. Returns the number of windows found in the graph
+    pub fn add_colors<R: AsRef<[u8]>>(&mut self, windows: impl IntoIterator<Item = R>, color: usize) -> usize {
+        let bit = 1u64 << color;
+        let mut found = 0;
+        for window in windows {
+            let window = window.as_ref();
+            let (Some(p), Some(s)) = (K::pack(&window[..self.k]), K::pack(&window[window.len() - self.k..])) else { continue };
+            let mut links = vec![(p.clone(), s.clone())];
+            if self.canonical { links.push((s.revcomp(self.k), p.revcomp(self.k))); }
+            let mut hit = false;
+            for (p, s) in links {
+                let (Some(p), Some(s)) = (self.nodes.id(&p), self.nodes.id(&s)) else { continue };
+                for edge in self.graph.link_mut(p, s).iter_mut().filter(|e| e.span == window.len()) {
+                    edge.colors |= bit;
+                    hit = true;
+                }
+            }
+            if hit { found += 1; }
+        }
+        found
+    }
+
+    // Record that the read with index `read` has the edge of each of its windows, matched to edges as add_colors does.
+    // Contigs spelled afterwards list where their reads are, see read_spans. Returns the number of windows found
+    pub fn add_read_ids<R: AsRef<[u8]>>(&mut self, windows: impl IntoIterator<Item = (u32, R)>) -> usize {
+        let mut found = 0;
+        for (read, window) in windows {
+            let window = window.as_ref();
+            let (Some(p), Some(s)) = (K::pack(&window[..self.k]), K::pack(&window[window.len() - self.k..])) else { continue };
+            let mut links = vec![(p.clone(), s.clone())];
+            if self.canonical { links.push((s.revcomp(self.k), p.revcomp(self.k))); }
+            let mut hit = false;
+            for (p, s) in links {
+                let (Some(pid), Some(sid)) = (self.nodes.id(&p), self.nodes.id(&s)) else { continue };
+                if !self.graph.link(pid, sid).iter().any(|e| e.span == window.len()) { continue; }
+                let ids = self.read_ids.entry((p, s)).or_default();
+                // A read that has a link more than once is listed once
+                if ids.last() != Some(&read) { ids.push(read); }
+                hit = true;
+            }
+            if hit { found += 1; }
+        }
+        found
+    }
+
+    // Where along a walk the reads recorded by add_read_ids have its links, one span for each stretch of links a
+    // read has
+    pub fn read_spans(&self, walk: &[NodeId]) -> Vec<ReadSpan> {
+        let mut spans = vec![];
+        let mut start = 0;
+        for pair in walk.windows(2) {
+            let span = self.span(pair[0], pair[1]);
+            let link = (self.nodes[pair[0]].idx.clone(), self.nodes[pair[1]].idx.clone());
+            for &read in self.read_ids.get(&link).into_iter().flatten() {
+                spans.push(ReadSpan { read, start, end: start + span });
+            }
+            start += span - self.k;
+        }
+        ReadSpan::join(spans)
+    }
+
+    // Record the pairing of paired-end mates, given as the sequences of each pair
+    // A pair is kept if the first and last k-mer of both mates are nodes of the graph. Returns the number kept
+    pub fn add_mates(&mut self, mates: &[(Vec<u8>, Vec<u8>)]) -> usize {
+        let ends = |read: &[u8]| -> Option<(K, K)> {
+            if read.len() < self.k { return None; }
+            let first = K::pack(&read[..self.k])?;
+            let last = K::pack(&read[read.len() - self.k..])?;
+            (self.nodes.contains_key(&first) && self.nodes.contains_key(&last)).then_some((first, last))
+        };
+        let before = self.mates.len();
+        for (first, second) in mates {
+            if let (Some(first), Some(second)) = (ends(first), ends(second)) {
+                self.mates.push(MatePair { first, second });
+            }
         }
+        self.mates.len() - before
+    }
+
+    // Add reads to a graph that was already built, such as one from load_graph, to top up its coverage. Degrees,
+    // coverage and edges are updated as if the reads had been there from the start, on both strands once they are
+    // joined. Walks through a node the reads touch give their edges back so they can be walked again with the new
+    // ones, and the contigs are cleared since paths_cycles_to_contigs spells them from all walks. Returns the number
+    // of walks that were dropped
+    pub fn add_reads<R: AsRef<[u8]>>(&mut self, reads: impl IntoIterator<Item = (R, usize)>) -> io::Result<usize> {
+        let k = self.k;
+        let mut edges: Vec<Edge> = vec![];
+        let mut touched: HashSet<NodeId> = HashSet::new();
+        for (read, weight) in reads {
+            let read = read.as_ref();
+            let pidx: K = utils::vec2idx(read, NodeType::Prefix, k);
+            let sidx: K = utils::vec2idx(read, NodeType::Suffix, k);
+            let mut ends = vec![(pidx.clone(), sidx.clone())];
+            // A palindromic read is its own twin
+            if self.canonical && (sidx.revcomp(k), pidx.revcomp(k)) != (pidx.clone(), sidx.clone()) {
+                ends.push((sidx.revcomp(k), pidx.revcomp(k)));
+            }
+            for (p, s) in ends {
+                let (prefix, suffix) = (self.nodes.id_or_insert(&p), self.nodes.id_or_insert(&s));
+                self.nodes[prefix].odeg += 1;
+                self.nodes[suffix].ideg += 1;
+                touched.extend([prefix, suffix]);
+                let mut edge = Edge::new(prefix, suffix, read.len());
+                edge.reads = weight;
+                edges.push(edge);
+            }
+            // Both sides of a joined vertex carry its coverage
+            for idx in [pidx, sidx] {
+                let id = self.nodes.id(&idx).expect("just inserted");
+                self.nodes[id].cov += weight;
+                let rc = idx.revcomp(k);
+                if self.canonical && rc != idx {
+                    let twin = self.nodes.id_or_insert(&rc);
+                    self.nodes[twin].cov += weight;
+                }
+            }
+        }
+        let dropped = self.drop_walks(|id| touched.contains(&id));
+        self.contigs.clear();
+        self.graph.extend(edges, self.nodes.len())?;
+        Ok(dropped)
+    }
+
+    // Remove the edges for which f returns true, such as those below a coverage, and take their unused copies off
+    // the degrees of their ends. Once the strands are joined f should treat an edge and its twin alike. Returns the
+    // number of edge copies removed
+    pub fn prune_edges(&mut self, mut f: impl FnMut(&Edge) -> bool) -> io::Result<usize> {
+        let (before, nodes) = (self.graph.count(), &mut self.nodes);
+        self.graph.retain(|e| {
+            if !f(e) { return true; }
+            nodes[e.prefix].odeg -= e.unused();
+            nodes[e.suffix].ideg -= e.unused();
+            false
+        })?;
+        Ok(before - self.graph.count())
+    }
+
+    // Remove the nodes for which f returns true with all their edges, fixing up the degrees of the nodes at the other
+    // ends. The remaining nodes get new ids, so walks are renumbered. Walks through a removed node give their edges
+    // back as with add_reads and are dropped. Returns the number of nodes removed
+    pub fn prune_nodes(&mut self, mut f: impl FnMut(&Node<K>) -> bool) -> io::Result<usize> {
+        let pruned: Vec<bool> = self.nodes.iter().map(|(_, node)| f(node)).collect();
+        self.drop_walks(|id| pruned[id.index()]);
+        self.prune_edges(|e| pruned[e.prefix.index()] || pruned[e.suffix.index()])?;
+        let ids = self.nodes.retain(|id, _| !pruned[id.index()]);
+        self.graph.renumber(&ids)?;
+        for walk in self.paths.iter_mut().chain(self.cycles.iter_mut()).chain(self.abandoned.iter_mut()) {
+            for id in walk.iter_mut() { *id = ids[id.index()].expect("walk through a kept node"); }
+        }
+        Ok(pruned.iter().filter(|&&p| p).count())
+    }
+
+    // Drop the walks through a node for which f returns true, giving their edges back. Returns how many were dropped
+    fn drop_walks(&mut self, f: impl Fn(NodeId) -> bool) -> usize {
+        let mut affected = vec![];
+        for walks in [&mut self.paths, &mut self.cycles, &mut self.abandoned] {
+            let (kept, dropped): (Vec<_>, Vec<_>) = std::mem::take(walks).into_iter().partition(|walk| !walk.iter().any(|&id| f(id)));
+            *walks = kept;
+            affected.extend(dropped);
+        }
+        for walk in &affected {
+            for pair in walk.windows(2) {
+                self.release(pair[0], pair[1]);
+            }
+        }
+        affected.len()
+    }
+
+    // Undo the step of a walk from p to s, and its twin once the strands are joined
+    fn release(&mut self, p: NodeId, s: NodeId) {
+        let Some(edge) = self.graph.link_mut(p, s).iter_mut().find(|e| e.used > 0) else { return };
+        edge.used -= 1;
+        let span = edge.span;
+        self.nodes[p].odeg += 1;
+        self.nodes[s].ideg += 1;
+        if !self.canonical { return; }
+        let (rp, rs) = (self.nodes[p].idx.revcomp(self.k), self.nodes[s].idx.revcomp(self.k));
+        let (Some(rp), Some(rs)) = (self.nodes.id(&rp), self.nodes.id(&rs)) else { return };
+        if rs == p && rp == s { return; }
+        if let Some(twin) = self.graph.link_mut(rs, rp).iter_mut().find(|e| e.used > 0 && e.span == span) {
+            twin.used -= 1;
+            self.nodes[rs].odeg += 1;
+            self.nodes[rp].ideg += 1;
+        }
+    }
+
+    // Mate pairs with either mate starting or ending at a node
+    pub fn mates_at(&self, idx: K) -> impl Iterator<Item = &MatePair<K>> {
+        self.mates.iter().filter(move |m| [&m.first.0, &m.first.1, &m.second.0, &m.second.1].contains(&&idx))
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Summarize the shape of the graph. Degrees are only meaningful before traversal
+    // Once the strands are joined, nodes, links, edges and components count a vertex or an edge together with its
+    // twin, while the degree counts stay per side
+    pub fn topology(&self) -> Topology {
+        let mut t = Topology { nodes: self.nodes.len(), ..Topology::default() };
+        for edges in self.graph.links() {
+            let (p, s) = (edges[0].prefix, edges[0].suffix);
+            if !self.is_twin(&self.nodes[p].idx, &self.nodes[s].idx) {
+                t.links += 1;
+                t.edges += edges.iter().map(|e| e.count as usize).sum::<usize>();
+            }
+        }
+        if self.canonical {
+            t.nodes = self.nodes.iter().filter(|(_, node)| node.idx == node.idx.canonical(self.k)).count();
+        }
+        for (id, node) in self.nodes.iter() {
+            let successors = self.graph.successors(id).count();
+            if node.ideg == 0 { t.sources += 1; }
+            if node.odeg == 0 { t.sinks += 1; }
+            if node.ideg == node.odeg { t.balanced += 1; }
+            if successors > 1 { t.branching += 1; }
+            t.max_degree = t.max_degree.max(node.ideg + node.odeg);
+        }
+        t.components = self.components().nodes.len();
+        t
+    }
+
+    // Label the weakly connected components of the graph, largest first. Once the strands are joined a node and its
+    // reverse complement are in the same component, as a walk on one strand uses the edges of the other
+    pub fn components(&self) -> Components {
+        // Union-find over node indices
+        let mut parent: Vec<NodeId> = self.nodes.iter().map(|(id, _)| id).collect();
+        fn find(parent: &mut [NodeId], mut i: NodeId) -> NodeId {
+            while parent[i.index()] != i {
+                let grand = parent[parent[i.index()].index()];
+                parent[i.index()] = grand;
+                i = grand;
+            }
+            i
+        }
+        let union = |parent: &mut [NodeId], p: NodeId, s: NodeId| {
+            let (a, b) = (find(parent, p), find(parent, s));
+            if a != b { parent[a.index()] = b; }
+        };
+        for edges in self.graph.links() {
+            union(&mut parent, edges[0].prefix, edges[0].suffix);
+        }
+        if self.canonical {
+            for (id, node) in self.nodes.iter() {
+                let Some(rc) = self.nodes.id(&node.idx.revcomp(self.k)) else { continue };
+                union(&mut parent, id, rc);
+            }
+        }
+        let mut by_root: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        for (id, _) in self.nodes.iter() {
+            by_root.entry(find(&mut parent, id)).or_default().push(id);
+        }
+        let mut nodes: Vec<Vec<NodeId>> = by_root.into_values().collect();
+        // Ties are broken by the first node so the labels do not depend on the order of the map
+        nodes.sort_unstable_by_key(|members| (std::cmp::Reverse(members.len()), members[0]));
+        let mut of = vec![0; self.nodes.len()];
+        for (c, members) in nodes.iter().enumerate() {
+            for id in members { of[id.index()] = c; }
+        }
+        Components { of, nodes }
+    }
+
+    // Check the degree conditions for walking the edges in one go, per weakly connected component and for the graph
+    // as a whole. Like the degrees, this is only meaningful before traversal, and once the strands are joined the
+    // balance is per side
+    pub fn eulerian(&self) -> Eulerian {
+        let components: Vec<Balance> = self.components().nodes.iter().map(|ids| {
+            let mut b = Balance { nodes: ids.len(), ..Balance::default() };
+            for &id in ids {
+                let node = &self.nodes[id];
+                b.edges += node.odeg;
+                if node.odeg != node.ideg { b.unbalanced += 1; }
+                b.starts += node.odeg.saturating_sub(node.ideg);
+            }
+            b
+        }).collect();
+        // A walk cannot cross between components, so the graph only has one if a single component has edges
+        let mut with_edges = components.iter().filter(|b| b.edges > 0);
+        let walk = match (with_edges.next(), with_edges.next()) {
+            (Some(b), None) => b.walk(),
+            _ => EulerianWalk::Neither,
+        };
+        Eulerian { components, walk }
+    }
+
+    // The most common node coverage, taken as the coverage of single copy sequence
+    // Nodes seen in a single read are mostly errors, so they are ignored unless there is nothing else
+    pub fn modal_coverage(&self) -> f32 {
+        let mut histogram: HashMap<usize, usize> = HashMap::new();
+        for (_, node) in self.nodes.iter() {
+            *histogram.entry(node.cov).or_default() += 1;
+        }
+        let mode = |min: usize| histogram.iter()
+            .filter(|(&cov, _)| cov >= min)
+            .max_by_key(|(&cov, &n)| (n, std::cmp::Reverse(cov)))
+            .map(|(&cov, _)| cov);
+        mode(2).or(mode(0)).unwrap_or(0) as f32
     }
 
     // Find all paths or cycles depending on the type requested
     pub fn populate_paths_or_cycles(&mut self, typ: PathType) {
+        let ids: Vec<NodeId> = self.nodes.iter().map(|(id, _)| id).collect();
+        self.populate_paths_or_cycles_in(typ, &ids);
+    }
+
+    // Same as populate_paths_or_cycles, with walks only starting at the given nodes. Walks never leave the component
+    // they start in, so the nodes of a component find the same walks there as the whole graph does
+    pub fn populate_paths_or_cycles_in(&mut self, typ: PathType, ids: &[NodeId]) {
         // Get all valid start nodes depending on the type requested
-        let starts: Vec<Rc<RefCell<Node>>> = self.nodes.values().filter_map(|n| {
-            let node = n.borrow();
+        let starts: Vec<NodeId> = ids.iter().filter_map(|&id| {
+            let node = &self.nodes[id];
             match typ {
                 // Paths only start where outdegree > indegree
-                PathType::Path if node.odeg > node.ideg => Some(n.clone()),
+                PathType::Path if node.odeg > node.ideg => Some(id),
                 // Cycles only start where outdegree and indegree is positive
-                PathType::Cycle if node.odeg > 0 && node.ideg > 0 => Some(n.clone()),
+                PathType::Cycle if node.odeg > 0 && node.ideg > 0 => Some(id),
                 _ => None,
             }
         }).collect();
         // Get all paths or cycles and populate their respective vector
-        for start in starts {
-            let p = self.find_path_or_cycle(start.clone(), typ);
+        let walks = if self.parallel && self.deterministic {
+            self.det_find_paths_or_cycles(typ, &starts)
+        } else if self.parallel {
+            self.par_find_paths_or_cycles(typ, &starts)
+        } else {
+            let mut walks = Vec::with_capacity(starts.len());
+            for start in starts {
+                if self.is_cancelled() { break; }
+                walks.push(self.best_attempt(start, typ));
+            }
+            walks
+        };
+        for p in walks {
             match typ {
                 PathType::Path => if p.len() >= 5 { self.paths.push(p); },
-                PathType::Cycle => if p.len() >= 3 { self.cycles.push(p); },
+                // A cycle only counts if its last edge returns to the start node
+                PathType::Cycle if is_closed(&p) => if p.len() >= 3 { self.cycles.push(p); },
+                PathType::Cycle => if p.len() >= 2 { self.abandoned.push(p); },
             }
         }
+        if self.paranoid { self.check_degrees(); }
     }
 
-    // Find the path or cycle that starts at the start node if it exists
-    fn find_path_or_cycle(&mut self, start: Rc<RefCell<Node>>, typ: PathType) -> Vec<Rc<RefCell<Node>>> {
-        let mut path = vec![start.clone()];
-        let mut current = start.clone();
-        loop {
-            // Get suffix graph associated with the start node
-            let sufs = match self.graph.get_mut(&current.borrow().idx) {
-                Some(m) => m,
-                None => break,
+    // With attempts above 1, walk from the start node that many times, the first with the traversal strategy and the
+    // others picking successors at random, giving back the edges of every attempt, then take the best again. A cycle
+    // that closes beats one that does not, and otherwise the longer walk wins, the earlier attempt on a tie
+    fn best_attempt(&mut self, start: NodeId, typ: PathType) -> Vec<NodeId> {
+        if self.attempts <= 1 { return self.find_path_or_cycle(start, typ); }
+        let strategy = self.traversal;
+        let mut best: Vec<NodeId> = vec![];
+        for attempt in 0..self.attempts {
+            if attempt == 1 && !matches!(strategy, TraversalStrategy::Random(_)) {
+                self.traversal = TraversalStrategy::Random(self.seed);
+            }
+            let walk = self.find_path_or_cycle(start, typ);
+            self.unwalk(&walk);
+            if (is_closed(&walk), walk.len()) > (is_closed(&best), best.len()) { best = walk; }
+        }
+        self.traversal = strategy;
+        self.retrace(&best);
+        best
+    }
+
+    // Find the path or cycle that starts at the start node if it exists. Once the walk is stuck, Hierholzer's
+    // splicing goes back over it and inserts every detour that returns to the node it leaves, so the walk takes
+    // every unused edge it can reach and come back from instead of leaving the sub-cycles along it for later walks
+    fn find_path_or_cycle(&mut self, start: NodeId, typ: PathType) -> Vec<NodeId> {
+        // The only difference between a path and a cycle is a cycle stops when we get back to the start node
+        let path = self.walk(start, matches!(typ, PathType::Cycle).then_some(start), usize::MAX);
+        Self::splice(path, |node| {
+            let detour = self.walk(node, Some(node), DETOUR_MAX_NODES);
+            if is_closed(&detour) { return Some(detour); }
+            self.unwalk(&detour);
+            None
+        })
+    }
+
+    // Walk from every start node at once on the rayon pool. A walk claims the link it takes next, and its twin once
+    // the strands are joined, in a bitmap all walks share, so no two walks take the same link. The edges of the
+    // walks are then marked used as a serial run would have. Walks race for the links, so which walk gets a contested
+    // link can change from run to run. Backtracking and attempts are serial only
+    fn par_find_paths_or_cycles(&mut self, typ: PathType, starts: &[NodeId]) -> Vec<Vec<NodeId>> {
+        let claims = EdgeClaims::new(self.graph.len());
+        let this = &*self;
+        let walks: Vec<Vec<NodeId>> = starts.par_iter().map(|&start| {
+            if this.is_cancelled() { return vec![]; }
+            this.claim_path_or_cycle(start, typ, &claims)
+        }).collect();
+        for walk in &walks { self.retrace(walk); }
+        walks
+    }
+
+    // Like par_find_paths_or_cycles, in rounds whose outcome does not depend on timing or the number of threads. In a
+    // round the earliest pending walks, up to a window of them, propose a walk each over the links committed in
+    // earlier rounds. The proposals are then gone through in start node order, and each is committed unless a link of
+    // it was committed before it, so the earliest always is. The others stay pending at the front. On a contested
+    // graph most proposals lose and are walked again, so the window halves after a round that commits under a quarter
+    // of its walks, down to one walk at a time, and doubles after one that commits over three quarters, starting from one
+    fn det_find_paths_or_cycles(&mut self, typ: PathType, starts: &[NodeId]) -> Vec<Vec<NodeId>> {
+        let committed = EdgeClaims::new(self.graph.len());
+        let mut walks: Vec<Option<Vec<NodeId>>> = vec![None; starts.len()];
+        let mut pending: VecDeque<usize> = (0..starts.len()).collect();
+        let mut window = 1;
+        let this = &*self;
+        while !pending.is_empty() && !this.is_cancelled() {
+            let round: Vec<usize> = pending.drain(..window.min(pending.len())).collect();
+            let proposals: Vec<(Vec<NodeId>, Vec<usize>)> = round.par_iter().map(|&rank| {
+                let proposal = Proposal::new(&committed);
+                let walk = this.claim_path_or_cycle(starts[rank], typ, &proposal);
+                (walk, proposal.into_links())
+            }).collect();
+            let mut lost = Vec::new();
+            for ((walk, links), &rank) in proposals.into_iter().zip(&round) {
+                if links.iter().all(|&i| !committed.is_claimed(i)) {
+                    for &i in &links { committed.claim(i); }
+                    walks[rank] = Some(walk);
+                } else {
+                    lost.push(rank);
+                }
+            }
+            let won = round.len() - lost.len();
+            if 4 * won < round.len() {
+                window = (window / 2).max(1);
+            } else if 4 * won > 3 * round.len() {
+                window = (window * 2).min(DET_MAX_WINDOW);
+            }
+            for rank in lost.into_iter().rev() { pending.push_front(rank); }
+        }
+        let walks: Vec<Vec<NodeId>> = walks.into_iter().flatten().collect();
+        for walk in &walks { self.retrace(walk); }
+        walks
+    }
+
+    // Find the path or cycle from a start node like find_path_or_cycle, taking the links it claims
+    fn claim_path_or_cycle(&self, start: NodeId, typ: PathType, claims: &impl Claims) -> Vec<NodeId> {
+        // Every walk draws from its own generator, so random picks do not depend on the order walks run in
+        let mut rng = match self.traversal {
+            TraversalStrategy::Random(seed) => Some(utils::SplitMix64::new(seed ^ start.index() as u64)),
+            _ => None,
+        };
+        let path = self.claim_walk(start, matches!(typ, PathType::Cycle).then_some(start), usize::MAX, claims, &mut rng);
+        Self::splice(path, |node| {
+            let detour = self.claim_walk(node, Some(node), DETOUR_MAX_NODES, claims, &mut rng);
+            if is_closed(&detour) { return Some(detour); }
+            self.unclaim(&detour, claims);
+            None
+        })
+    }
+
+    // Like greedy_walk, taking the links it claims instead of marking them used
+    fn claim_walk(&self, start: NodeId, stop: Option<NodeId>, max_len: usize, claims: &impl Claims,
+        rng: &mut Option<utils::SplitMix64>) -> Vec<NodeId> {
+        let is_open = |edges: &[Edge]| {
+            edges.iter().all(|e| e.used == 0) && !claims.is_claimed(self.graph.link_start(edges[0].prefix, edges[0].suffix))
+        };
+        let mut path = vec![start];
+        let mut current = start;
+        while path.len() < max_len {
+            let prev = path.len().checked_sub(2).map(|i| path[i]);
+            let mut open: SmallVec<[NodeId; 4]> = self.graph.successors(current)
+                .filter(|edges| is_open(edges))
+                .map(|edges| edges[0].suffix)
+                .collect();
+            // Another walk may claim the pick first, then the next best is tried
+            let next = loop {
+                let Some(next) = self.pick(current, prev, &open, &is_open, rng) else { break None };
+                if self.claim(current, next, claims) { break Some(next); }
+                open.retain(|s| *s != next);
+            };
+            let Some(next) = next else { break };
+            path.push(next);
+            if Some(next) == stop { break; }
+            current = next;
+        }
+        path
+    }
+
+    // The link from p to s, and its twin once the strands are joined, unless another walk claimed either first
+    fn claim(&self, p: NodeId, s: NodeId, claims: &impl Claims) -> bool {
+        let link = self.graph.link_start(p, s);
+        if !claims.claim(link) { return false; }
+        let Some(twin) = self.twin_link(p, s) else { return true };
+        if claims.claim(twin) { return true; }
+        claims.release(link);
+        false
+    }
+
+    // Give back the links of a walk, and their twins, to the other walks
+    fn unclaim(&self, walk: &[NodeId], claims: &impl Claims) {
+        for pair in walk.windows(2) {
+            claims.release(self.graph.link_start(pair[0], pair[1]));
+            if let Some(twin) = self.twin_link(pair[0], pair[1]) { claims.release(twin); }
+        }
+    }
+
+    // Where the twin of the link from p to s starts in Graph storage once the strands are joined, unless the link is
+    // its own twin
+    fn twin_link(&self, p: NodeId, s: NodeId) -> Option<usize> {
+        if !self.canonical { return None; }
+        let twin = |id: NodeId| self.nodes.id(&self.nodes[id].idx.revcomp(self.k));
+        let (rs, rp) = (twin(s)?, twin(p)?);
+        ((rs, rp) != (p, s) && !self.graph.link(rs, rp).is_empty()).then(|| self.graph.link_start(rs, rp))
+    }
+
+    // Hierholzer's splicing of a walk that is stuck. `detour` walks from a node of the walk and returns the walk if it
+    // closed, or gives back its edges and returns None
+    fn splice(path: Vec<NodeId>, mut detour: impl FnMut(NodeId) -> Option<Vec<NodeId>>) -> Vec<NodeId> {
+        let mut spliced = Vec::with_capacity(path.len());
+        // The walks still being read, innermost last. A detour ends where it left, so its last node is tried again
+        let mut pending = vec![path.into_iter()];
+        while let Some(walk) = pending.last_mut() {
+            let Some(node) = walk.next() else {
+                pending.pop();
+                continue;
             };
-            // Get the index of the next suffix
-            let next = sufs.keys().find(|idx| {
-                match sufs.get(idx) {
-                    Some(idx) => idx,
-                    None => return false,
-                }.iter().all(|e| !e.used)
-            });
-            match next {
-                Some(&idx) => {
-                    let edge = sufs.get_mut(&idx).unwrap().iter_mut().find(|e| !e.used).unwrap();
-                    edge.mark_used();
-                    let node = self.nodes.get(&idx).unwrap().clone();
-                    path.push(node.clone());
-                    // The only difference between a path and a cycle is a cycle stops when we get back to the start node
-                    if matches!(typ, PathType::Cycle) && node == start { break; }
-                    current = node;
-                } None => break,
+            spliced.push(node);
+            if let Some(detour) = detour(node) {
+                pending.push(detour.into_iter().skip(1).collect::<Vec<_>>().into_iter());
             }
         }
+        spliced
+    }
+
+    // Follow the successors none of whose edges were used yet, as the traversal strategy picks them, marking the
+    // edges used, until there is none or the walk gets to `stop`. With backtrack set, a walk that dead-ends short of
+    // `stop` takes back up to that many of its last steps to try the successors it passed over. The walk ends after
+    // max_len nodes either way
+    fn walk(&mut self, start: NodeId, stop: Option<NodeId>, max_len: usize) -> Vec<NodeId> {
+        let mut path = self.greedy_walk(start, stop, max_len);
+        while self.backtrack > 0 && path.last() != stop.as_ref() && path.len() < max_len && self.try_backtrack(&mut path, stop, max_len) {}
         path
     }
 
+    fn greedy_walk(&mut self, start: NodeId, stop: Option<NodeId>, max_len: usize) -> Vec<NodeId> {
+        let mut path = vec![start];
+        let mut current = start;
+        while path.len() < max_len {
+            let Some(next) = self.next_successor(current, path.len().checked_sub(2).map(|i| path[i])) else { break };
+            self.step(current, next);
+            path.push(next);
+            if Some(next) == stop { break; }
+            current = next;
+        }
+        path
+    }
+
+    // Take back the last steps of a dead-ended walk, one more at a time up to backtrack, and walk on greedily along
+    // every other successor of the node they start at. The first branch that gets to `stop` or takes more edges
+    // than the steps it replaces is kept, otherwise the steps are taken again. Returns whether a branch was kept
+    fn try_backtrack(&mut self, path: &mut Vec<NodeId>, stop: Option<NodeId>, max_len: usize) -> bool {
+        for back in 1..=self.backtrack.min(path.len() - 1) {
+            // The tail starts at the node to branch from
+            let tail = path.split_off(path.len() - 1 - back);
+            self.unwalk(&tail);
+            let (from, taken) = (tail[0], tail[1]);
+            let others: SmallVec<[NodeId; 4]> = self.graph.successors(from)
+                .filter(|edges| edges[0].suffix != taken && edges.iter().all(|e| e.used == 0))
+                .map(|edges| edges[0].suffix)
+                .collect();
+            for next in others {
+                self.step(from, next);
+                let mut branch = vec![from];
+                if Some(next) == stop {
+                    branch.push(next);
+                } else {
+                    branch.extend(self.greedy_walk(next, stop, max_len.saturating_sub(path.len() + 1)));
+                }
+                if branch.last() == stop.as_ref() || branch.len() > tail.len() {
+                    path.extend(branch);
+                    return true;
+                }
+                self.unwalk(&branch);
+            }
+            self.retrace(&tail);
+            path.extend(tail);
+        }
+        false
+    }
+
+    // Take one copy of the first edge from p to s, and its twin once the strands are joined
+    fn step(&mut self, p: NodeId, s: NodeId) {
+        let edge = &mut self.graph.link_mut(p, s)[0];
+        edge.mark_used(&mut self.nodes);
+        let span = edge.span;
+        if self.paranoid {
+            // The prefix's outdegree must match its unused outgoing edges right after every step
+            let unused: usize = self.graph.edges(p).iter().map(|e| e.unused()).sum();
+            let node = &self.nodes[p];
+            assert_eq!(node.odeg, unused, "paranoid: node {:?} has odeg {} but {} unused outgoing edges after mark_used",
+                node.idx, node.odeg, unused);
+        }
+        if self.canonical { self.mark_twin_used(p, s, span); }
+    }
+
+    // Take the steps of a walk given back by unwalk again
+    fn retrace(&mut self, walk: &[NodeId]) {
+        for pair in walk.windows(2) { self.step(pair[0], pair[1]); }
+    }
+
+    // The successor of p a walk that came from prev takes next, among those none of whose edges were used yet
+    fn next_successor(&mut self, p: NodeId, prev: Option<NodeId>) -> Option<NodeId> {
+        let is_open = |edges: &[Edge]| edges.iter().all(|e| e.used == 0);
+        let open: SmallVec<[NodeId; 4]> = self.graph.successors(p)
+            .filter(|edges| is_open(edges))
+            .map(|edges| edges[0].suffix)
+            .collect();
+        let mut rng = self.rng.take();
+        let next = self.pick(p, prev, &open, &is_open, &mut rng);
+        self.rng = rng;
+        next
+    }
+
+    // The successor the traversal strategy picks among the open ones, those whose link is_open. Ties go to the first
+    // successor, so every strategy but random is as deterministic as the graph. A random pick seeds the generator
+    // if it is not yet
+    fn pick(&self, p: NodeId, prev: Option<NodeId>, open: &[NodeId], is_open: &impl Fn(&[Edge]) -> bool,
+        rng: &mut Option<utils::SplitMix64>) -> Option<NodeId> {
+        match self.traversal {
+            _ if open.len() < 2 => open.first().copied(),
+            TraversalStrategy::FirstAvailable => open.first().copied(),
+            TraversalStrategy::HighestCoverage => open.iter().copied()
+                .min_by_key(|&s| std::cmp::Reverse(self.graph.link(p, s).iter().map(|e| e.reads).sum::<usize>())),
+            // A repeat collapses the copies into nodes of several times their coverage, so the coverage before the
+            // junction tells which copy the walk is on. A walk that starts at the junction has nothing before it
+            TraversalStrategy::CoverageMatch => {
+                let incoming = self.nodes[prev.unwrap_or(p)].cov;
+                open.iter().copied().min_by_key(|&s| self.nodes[s].cov.abs_diff(incoming))
+            }
+            TraversalStrategy::LongestExtensionLookahead(depth) => open.iter().copied()
+                .min_by_key(|&s| std::cmp::Reverse(self.extension(s, depth, &mut vec![p], is_open))),
+            TraversalStrategy::Random(seed) => {
+                let rng = rng.get_or_insert_with(|| utils::SplitMix64::new(seed));
+                Some(open[(rng.next_u64() % open.len() as u64) as usize])
+            }
+        }
+    }
+
+    // Steps of the longest walk from a node along links that are open, up to depth, that does not come back to a node
+    // it or `seen` passed. Nothing is marked used
+    fn extension(&self, from: NodeId, depth: usize, seen: &mut Vec<NodeId>, is_open: &impl Fn(&[Edge]) -> bool) -> usize {
+        if depth == 0 { return 0; }
+        seen.push(from);
+        let mut best = 0;
+        for edges in self.graph.successors(from) {
+            let s = edges[0].suffix;
+            if !is_open(edges) || seen.contains(&s) { continue; }
+            best = best.max(1 + self.extension(s, depth - 1, seen, is_open));
+            if best == depth { break; }
+        }
+        seen.pop();
+        best
+    }
+
+    // Give back the edges of a walk, and their twins once the strands are joined. A walk only takes links none of
+    // whose edges were used, and their twins with them, so every edge of a link it took is unused again
+    fn unwalk(&mut self, walk: &[NodeId]) {
+        for pair in walk.windows(2) {
+            let (p, s) = (pair[0], pair[1]);
+            let mut links = vec![(p, s)];
+            if self.canonical {
+                let twin = |id: NodeId| self.nodes.id(&self.nodes[id].idx.revcomp(self.k));
+                if let (Some(rs), Some(rp)) = (twin(s), twin(p)) { if (rs, rp) != (p, s) { links.push((rs, rp)); } }
+            }
+            for (p, s) in links {
+                for edge in self.graph.link_mut(p, s) { edge.unmark_used(&mut self.nodes); }
+            }
+        }
+    }
+
+    // Remove the links between two nodes that fewer than min_reads input reads support, counting all their edges, and
+    // take them off the degrees. Both strands of a link have the same reads, so twins go together. Returns the number
+    // of links removed
+    pub fn remove_weak_links(&mut self, min_reads: usize) -> io::Result<usize> {
+        let weak: HashSet<(NodeId, NodeId)> = self.graph.links()
+            .filter(|edges| edges.iter().map(|e| e.reads).sum::<usize>() < min_reads)
+            .map(|edges| (edges[0].prefix, edges[0].suffix))
+            .collect();
+        if !weak.is_empty() { self.prune_edges(|e| weak.contains(&(e.prefix, e.suffix)))?; }
+        Ok(weak.len())
+    }
+
+    // Remove tips: unitigs of at most max_len bp with a mean coverage of at most max_cov that nothing leads into or
+    // that lead nowhere, hanging off nodes with another way in or out. Sequencing errors near the ends of reads make
+    // most of them. The chain a tip hung off can become a tip once it is gone, so this repeats until no tip is left
+    // Returns the number of nodes removed
+    pub fn clip_tips(&mut self, max_len: usize, max_cov: f32) -> io::Result<usize> {
+        let mut removed = 0;
+        loop {
+            let unitigs = unitig::compact(self);
+            let (mut preds, mut succs) = (vec![vec![]; unitigs.unitigs.len()], vec![vec![]; unitigs.unitigs.len()]);
+            for link in unitigs.links.iter().filter(|link| link.from != link.to) {
+                succs[link.from].push(link.to);
+                preds[link.to].push(link.from);
+            }
+            let mut tips: HashSet<K> = HashSet::new();
+            for (u, unitig) in unitigs.unitigs.iter().enumerate() {
+                if unitig.seq.len() > max_len || unitig.cov > max_cov { continue; }
+                let hangs = match (preds[u].is_empty(), succs[u].is_empty()) {
+                    (true, false) => succs[u].iter().all(|&v| preds[v].len() > 1),
+                    (false, true) => preds[u].iter().all(|&v| succs[v].len() > 1),
+                    _ => false,
+                };
+                if hangs { tips.extend(unitig.nodes.iter().map(|&id| self.nodes[id].idx.clone())); }
+            }
+            if tips.is_empty() { return Ok(removed); }
+            removed += self.prune_nodes(|node| tips.contains(&node.idx))?;
+        }
+    }
+
+    // Pop simple bubbles: unitigs of at most max_len bp that run from the same unitig to the same other unitig, as a
+    // SNP or a sequencing error makes them. The branch with the highest mean coverage is kept and the others removed
+    // Popping can leave a bubble around a bubble, so this repeats until none is left. Returns what was popped, once
+    // for a bubble and its twin on the other strand
+    pub fn pop_bubbles(&mut self, max_len: usize) -> io::Result<Vec<Bubble>> {
+        let mut popped = vec![];
+        loop {
+            let unitigs = unitig::compact(self);
+            let (mut preds, mut succs) = (vec![vec![]; unitigs.unitigs.len()], vec![vec![]; unitigs.unitigs.len()]);
+            for link in unitigs.links.iter().filter(|link| link.from != link.to) {
+                succs[link.from].push(link.to);
+                preds[link.to].push(link.from);
+            }
+            preds.iter_mut().chain(succs.iter_mut()).for_each(|ends| ends.dedup());
+            let mut branches: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+            for (u, unitig) in unitigs.unitigs.iter().enumerate() {
+                if let ([pred], [succ]) = (preds[u].as_slice(), succs[u].as_slice()) {
+                    if pred != succ && unitig.seq.len() <= max_len { branches.entry((*pred, *succ)).or_default().push(u); }
+                }
+            }
+            let mut nodes: HashSet<K> = HashSet::new();
+            for mut branches in branches.into_values().filter(|b| b.len() > 1) {
+                // Ties go to the same branch on both strands
+                let strandless = |u: usize| {
+                    let seq = &unitigs.unitigs[u].seq;
+                    seq.clone().min(simd::revcomp(seq))
+                };
+                branches.sort_by(|&a, &b| {
+                    let (a_cov, b_cov) = (unitigs.unitigs[a].cov, unitigs.unitigs[b].cov);
+                    b_cov.total_cmp(&a_cov).then_with(|| strandless(a).cmp(&strandless(b)))
+                });
+                let kept = &unitigs.unitigs[branches[0]];
+                for &u in &branches[1..] {
+                    let branch = &unitigs.unitigs[u];
+                    nodes.extend(branch.nodes.iter().map(|&id| self.nodes[id].idx.clone()));
+                    if unitigs.twin[branches[0]].is_none() {
+                        popped.push(Bubble { kept: kept.seq.clone(), popped: branch.seq.clone(), kept_cov: kept.cov, popped_cov: branch.cov });
+                    }
+                }
+            }
+            if nodes.is_empty() { return Ok(popped); }
+            self.prune_nodes(|node| nodes.contains(&node.idx))?;
+        }
+    }
+
+    // Experimental iterative cleaning. Each iteration does a trial traversal and re-estimates which
+    // edges are supported by a kept path or cycle. Unsupported edges have their weight decayed and are
+    // removed once it falls below min_weight. Stops early once every remaining edge is supported
+    // Returns the number of edge copies that were removed
+    pub fn iterative_clean(&mut self, iterations: usize, decay: f32, min_weight: f32) -> io::Result<usize> {
+        let mut removed = 0;
+        for _ in 0..iterations {
+            if self.is_cancelled() { break; }
+            self.populate_paths_or_cycles(PathType::Path);
+            self.populate_paths_or_cycles(PathType::Cycle);
+            // Count how many times each prefix/suffix pair was walked by a kept path or cycle
+            let mut support: HashMap<(NodeId, NodeId), usize> = HashMap::new();
+            for walk in self.paths.iter().chain(self.cycles.iter()).chain(self.abandoned.iter()) {
+                for pair in walk.windows(2) {
+                    let (p, s) = (pair[0], pair[1]);
+                    // The twin was consumed with the edge, so it is supported as well
+                    if self.canonical {
+                        let twin = |id: NodeId| self.nodes.id(&self.nodes[id].idx.revcomp(self.k));
+                        if let (Some(rs), Some(rp)) = (twin(s), twin(p)) { *support.entry((rs, rp)).or_default() += 1; }
+                    }
+                    *support.entry((p, s)).or_default() += 1;
+                }
+            }
+            self.paths.clear();
+            self.cycles.clear();
+            self.abandoned.clear();
+            // Restore the supported edges, decay the rest and drop the ones that fell below min_weight
+            let (mut decayed, mut split) = (0, vec![]);
+            for edges in self.graph.links_mut() {
+                let mut supported = support.get(&(edges[0].prefix, edges[0].suffix)).copied().unwrap_or(0);
+                for edge in edges.iter_mut() {
+                    edge.unmark_used(&mut self.nodes);
+                    let kept = supported.min(edge.count as usize) as u32;
+                    supported -= kept as usize;
+                    decayed += (edge.count - kept) as usize;
+                    if kept == 0 {
+                        edge.weight *= decay;
+                        continue;
+                    }
+                    // The copies that were not walked decay apart from the others
+                    if kept < edge.count {
+                        let mut rest = edge.split_off(edge.count - kept);
+                        rest.weight *= decay;
+                        split.push(rest);
+                    }
+                    edge.weight = 1.0;
+                }
+            }
+            self.graph.extend(split, self.nodes.len())?;
+            let (before, nodes) = (self.graph.count(), &mut self.nodes);
+            self.graph.retain(|e| {
+                if e.weight >= min_weight { return true; }
+                nodes[e.prefix].odeg -= e.count as usize;
+                nodes[e.suffix].ideg -= e.count as usize;
+                false
+            })?;
+            removed += before - self.graph.count();
+            if self.paranoid { self.check_degrees(); }
+            if decayed == 0 { break; }
+        }
+        Ok(removed)
+    }
+
+    // Panic unless every node's degrees equal the number of its unused edges
+    pub fn check_degrees(&self) {
+        let (mut ideg, mut odeg) = (vec![0; self.nodes.len()], vec![0; self.nodes.len()]);
+        for edge in self.graph.iter() {
+            odeg[edge.prefix.index()] += edge.unused();
+            ideg[edge.suffix.index()] += edge.unused();
+        }
+        for (id, node) in self.nodes.iter() {
+            let (i, o) = (ideg[id.index()], odeg[id.index()]);
+            assert!(node.ideg == i && node.odeg == o,
+                "paranoid: node {:?} has ideg {} / odeg {} but {} / {} unused incoming / outgoing edges",
+                node.idx, node.ideg, node.odeg, i, o);
+        }
+    }
+
+    // Panic unless every pair of adjacent nodes of the walk a contig was spelled from is linked in the graph
+    // and the contig starts and ends with the first and last node of the walk
+    pub fn check_contig_support(&self, walk: &[NodeId], contig: &Contig) {
+        for (pos, pair) in walk.windows(2).enumerate() {
+            let linked = !self.graph.link(pair[0], pair[1]).is_empty();
+            assert!(linked, "paranoid: contig from {:?} walks {:?} -> {:?} at step {} but the graph has no such edge",
+                contig.provenance, self.nodes[pair[0]].idx, self.nodes[pair[1]].idx, pos);
+        }
+        if let (Some(first), Some(last)) = (walk.first().map(|&id| &self.nodes[id].idx), walk.last().map(|&id| &self.nodes[id].idx)) {
+            assert!(contig.seq.starts_with(&first.unpack(self.k)) && contig.seq.ends_with(&last.unpack(self.k)),
+                "paranoid: contig from {:?} does not start with node {:?} and end with node {:?}", contig.provenance, first, last);
+        }
+    }
+
+    // Panic if two contigs have the same sequence
+    pub fn check_no_duplicate_contigs(&self) {
+        let mut seen: HashMap<&[u8], usize> = HashMap::new();
+        for (i, contig) in self.contigs.iter().enumerate() {
+            if let Some(j) = seen.insert(&contig.seq, i) {
+                panic!("paranoid: contigs {} and {} are identical ({} nucleotides, from {:?} and {:?})",
+                    j, i, contig.len(), self.contigs[j].provenance, contig.provenance);
+            }
+        }
+    }
+
     // Convert paths and cycles to contigs
     pub fn paths_cycles_to_contigs(&mut self) {
+        self.walks_to_contigs([0, 0, 0]);
+    }
+
+    // Find the paths and cycles of one component, as listed by components, and convert them to contigs. Returns the
+    // number of contigs added
+    pub fn assemble_component(&mut self, ids: &[NodeId]) -> usize {
+        let (contigs, from) = (self.contigs.len(), [self.paths.len(), self.cycles.len(), self.abandoned.len()]);
+        self.populate_paths_or_cycles_in(PathType::Path, ids);
+        self.populate_paths_or_cycles_in(PathType::Cycle, ids);
+        self.walks_to_contigs(from);
+        self.contigs.len() - contigs
+    }
+
+    // Assemble every component, as listed by components, in a graph of its own on the rayon pool and condense its
+    // contigs there. Components share no edges, so the walks are the ones assemble_component finds, and a task only
+    // holds the graph of its component. The walks are then marked used here, and they and the contigs are added in
+    // component order. Returns the number of components with contigs
+    pub fn assemble_components_par(&mut self, components: &Components, condense: impl Fn(&mut Assembler<K>) + Sync) -> usize {
+        let assembled: Vec<Assembled> = components.nodes.par_iter().map(|ids| {
+            if self.is_cancelled() { return Default::default(); }
+            let mut sub = self.subgraph(ids);
+            let all: Vec<NodeId> = sub.nodes.iter().map(|(id, _)| id).collect();
+            if sub.assemble_component(&all) > 0 { condense(&mut sub); }
+            // Back to the ids of this graph
+            let back = |walks: Vec<Vec<NodeId>>| -> Vec<Vec<NodeId>> {
+                walks.into_iter().map(|walk| walk.into_iter().map(|id| ids[id.index()]).collect()).collect()
+            };
+            ([back(sub.paths), back(sub.cycles), back(sub.abandoned)], sub.contigs)
+        }).collect();
+        let mut with_contigs = 0;
+        for ([paths, cycles, abandoned], contigs) in assembled {
+            for walk in paths.iter().chain(&cycles).chain(&abandoned) { self.retrace(walk); }
+            let from = [self.paths.len(), self.cycles.len(), self.abandoned.len()];
+            self.paths.extend(paths);
+            self.cycles.extend(cycles);
+            self.abandoned.extend(abandoned);
+            if !contigs.is_empty() { with_contigs += 1; }
+            self.contigs.extend(contigs.into_iter().map(|mut contig| {
+                for source in contig.provenance.iter_mut() { *source = source.offset(from); }
+                contig
+            }));
+        }
+        if self.paranoid { self.check_degrees(); }
+        with_contigs
+    }
+
+    // A graph of its own with the given nodes, such as those of a component, and the edges between them. The walk
+    // settings and the reads and mates of the nodes come along, paths and contigs do not. Node i of the new graph is
+    // ids[i], so ids should be in id order to keep the order successors are tried in
+    pub fn subgraph(&self, ids: &[NodeId]) -> Self {
+        let mut nodes = Nodes::with_hasher(self.nodes.hasher().clone());
+        let mut new: HashMap<NodeId, NodeId> = HashMap::with_capacity(ids.len());
+        for &id in ids {
+            let node = &self.nodes[id];
+            new.insert(id, nodes.insert(Node::new(node.idx.clone(), node.ideg, node.odeg, node.cov)));
+        }
+        let edges: Vec<Edge> = ids.iter().flat_map(|&id| self.graph.edges(id))
+            .filter_map(|e| Some(Edge { prefix: new[&e.prefix], suffix: *new.get(&e.suffix)?, ..*e }))
+            .collect();
+        let mut sub = Assembler::from_graph(Graph::new(edges, nodes.len()), nodes, self.k);
+        if !self.read_ids.is_empty() {
+            sub.read_ids = sub.graph.links().filter_map(|edges| {
+                let key = (sub.nodes[edges[0].prefix].idx.clone(), sub.nodes[edges[0].suffix].idx.clone());
+                let reads = self.read_ids.get(&key)?.clone();
+                Some((key, reads))
+            }).collect();
+        }
+        sub.mates = self.mates.iter().filter(|m| sub.nodes.contains_key(&m.first.0)).cloned().collect();
+        sub.cancelled = self.cancelled.clone();
+        sub.paranoid = self.paranoid;
+        sub.canonical = self.canonical;
+        sub.traversal = self.traversal;
+        sub.backtrack = self.backtrack;
+        sub.attempts = self.attempts;
+        sub.seed = self.seed;
+        sub.parallel = self.parallel;
+        sub.deterministic = self.deterministic;
+        sub
+    }
+
+    // Convert the paths, cycles and abandoned cycle walks from the given indices on to contigs
+    fn walks_to_contigs(&mut self, [paths, cycles, abandoned]: [usize; 3]) {
         // Chain the paths and cycles into one vector
-        for path_or_cycle in self.paths.iter().chain(self.cycles.iter()) {
-            let mut contig = Vec::new();
-            for node in path_or_cycle {
-                let seq = utils::idx2vec(node.borrow().idx, 15);
-                contig.extend_from_slice(&seq);
+        // Abandoned cycle walks are still valid linear walks, so they are spelled like paths
+        let paths = self.paths.iter().enumerate().skip(paths).map(|(i, p)| (p, Source::Path(i)));
+        let cycles = self.cycles.iter().enumerate().skip(cycles).map(|(i, c)| (c, Source::Cycle(i)));
+        let abandoned = self.abandoned.iter().enumerate().skip(abandoned)
+            .filter(|(_, w)| w.len() >= 3)
+            .map(|(i, w)| (w, Source::Abandoned(i)));
+        for (path_or_cycle, source) in paths.chain(cycles).chain(abandoned) {
+            let mut seq = Vec::new();
+            let mut cov = 0;
+            let mut prev: Option<NodeId> = None;
+            for &id in path_or_cycle {
+                let node = &self.nodes[id];
+                // Skip the part of the node that overlaps the previous one when the edge came from a short read
+                let skip = prev.map(|p| 2 * self.k - self.span(p, id)).unwrap_or(0);
+                seq.extend_from_slice(&node.idx.unpack(self.k)[skip..]);
+                cov += node.cov;
+                prev = Some(id);
             }
+            let coverage = cov as f32 / path_or_cycle.len().max(1) as f32;
+            let ends = self.break_points(path_or_cycle, source);
+            let mut contig = Contig::new(seq, coverage, matches!(source, Source::Cycle(_)), source, ends);
+            let support = self.walk_coverage(path_or_cycle);
+            contig.edge_coverage = support.iter().sum::<usize>() as f32 / support.len().max(1) as f32;
+            contig.min_edge_coverage = support.iter().min().copied().unwrap_or(0);
+            (contig.colors, contig.any_colors) = self.walk_colors(path_or_cycle);
+            if !self.read_ids.is_empty() { contig.reads = self.read_spans(path_or_cycle); }
+            if self.paranoid { self.check_contig_support(path_or_cycle, &contig); }
             self.contigs.push(contig);
         }
     }
 
-    // Remove all contigs that are completely encompassed by another contig
+    // Number of reads containing the k-mer, 0 if it is not a node
+    pub fn node_coverage(&self, idx: &K) -> usize {
+        self.nodes.get(idx).map(|node| node.cov).unwrap_or(0)
+    }
+
+    // Number of reads linking the k-mer p to the k-mer s, 0 if they are not linked
+    pub fn edge_coverage(&self, p: &K, s: &K) -> usize {
+        match (self.nodes.id(p), self.nodes.id(s)) {
+            (Some(p), Some(s)) => self.graph.link(p, s).iter().map(|e| e.reads).sum(),
+            _ => 0,
+        }
+    }
+
+    // Number of reads supporting each step of a walk
+    pub fn walk_coverage(&self, walk: &[NodeId]) -> Vec<usize> {
+        walk.windows(2).map(|pair| self.graph.link(pair[0], pair[1]).iter().map(|e| e.reads).sum()).collect()
+    }
+
+    // The samples that have every step of a walk and those that have any, see add_colors
+    pub fn walk_colors(&self, walk: &[NodeId]) -> (u64, u64) {
+        let steps: Vec<u64> = walk.windows(2)
+            .map(|pair| self.graph.link(pair[0], pair[1]).iter().fold(0, |colors, e| colors | e.colors))
+            .collect();
+        (steps.iter().copied().reduce(|a, b| a & b).unwrap_or(0), steps.iter().fold(0, |a, b| a | b))
+    }
+
+    // Read length of the edges from node p to node s. Should reads of different lengths link the same
+    // pair of nodes, the most common length wins
+    pub fn span(&self, p: NodeId, s: NodeId) -> usize {
+        let edges = self.graph.link(p, s);
+        if edges.is_empty() { return 2 * self.k; }
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for edge in edges { *counts.entry(edge.span).or_default() += edge.reads; }
+        counts.into_iter().max_by_key(|&(span, n)| (n, span)).map(|(span, _)| span).unwrap_or(2 * self.k)
+    }
+
+    // The nodes a walk starts and stops at and why
+    // Traversal only stops when it closes a cycle or no successor is left, so a stop at a node that
+    // still has outgoing edges means all of them were consumed by earlier walks
+    fn break_points(&self, walk: &[NodeId], source: Source) -> [BreakPoint; 2] {
+        let first = walk.first().map(|&id| self.nodes[id].idx.clone());
+        let last = walk.last().map(|&id| self.nodes[id].idx.clone());
+        let exhausted = walk.last().is_some_and(|&id| !self.graph.edges(id).is_empty());
+        let start = match source {
+            Source::Path(_) => EndReason::Source,
+            Source::Cycle(_) => EndReason::Closed,
+            Source::Abandoned(_) => EndReason::CycleStart,
+        };
+        let stop = match source {
+            Source::Cycle(_) => EndReason::Closed,
+            _ if exhausted => EndReason::Exhausted,
+            _ => EndReason::DeadEnd,
+        };
+        // Both sides of a canonical vertex are reported as the vertex
+        let id = |idx: Option<K>| idx.map(|idx| if self.canonical { idx.canonical(self.k) } else { idx }.id()).unwrap_or_default();
+        [BreakPoint { node: id(first), reason: start }, BreakPoint { node: id(last), reason: stop }]
+    }
+
+    // Remove all contigs that are completely encompassed by another contig, or by its reverse complement once the
+    // strands are joined
     // This method is parallalized, making it orders of magnitudes faster for large conig arrays
     // Returns the number of contigs that was removes
     pub fn remove_contained_contigs(&mut self) -> usize {
@@ -125,13 +1310,15 @@ impl Assembler {
         self.contigs.sort_unstable_by_key(|c| std::cmp::Reverse(c.len()));
         // Describes which contigs should be removed
         let to_remove = Vec::from_iter((0..self.contigs.len()).map(|_| AtomicBool::new(false)));
+        let contains = |contig: &[u8], sub: &[u8]| Self::is_contig_contains(contig, sub)
+            || (self.canonical && Self::is_contig_contains(contig, &simd::revcomp(sub)));
         self.contigs.par_iter().enumerate().for_each(|(i, contig_i)| {
-            if to_remove[i].load(Ordering::SeqCst) { return }
+            if to_remove[i].load(Ordering::SeqCst) || self.cancelled.load(Ordering::Relaxed) { return }
             for j in (i + 1)..self.contigs.len() {
                 if to_remove[j].load(Ordering::SeqCst) { continue; }
-                if contig_i.len() > self.contigs[j].len() && Self::is_contig_contains(contig_i.as_slice(), self.contigs[j].as_slice()) {
+                if contig_i.len() > self.contigs[j].len() && contains(&contig_i.seq, &self.contigs[j].seq) {
                     to_remove[j].store(true, Ordering::SeqCst);
-                } else if Self::is_contig_contains(self.contigs[j].as_slice(), contig_i.as_slice()) {
+                } else if contains(&self.contigs[j].seq, &contig_i.seq) {
                     to_remove[i].store(true, Ordering::SeqCst);
                     break;
                 }
@@ -144,6 +1331,7 @@ impl Assembler {
             .enumerate()
             .filter_map(|(i, v)| if !to_remove[i].load(Ordering::SeqCst) { Some(v.clone()) } else { None })
             .collect();
+        if self.paranoid { self.check_no_duplicate_contigs(); }
         // return number of removed contigs
         to_remove.iter().filter(|&b| b.load(Ordering::SeqCst)).count()
     }
@@ -159,19 +1347,27 @@ impl Assembler {
         false
     }
 
-    // Merges contigs if they overlap
+    // Merges contigs if they overlap. Once the strands are joined, a contig also merges with the reverse complement of another
+    // The required overlap is the larger of min_overlap and min_overlap_frac of the shorter contig
     // This method is parallalized, making it orders of magnitudes faster for large conig arrays
     // Returns the number of contigs that were merged
-    pub fn merge_contigs(&mut self, min_overlap: usize) -> usize {
+    pub fn merge_contigs(&mut self, min_overlap: usize, min_overlap_frac: f32) -> usize {
         let mut merged = 0;
+        self.merge_stats.reset();
+        let stats = &self.merge_stats;
         self.contigs.sort_unstable_by_key(|contig| std::cmp::Reverse(contig.len()));
         let mut i = 0;
         while i < self.contigs.len() {
+            if self.is_cancelled() { break; }
             let j_range = i + 1..self.contigs.len();
             let overlaps = j_range.clone().into_par_iter().map(|j| {
-                Self::merge_if_overlap(&self.contigs[i], &self.contigs[j], min_overlap)
+                let required = Self::required_overlap(&self.contigs[i].seq, &self.contigs[j].seq, min_overlap, min_overlap_frac);
+                Self::merge_if_overlap(&self.contigs[i], &self.contigs[j], required, stats.current())
+                    .or_else(|| self.canonical.then(|| {
+                        Self::merge_if_overlap(&self.contigs[i], &self.contigs[j].revcomp(), required, stats.current())
+                    }).flatten())
             }).collect::<Vec<_>>();
-            for (j, overlap) in (j_range).zip(overlaps.into_iter()) {
+            for (j, overlap) in (j_range).zip(overlaps) {
                 if let Some((_, new_contig)) = overlap {
                     self.contigs.swap_remove(j);
                     self.contigs.swap_remove(i);
@@ -186,66 +1382,798 @@ impl Assembler {
         merged
     }
 
+    // Remove contained contigs and merge overlapping ones, along their string graph with string_graph set, until the
+    // number of contigs settles
+    pub fn condense(&mut self, min_overlap: usize, min_overlap_frac: f32, string_graph: bool) {
+        let mut prev = usize::MAX;
+        while prev != self.contigs.len() && !self.is_cancelled() {
+            prev = self.contigs.len();
+            self.remove_contained_contigs();
+            if string_graph {
+                self.merge_string_graph(min_overlap, min_overlap_frac);
+            } else {
+                self.merge_contigs(min_overlap, min_overlap_frac);
+            }
+        }
+    }
+
+    // Merge contigs along the chains of their transitively reduced overlap graph, see string_graph. Returns the
+    // graph, with the number of overlaps and transitive edges, and the number of contigs that were merged
+    pub fn merge_string_graph(&mut self, min_overlap: usize, min_overlap_frac: f32) -> (StringGraph, usize) {
+        self.merge_stats.reset();
+        let mut graph = StringGraph::new(&self.contigs, self.canonical, min_overlap, min_overlap_frac, &self.merge_stats);
+        graph.reduce();
+        let (contigs, merged) = graph.merge_chains(&self.contigs, self.canonical);
+        self.contigs = contigs;
+        (graph, merged)
+    }
+
+    // The overlap needed to merge c1 and c2
+    pub(crate) fn required_overlap(c1: &[u8], c2: &[u8], min_overlap: usize, min_overlap_frac: f32) -> usize {
+        let shorter = c1.len().min(c2.len());
+        min_overlap.max((shorter as f32 * min_overlap_frac).ceil() as usize)
+    }
+
+    // Find joins between contigs that were almost merged: exact overlaps between half the required
+    // overlap and the required overlap, or overlaps of at least half the required overlap with 1 to
+    // max_mismatches mismatches. Only the longest candidate of each ordered pair is reported
+    // This method is parallalized
+    pub fn near_miss_merges(&self, min_overlap: usize, min_overlap_frac: f32, max_mismatches: usize) -> Vec<NearMiss> {
+        let contigs = &self.contigs;
+        let n = contigs.len();
+        let mut near_misses: Vec<NearMiss> = (0..n).into_par_iter().flat_map_iter(|i| {
+            (0..n).filter(move |&j| j != i).filter_map(move |j| {
+                let (left, right) = (&contigs[i].seq, &contigs[j].seq);
+                let required = Self::required_overlap(left, right, min_overlap, min_overlap_frac);
+                let floor = (required / 2).max(1);
+                // Longest suffix of left matching a prefix of right that was not merged
+                for len in (floor..=left.len().min(right.len())).rev() {
+                    let mismatches = simd::hamming(&left[left.len() - len..], &right[..len]);
+                    if mismatches > max_mismatches || (mismatches == 0 && len >= required) { continue; }
+                    return Some(NearMiss { left: i, right: j, overlap: len, mismatches, required });
+                }
+                None
+            })
+        }).collect();
+        near_misses.sort_unstable_by_key(|m| (m.left, m.right));
+        near_misses
+    }
+
     // merges c1 and c2 if they overlap
-    fn merge_if_overlap(c1: &[u8], c2: &[u8], min_overlap_len: usize) -> Option<(usize, Vec<u8>)> {
+    fn merge_if_overlap(c1: &Contig, c2: &Contig, min_overlap_len: usize, counters: &MergeCounters) -> Option<(usize, Contig)> {
         let overlap_range = c1.len().min(c2.len());
+        let (s1, s2) = (&c1.seq, &c2.seq);
+        let mut merged = None;
+        let mut bytes = 0;
         // Check for overlap at beginning of c1 and end of c2
-        for i in 0..overlap_range {
-            if c1.starts_with(&c2[c2.len() - i - 1..]) {
-                let overlap_len = i + 1;
-                if overlap_len >= min_overlap_len {
-                    let new_contig = [&c2[..c2.len() - overlap_len], c1].concat();
-                    return Some((overlap_len, new_contig));
-                }
+        for overlap_len in min_overlap_len.max(1)..=overlap_range {
+            bytes += 2 * overlap_len;
+            if s1.starts_with(&s2[s2.len() - overlap_len..]) {
+                merged = Some((overlap_len, Contig::merge(c2, c1, overlap_len)));
+                break;
             }
-            if c2.starts_with(&c1[c1.len() - i - 1..]) {
-                let overlap_len = i + 1;
-                if overlap_len >= min_overlap_len {
-                    let new_contig = [&c1[..c1.len() - overlap_len], c2].concat();
-                    return Some((overlap_len, new_contig));
-                }
+            if s2.starts_with(&s1[s1.len() - overlap_len..]) {
+                merged = Some((overlap_len, Contig::merge(c1, c2, overlap_len)));
+                break;
             }
         }
-        None
+        counters.record(bytes, merged.is_some());
+        merged
+    }
+}
+
+// The side of a canonical vertex a node is: the canonical k-mer itself or its reverse complement
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    pub fn of<K: KmerRepr>(idx: &K, k: usize) -> Self {
+        if *idx == idx.canonical(k) { Strand::Forward } else { Strand::Reverse }
+    }
+
+    // The orientation of a GFA link end
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Strand::Forward => "+",
+            Strand::Reverse => "-",
+        }
+    }
+}
+
+// Returns true if the walk consumed at least one edge and ends where it started. Walks are node ids whatever the
+// k-mer type, so this takes no Assembler
+pub fn is_closed(walk: &[NodeId]) -> bool {
+    match (walk.first(), walk.last()) {
+        (Some(first), Some(last)) => walk.len() > 1 && first == last,
+        _ => false,
     }
 }
 
 #[derive(PartialEq)]
-pub struct Node {
-    pub idx: u32,
+pub struct Node<K = u64> {
+    pub idx: K,
     pub ideg: usize,
     pub odeg: usize,
+    // Number of reads containing the k-mer. Unlike the degrees, this is not consumed by traversal
+    pub cov: usize,
 }
 
-impl Node {
-    pub fn new(idx: u32, ideg: usize, odeg: usize) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Node {
+impl<K> Node<K> {
+    pub fn new(idx: K, ideg: usize, odeg: usize, cov: usize) -> Self {
+        Node {
             idx,
             ideg,
             odeg,
-        }))
+            cov,
+        }
+    }
+}
+
+// Position of a node in Nodes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// Longest node Nodes::dense takes, for a table of 4^14 ids or 1 GiB
+pub const DENSE_MAX_K: usize = 14;
+
+// How node k-mers are hashed. Fx takes a multiply per word, far less than SipHash, but reads can be crafted so
+// that many k-mers collide. SipHash is keyed at random and holds up to that
+#[derive(Clone, Debug, Default)]
+pub enum NodeHasher {
+    #[default]
+    Fx,
+    Sip(RandomState),
+}
+
+impl NodeHasher {
+    pub fn sip() -> Self {
+        NodeHasher::Sip(RandomState::new())
+    }
+}
+
+impl BuildHasher for NodeHasher {
+    type Hasher = NodeHash;
+
+    fn build_hasher(&self) -> NodeHash {
+        match self {
+            NodeHasher::Fx => NodeHash::Fx(FxHasher::default()),
+            NodeHasher::Sip(state) => NodeHash::Sip(state.build_hasher()),
+        }
+    }
+}
+
+pub enum NodeHash {
+    Fx(FxHasher),
+    Sip(DefaultHasher),
+}
+
+impl Hasher for NodeHash {
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            NodeHash::Fx(hasher) => hasher.write(bytes),
+            NodeHash::Sip(hasher) => hasher.write(bytes),
+        }
+    }
+
+    // u64 k-mers are hashed as one word, which Fx does without going through write
+    fn write_u64(&mut self, word: u64) {
+        match self {
+            NodeHash::Fx(hasher) => hasher.write_u64(word),
+            NodeHash::Sip(hasher) => hasher.write_u64(word),
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        match self {
+            NodeHash::Fx(hasher) => hasher.finish(),
+            NodeHash::Sip(hasher) => hasher.finish(),
+        }
+    }
+}
+
+// The nodes of a graph, stored one after the other and addressed by NodeId, and the id of each k-mer hashed with S
+pub struct Nodes<K = u64, S = NodeHasher> {
+    nodes: Vec<Node<K>>,
+    ids: HashMap<K, NodeId, S>,
+    // With Nodes::dense, the id plus one of every possible k-mer, or 0 if it is not a node, indexed by its code
+    // instead of ids. Empty otherwise
+    dense: Vec<u32>,
+}
+
+impl<K, S: Default> Default for Nodes<K, S> {
+    fn default() -> Self {
+        Nodes::with_hasher(S::default())
+    }
+}
+
+impl<K: KmerRepr, S: BuildHasher + Default> Nodes<K, S> {
+    // Nodes of length k looked up in a table of all 4^k k-mers, so none is hashed. The table is zeroed by the allocator
+    // and only the pages around k-mers that are seen take memory, which pays off once the nodes fill a good part of it
+    // None if k is over DENSE_MAX_K or K does not give k-mers a code
+    pub fn dense(k: usize) -> Option<Self> {
+        let coded = K::pack(b"A").is_some_and(|a| a.code().is_some());
+        (coded && k <= DENSE_MAX_K).then(|| Nodes { dense: vec![0; 1 << (2 * k)], ..Nodes::default() })
+    }
+
+    // Whether k-mers are looked up in the table of all of them rather than hashed
+    pub fn is_dense(&self) -> bool {
+        !self.dense.is_empty()
+    }
+
+    fn code(&self, idx: &K) -> Option<usize> {
+        idx.code().filter(|_| self.is_dense())
+    }
+
+    pub fn id(&self, idx: &K) -> Option<NodeId> {
+        match self.code(idx) {
+            Some(code) => self.dense[code].checked_sub(1).map(NodeId),
+            None => self.ids.get(idx).copied(),
+        }
+    }
+
+    pub fn get(&self, idx: &K) -> Option<&Node<K>> {
+        self.id(idx).map(|id| &self[id])
+    }
+
+    pub fn contains_key(&self, idx: &K) -> bool {
+        self.id(idx).is_some()
+    }
+
+    // Add a node, or replace the one with the same k-mer
+    pub fn insert(&mut self, node: Node<K>) -> NodeId {
+        if let Some(id) = self.id(&node.idx) {
+            self[id] = node;
+            return id;
+        }
+        let id = NodeId(u32::try_from(self.nodes.len()).ok().filter(|&i| i < u32::MAX).expect("more than 2^32 - 1 nodes"));
+        match self.code(&node.idx) {
+            Some(code) => self.dense[code] = id.0 + 1,
+            None => { self.ids.insert(node.idx.clone(), id); }
+        }
+        self.nodes.push(node);
+        id
+    }
+
+    // The id of a k-mer, adding it without degrees or coverage if it is not a node yet
+    pub fn id_or_insert(&mut self, idx: &K) -> NodeId {
+        match self.id(idx) {
+            Some(id) => id,
+            None => self.insert(Node::new(idx.clone(), 0, 0, 0)),
+        }
+    }
+
+    // Remove the nodes for which keep returns false. The others keep their order but move down to close the gaps, so
+    // the new id of every old one is returned, None for the removed ones
+    pub fn retain(&mut self, mut keep: impl FnMut(NodeId, &Node<K>) -> bool) -> Vec<Option<NodeId>> {
+        let mut ids = Vec::with_capacity(self.nodes.len());
+        let mut kept = 0;
+        for (i, node) in self.nodes.iter().enumerate() {
+            let code = node.idx.code().filter(|_| !self.dense.is_empty());
+            if !keep(NodeId(i as u32), node) {
+                match code {
+                    Some(code) => self.dense[code] = 0,
+                    None => { self.ids.remove(&node.idx); }
+                }
+                ids.push(None);
+                continue;
+            }
+            let id = NodeId(kept);
+            match code {
+                Some(code) => self.dense[code] = id.0 + 1,
+                None => { self.ids.insert(node.idx.clone(), id); }
+            }
+            ids.push(Some(id));
+            kept += 1;
+        }
+        let mut old = ids.iter();
+        self.nodes.retain(|_| old.next().is_some_and(|id| id.is_some()));
+        ids
+    }
+}
+
+impl<K, S> Nodes<K, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        Nodes { nodes: vec![], ids: HashMap::with_hasher(hasher), dense: vec![] }
+    }
+
+    pub fn hasher(&self) -> &S {
+        self.ids.hasher()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node<K>)> {
+        self.nodes.iter().enumerate().map(|(i, node)| (NodeId(i as u32), node))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Node<K>> {
+        self.nodes.iter_mut()
+    }
+}
+
+impl<K, S> Index<NodeId> for Nodes<K, S> {
+    type Output = Node<K>;
+
+    fn index(&self, id: NodeId) -> &Node<K> {
+        &self.nodes[id.index()]
+    }
+}
+
+impl<K, S> IndexMut<NodeId> for Nodes<K, S> {
+    fn index_mut(&mut self, id: NodeId) -> &mut Node<K> {
+        &mut self.nodes[id.index()]
     }
 }
 
+// The reads of one length linking two nodes. Each copy of the read can be walked once
 pub struct Edge {
-    pub prefix: Rc<RefCell<Node>>,
-    pub suffix: Rc<RefCell<Node>>,
-    pub used: bool,
+    pub prefix: NodeId,
+    pub suffix: NodeId,
+    // Number of copies and how many of them were walked
+    pub count: u32,
+    pub used: u32,
+    pub weight: f32,
+    // Number of input reads collapsed into this edge
+    pub reads: usize,
+    // Length of the read, 2k unless the suffix node overlaps the prefix node
+    pub span: usize,
+    // The samples whose reads have this edge, one bit each, see Assembler::add_colors
+    pub colors: u64,
 }
 
 impl Edge {
-    pub fn new(prefix: Rc<RefCell<Node>>, suffix: Rc<RefCell<Node>>) -> Self {
+    pub fn new(prefix: NodeId, suffix: NodeId, span: usize) -> Self {
         Edge {
             prefix,
             suffix,
-            used: false,
+            count: 1,
+            used: 0,
+            weight: 1.0,
+            reads: 1,
+            span,
+            colors: 0,
         }
     }
 
-    pub fn mark_used(&mut self) {
-        self.prefix.borrow_mut().odeg -= 1;
-        self.suffix.borrow_mut().ideg -= 1;
-        self.used = true;
+    // Copies that were not walked yet
+    pub fn unused(&self) -> usize {
+        (self.count - self.used) as usize
+    }
+
+    // Walk one copy
+    pub fn mark_used<K>(&mut self, nodes: &mut Nodes<K>) {
+        nodes[self.prefix].odeg -= 1;
+        nodes[self.suffix].ideg -= 1;
+        self.used += 1;
+    }
+
+    // Undo mark_used so every copy can be traversed again
+    pub fn unmark_used<K>(&mut self, nodes: &mut Nodes<K>) {
+        nodes[self.prefix].odeg += self.used as usize;
+        nodes[self.suffix].ideg += self.used as usize;
+        self.used = 0;
+    }
+
+    // Move n unused copies, and their share of the input reads, into an edge of their own
+    pub fn split_off(&mut self, n: u32) -> Edge {
+        let reads = self.reads * n as usize / self.count as usize;
+        self.count -= n;
+        self.reads -= reads;
+        Edge { count: n, used: 0, reads, ..*self }
+    }
+
+    // The sides of the canonical vertices the edge leaves and enters
+    pub fn orientation<K: KmerRepr>(&self, nodes: &Nodes<K>, k: usize) -> [Strand; 2] {
+        [Strand::of(&nodes[self.prefix].idx, k), Strand::of(&nodes[self.suffix].idx, k)]
+    }
+}
+
+// The edges of a graph in compressed sparse row form, frozen once it is built. The edges leaving a node are stored one
+// after the other, sorted by suffix and span so the reads linking the same two nodes are next to each other
+pub struct Graph {
+    storage: Box<dyn GraphStorage>,
+}
+
+// Where a graph keeps its arrays. The edges leaving node n are edges[offsets[n]..offsets[n + 1]]
+// A graph is always built in memory and then stored, so a storage only has to lend the arrays out as slices
+pub trait GraphStorage: Send + Sync {
+    fn offsets(&self) -> &[usize];
+    fn edges(&self) -> &[Edge];
+    fn edges_mut(&mut self) -> &mut [Edge];
+    // Replace the arrays, such as when the graph is rebuilt
+    fn store(&mut self, offsets: Vec<usize>, edges: Vec<Edge>) -> io::Result<()>;
+    // Move the arrays out to rebuild them, leaving the storage empty until the next store
+    fn take(&mut self) -> (Vec<usize>, Vec<Edge>);
+}
+
+// The default storage, two Vecs on the heap
+pub struct MemoryGraph {
+    offsets: Vec<usize>,
+    edges: Vec<Edge>,
+}
+
+impl GraphStorage for MemoryGraph {
+    fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    fn edges_mut(&mut self) -> &mut [Edge] {
+        &mut self.edges
+    }
+
+    fn store(&mut self, offsets: Vec<usize>, edges: Vec<Edge>) -> io::Result<()> {
+        self.offsets = offsets;
+        self.edges = edges;
+        Ok(())
+    }
+
+    fn take(&mut self) -> (Vec<usize>, Vec<Edge>) {
+        (std::mem::replace(&mut self.offsets, vec![0]), std::mem::take(&mut self.edges))
+    }
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Graph::new(vec![], 0)
+    }
+}
+
+impl Graph {
+    // Freeze the edges between the first n nodes, counting copies of the same read as one edge
+    pub fn new(edges: Vec<Edge>, n: usize) -> Self {
+        let (offsets, edges) = Self::freeze(edges, n);
+        Graph { storage: Box::new(MemoryGraph { offsets, edges }) }
+    }
+
+    fn freeze(mut edges: Vec<Edge>, n: usize) -> (Vec<usize>, Vec<Edge>) {
+        Self::collapse(&mut edges);
+        let mut offsets = vec![0; n + 1];
+        for edge in edges.iter() { offsets[edge.prefix.index() + 1] += 1; }
+        for i in 1..offsets.len() { offsets[i] += offsets[i - 1]; }
+        (offsets, edges)
+    }
+
+    // Move the graph into another storage, such as disk_graph::DiskGraph
+    pub fn move_to(&mut self, mut storage: Box<dyn GraphStorage>) -> io::Result<()> {
+        let (offsets, edges) = self.storage.take();
+        storage.store(offsets, edges)?;
+        self.storage = storage;
+        Ok(())
+    }
+
+    // Rebuild the graph from its edges in the storage it is in
+    fn rebuild(&mut self, edges: Vec<Edge>, n: usize) -> io::Result<()> {
+        let (offsets, edges) = Self::freeze(edges, n);
+        self.storage.store(offsets, edges)
+    }
+
+    // Sort edges by the nodes they link and their span, and fold together the ones that only differ in their counts.
+    // Edges of the same read that were weighted differently stay apart in the order they were given in
+    pub fn collapse(edges: &mut Vec<Edge>) {
+        edges.par_sort_by_key(|e| (e.prefix, e.suffix, e.span));
+        edges.dedup_by(|e, kept| {
+            let same = (e.prefix, e.suffix, e.span) == (kept.prefix, kept.suffix, kept.span) && e.weight.to_bits() == kept.weight.to_bits();
+            if same {
+                kept.count += e.count;
+                kept.used += e.used;
+                kept.reads += e.reads;
+                kept.colors |= e.colors;
+            }
+            same
+        });
+    }
+
+    // Add edges, which may leave nodes added since the graph was frozen, and freeze it again
+    pub fn extend(&mut self, edges: Vec<Edge>, n: usize) -> io::Result<()> {
+        let mut all = self.storage.take().1;
+        all.extend(edges);
+        self.rebuild(all, n)
+    }
+
+    // Remove the edges for which f returns false
+    pub fn retain(&mut self, f: impl FnMut(&Edge) -> bool) -> io::Result<()> {
+        let (offsets, mut edges) = self.storage.take();
+        edges.retain(f);
+        self.rebuild(edges, offsets.len() - 1)
+    }
+
+    // Give the nodes the new ids from Nodes::retain. No edge may be left at a removed node
+    pub fn renumber(&mut self, ids: &[Option<NodeId>]) -> io::Result<()> {
+        let (_, mut edges) = self.storage.take();
+        for edge in edges.iter_mut() {
+            edge.prefix = ids[edge.prefix.index()].expect("edge at a removed node");
+            edge.suffix = ids[edge.suffix.index()].expect("edge at a removed node");
+        }
+        self.rebuild(edges, ids.iter().flatten().count())
+    }
+
+    fn range(&self, p: NodeId) -> Range<usize> {
+        match self.storage.offsets().get(p.index()..p.index() + 2) {
+            Some(&[start, end]) => start..end,
+            _ => 0..0,
+        }
+    }
+
+    // The edges leaving p
+    pub fn edges(&self, p: NodeId) -> &[Edge] {
+        &self.storage.edges()[self.range(p)]
+    }
+
+    // The edges from p to s
+    pub fn link(&self, p: NodeId, s: NodeId) -> &[Edge] {
+        let edges = self.edges(p);
+        &edges[edges.partition_point(|e| e.suffix < s)..edges.partition_point(|e| e.suffix <= s)]
+    }
+
+    pub fn link_mut(&mut self, p: NodeId, s: NodeId) -> &mut [Edge] {
+        let range = self.range(p);
+        let edges = &self.storage.edges()[range.clone()];
+        let (start, end) = (edges.partition_point(|e| e.suffix < s), edges.partition_point(|e| e.suffix <= s));
+        &mut self.storage.edges_mut()[range.start + start..range.start + end]
+    }
+
+    // Where the edges from p to s start in storage
+    pub fn link_start(&self, p: NodeId, s: NodeId) -> usize {
+        self.range(p).start + self.edges(p).partition_point(|e| e.suffix < s)
+    }
+
+    // The edges leaving p grouped by suffix
+    pub fn successors(&self, p: NodeId) -> impl Iterator<Item = &[Edge]> {
+        self.edges(p).chunk_by(|a, b| a.suffix == b.suffix)
+    }
+
+    // All edges grouped by the prefix and suffix they link
+    pub fn links(&self) -> impl Iterator<Item = &[Edge]> {
+        self.storage.edges().chunk_by(|a, b| (a.prefix, a.suffix) == (b.prefix, b.suffix))
+    }
+
+    pub fn links_mut(&mut self) -> impl Iterator<Item = &mut [Edge]> {
+        self.storage.edges_mut().chunk_by_mut(|a, b| (a.prefix, a.suffix) == (b.prefix, b.suffix))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Edge> {
+        self.storage.edges().iter()
+    }
+
+    // Number of edges, counting each once however many copies it has
+    pub fn len(&self) -> usize {
+        self.storage.edges().len()
+    }
+
+    // Number of copies of all edges, which is the number of reads the graph was built from
+    pub fn count(&self) -> usize {
+        self.storage.edges().iter().map(|e| e.count as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.edges().is_empty()
+    }
+}
+
+// Counters for a single thread during merging, each on a cache line of its own so threads counting next to each
+// other do not contend for it
+#[derive(Default)]
+#[repr(align(64))]
+pub struct MergeCounters {
+    pub pairs: AtomicUsize,
+    pub overlaps: AtomicUsize,
+    pub bytes: AtomicUsize,
+}
+
+impl MergeCounters {
+    pub(crate) fn record(&self, bytes: usize, overlap: bool) {
+        self.pairs.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        if overlap { self.overlaps.fetch_add(1, Ordering::Relaxed); }
+    }
+
+    pub fn get(&self) -> (usize, usize, usize) {
+        (self.pairs.load(Ordering::Relaxed), self.overlaps.load(Ordering::Relaxed), self.bytes.load(Ordering::Relaxed))
+    }
+}
+
+// Per-thread merge counters for the last call to merge_contigs
+// The last slot is used by threads outside of the rayon pool
+pub struct MergeStats {
+    pub threads: Vec<MergeCounters>,
+}
+
+impl MergeStats {
+    fn new() -> Self {
+        MergeStats { threads: (0..=rayon::current_num_threads()).map(|_| MergeCounters::default()).collect() }
+    }
+
+    pub(crate) fn current(&self) -> &MergeCounters {
+        let slot = rayon::current_thread_index().unwrap_or(self.threads.len() - 1);
+        &self.threads[slot.min(self.threads.len() - 1)]
+    }
+
+    fn reset(&self) {
+        for t in self.threads.iter() {
+            t.pairs.store(0, Ordering::Relaxed);
+            t.overlaps.store(0, Ordering::Relaxed);
+            t.bytes.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Sum of (pairs tested, overlaps found, bytes compared) over all threads
+    pub fn totals(&self) -> (usize, usize, usize) {
+        self.threads.iter().map(|t| t.get()).fold((0, 0, 0), |a, t| (a.0 + t.0, a.1 + t.1, a.2 + t.2))
+    }
+}
+
+// Graph shape statistics, see Assembler::topology
+#[derive(Default, Debug)]
+pub struct Topology {
+    pub nodes: usize,
+    // Distinct prefix/suffix pairs
+    pub links: usize,
+    // Every read is an edge, so this counts duplicates
+    pub edges: usize,
+    pub sources: usize,
+    pub sinks: usize,
+    pub balanced: usize,
+    // Nodes with more than one distinct successor
+    pub branching: usize,
+    pub max_degree: usize,
+    pub components: usize,
+}
+
+// Weakly connected components of the graph, see Assembler::components
+pub struct Components {
+    // The component every node is in
+    pub of: Vec<usize>,
+    // The nodes of each component in id order, largest component first
+    pub nodes: Vec<Vec<NodeId>>,
+}
+
+// The paths, cycles and abandoned walks of a component assembled in a graph of its own, and its contigs
+type Assembled = ([Vec<Vec<NodeId>>; 3], Vec<Contig>);
+
+// Whether the edges of a component or graph can all be taken by a single walk, see Assembler::eulerian
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EulerianWalk {
+    // Every node is balanced, so a cycle takes every edge
+    Circuit,
+    // One node has an extra outgoing edge and one an extra incoming edge, the ends of a path taking every edge
+    Path,
+    Neither,
+}
+
+impl EulerianWalk {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EulerianWalk::Circuit => "circuit",
+            EulerianWalk::Path => "path",
+            EulerianWalk::Neither => "none",
+        }
+    }
+}
+
+// Degree balance of a weakly connected component, see Assembler::eulerian
+#[derive(Default, Debug)]
+pub struct Balance {
+    pub nodes: usize,
+    // Unused edges, counting duplicates
+    pub edges: usize,
+    // Nodes whose indegree and outdegree differ
+    pub unbalanced: usize,
+    // Outgoing edges in excess of incoming ones, summed over the nodes. Every walk that takes them starts at one
+    pub starts: usize,
+}
+
+impl Balance {
+    pub fn walk(&self) -> EulerianWalk {
+        match (self.edges, self.unbalanced, self.starts) {
+            (0, _, _) => EulerianWalk::Neither,
+            (_, 0, _) => EulerianWalk::Circuit,
+            (_, 2, 1) => EulerianWalk::Path,
+            _ => EulerianWalk::Neither,
+        }
+    }
+
+    // The fewest walks that can take every edge of the component
+    pub fn min_walks(&self) -> usize {
+        if self.edges == 0 { 0 } else { self.starts.max(1) }
+    }
+}
+
+// Degree balance of every weakly connected component, in the order of Assembler::components, and whether a single
+// walk takes every edge of the graph
+pub struct Eulerian {
+    pub components: Vec<Balance>,
+    pub walk: EulerianWalk,
+}
+
+impl Eulerian {
+    pub fn unbalanced(&self) -> usize {
+        self.components.iter().map(|b| b.unbalanced).sum()
+    }
+
+    pub fn min_walks(&self) -> usize {
+        self.components.iter().map(|b| b.min_walks()).sum()
+    }
+}
+
+// The two mates of a paired-end read, each as the nodes of its first and last k-mer
+// The second mate is kept as sequenced, so it reads the opposite strand of the first
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MatePair<K = u64> {
+    pub first: (K, K),
+    pub second: (K, K),
+}
+
+// A join between the end of contig left and the start of contig right that was rejected
+pub struct NearMiss {
+    pub left: usize,
+    pub right: usize,
+    pub overlap: usize,
+    pub mismatches: usize,
+    pub required: usize,
+}
+
+// A branch removed by Assembler::pop_bubbles and the branch kept in its place, with their mean node coverage
+pub struct Bubble {
+    pub kept: Vec<u8>,
+    pub popped: Vec<u8>,
+    pub kept_cov: f32,
+    pub popped_cov: f32,
+}
+
+// How a walk picks the successor to take next when more than one is left, see Assembler::traversal
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TraversalStrategy {
+    // The first in node order
+    FirstAvailable,
+    // The one whose link the most input reads support
+    HighestCoverage,
+    // The one whose coverage is closest to that of the node the walk came from
+    CoverageMatch,
+    // The one that starts the longest walk of unused links, looking up to this many steps ahead
+    LongestExtensionLookahead(usize),
+    // One drawn at random, from a generator with this seed
+    Random(u64),
+}
+
+// Deepest lookahead Config takes. Every step looks at up to four successors, so a pick takes up to 4^depth steps
+pub const LOOKAHEAD_MAX_DEPTH: usize = 16;
+
+impl FromStr for TraversalStrategy {
+    type Err = ();
+
+    // first, coverage, match, lookahead[:depth] or random. The seed of random is set by the caller
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.split_once(':') {
+            None if s == "first" => Ok(TraversalStrategy::FirstAvailable),
+            None if s == "coverage" => Ok(TraversalStrategy::HighestCoverage),
+            None if s == "match" => Ok(TraversalStrategy::CoverageMatch),
+            None if s == "lookahead" => Ok(TraversalStrategy::LongestExtensionLookahead(4)),
+            None if s == "random" => Ok(TraversalStrategy::Random(0)),
+            Some(("lookahead", depth)) => match depth.parse() {
+                Ok(depth @ 1..) => Ok(TraversalStrategy::LongestExtensionLookahead(depth)),
+                _ => Err(()),
+            },
+            _ => Err(()),
+        }
     }
 }
 
@@ -255,6 +2183,7 @@ pub enum PathType {
     Cycle,
 }
 
+#[derive(Copy, Clone)]
 pub enum NodeType {
     Prefix,
     Suffix,
@@ -0,0 +1,74 @@
+use crate::utils::{ self, FastaRecord };
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{ self, BufWriter },
+    path::Path,
+};
+
+pub const USAGE: &str = "Usage: sbh seq <command> <in.fasta> [out.fasta]
+    revcomp <in> [out]                      reverse complement every record
+    translate <in> [out]                    translate every record to protein (frame 0)
+    stats <in>...                           record count, total, shortest, longest, N50 and GC content
+    length-filter <min> <max> <in> [out]    keep records with min <= length <= max (0 for no max)";
+
+// Entry point for `sbh seq ...`. Output goes to stdout unless an output file is given
+// File names are taken as they are, so they do not have to be valid UTF-8
+pub fn seq_command(args: &[OsString]) -> Result<(), String> {
+    let command = args.first().map(|a| a.to_string_lossy()).ok_or(USAGE)?;
+    let args = &args[1..];
+    match command.as_ref() {
+        "revcomp" => map_records(args, |r| FastaRecord { name: r.name.clone(), seq: utils::revcomp(&r.seq) }),
+        "translate" => map_records(args, |r| FastaRecord { name: r.name.clone(), seq: utils::translate(&r.seq) }),
+        "stats" => {
+            if args.is_empty() { return Err(USAGE.to_string()); }
+            println!("{:<32}{:>10}{:>14}{:>10}{:>10}{:>10}{:>8}", "file", "records", "total", "min", "max", "n50", "gc%");
+            for fname in args {
+                let records = read(fname)?;
+                let lens: Vec<usize> = records.iter().map(|r| r.seq.len()).collect();
+                let total: usize = lens.iter().sum();
+                let gc = records.iter()
+                    .flat_map(|r| r.seq.iter())
+                    .filter(|c| matches!(c.to_ascii_uppercase(), b'G' | b'C'))
+                    .count();
+                println!("{:<32}{:>10}{:>14}{:>10}{:>10}{:>10}{:>8.2}", Path::new(fname).display(), records.len(), total,
+                    lens.iter().min().copied().unwrap_or(0), lens.iter().max().copied().unwrap_or(0),
+                    utils::n50(&lens), 100.0 * gc as f64 / total.max(1) as f64);
+            }
+            Ok(())
+        }
+        "length-filter" => {
+            if args.len() < 3 { return Err(USAGE.to_string()); }
+            let bound = |s: &OsString| s.to_string_lossy().parse::<usize>()
+                .map_err(|_| format!("Invalid length: '{}'", s.to_string_lossy()));
+            let (min, max) = (bound(&args[0])?, bound(&args[1])?);
+            let max = if max == 0 { usize::MAX } else { max };
+            let records: Vec<FastaRecord> = read(&args[2])?.into_iter()
+                .filter(|r| (min..=max).contains(&r.seq.len()))
+                .collect();
+            write(args.get(3), &records)
+        }
+        _ => Err(format!("Unknown seq command: '{}'.\n{}", command, USAGE)),
+    }
+}
+
+fn map_records(args: &[OsString], f: impl Fn(&FastaRecord) -> FastaRecord) -> Result<(), String> {
+    let fname = args.first().ok_or(USAGE)?;
+    let records: Vec<FastaRecord> = read(fname)?.iter().map(f).collect();
+    write(args.get(1), &records)
+}
+
+fn read(fname: impl AsRef<Path>) -> Result<Vec<FastaRecord>, String> {
+    let fname = fname.as_ref();
+    utils::read_fasta(fname).map_err(|e| format!("Failed to read {}: {}", fname.display(), e))
+}
+
+fn write(outfile: Option<&OsString>, records: &[FastaRecord]) -> Result<(), String> {
+    let result = match outfile {
+        Some(fname) => File::create(fname)
+            .and_then(|f| utils::write_fasta(&mut BufWriter::new(f), records, 60)),
+        None => utils::write_fasta(&mut BufWriter::new(io::stdout().lock()), records, 60),
+    };
+    result.map_err(|e| format!("Failed to write {}: {}", outfile.map(|s| s.to_string_lossy()).unwrap_or("stdout".into()), e))
+}
+
@@ -0,0 +1,498 @@
+// Runtime dispatched versions of the per-base loops: 2-bit packing of k-mers, reverse complement and
+// Hamming distance. The widest instruction set the CPU supports is picked once, SSE/AVX2 on x86_64 and
+// NEON on aarch64, with the scalar loops as fallback. SBH_SIMD=scalar forces the fallback
+use std::sync::OnceLock;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Level {
+    Scalar,
+    Sse,
+    Avx2,
+    Neon,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Scalar => "scalar",
+            Level::Sse => "SSE",
+            Level::Avx2 => "AVX2",
+            Level::Neon => "NEON",
+        }
+    }
+}
+
+pub fn level() -> Level {
+    static LEVEL: OnceLock<Level> = OnceLock::new();
+    *LEVEL.get_or_init(|| {
+        if std::env::var_os("SBH_SIMD").is_some_and(|v| v == "scalar") { return Level::Scalar; }
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") { return Level::Avx2; }
+            if is_x86_feature_detected!("ssse3") { return Level::Sse; }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") { return Level::Neon; }
+        }
+        Level::Scalar
+    })
+}
+
+// Pack up to 32 bases into a u64, the first base in the lowest two bits. None if a base is not ACGT
+// Lowercase bases pack like uppercase ones. The kernels work on 16 bases, so longer k-mers are packed in two halves
+pub fn pack(bases: &[u8]) -> Option<u64> {
+    assert!(bases.len() <= 32, "can only pack up to 32 bases");
+    let (low, high) = bases.split_at(bases.len().min(16));
+    let low = pack16(low)? as u64;
+    if high.is_empty() { return Some(low); }
+    Some(low | (pack16(high)? as u64) << 32)
+}
+
+fn pack16(bases: &[u8]) -> Option<u32> {
+    match level() {
+        // AVX2 gains nothing over SSE for a single 16 byte lane
+        #[cfg(target_arch = "x86_64")]
+        Level::Sse | Level::Avx2 => unsafe { x86::pack_ssse3(bases) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { neon::pack(bases) },
+        _ => scalar::pack(bases),
+    }
+}
+
+// The pack of every window of `len` bases of seq and of its reverse complement, rolled one base at a time instead of
+// packing each window again: the forward code drops its first base from the bottom and takes the new one at the top,
+// the reverse one the other way around. None for windows with anything but uppercase ACGT, which
+// utils::kmerize leaves out as well
+pub fn rolling(seq: &[u8], len: usize) -> impl Iterator<Item = Option<(u64, u64)>> + '_ {
+    assert!((1..=32).contains(&len), "can only roll windows of 1 to 32 bases");
+    let mask = u64::MAX >> (64 - 2 * len);
+    let top = 2 * (len - 1);
+    let (mut fwd, mut rev, mut run) = (0u64, 0u64, 0usize);
+    seq.iter().enumerate().filter_map(move |(i, c)| {
+        let base = match c {
+            b'A' => Some(0),
+            b'C' => Some(1),
+            b'G' => Some(2),
+            b'T' => Some(3),
+            _ => None,
+        };
+        match base {
+            Some(v) => {
+                fwd = fwd >> 2 | v << top;
+                // A base is complemented by flipping both of its bits
+                rev = (rev << 2 | (3 ^ v)) & mask;
+                run += 1;
+            }
+            None => run = 0,
+        }
+        (i + 1 >= len).then_some((run >= len).then_some((fwd, rev)))
+    })
+}
+
+// Inverse of pack. Lengths over 32 repeat A like the scalar loop
+pub fn unpack(idx: u64, length: usize) -> Vec<u8> {
+    if length > 32 { return scalar::unpack(idx, length); }
+    let mut bases = unpack16(idx as u32, length.min(16));
+    if length > 16 { bases.extend(unpack16((idx >> 32) as u32, length - 16)); }
+    bases
+}
+
+fn unpack16(idx: u32, length: usize) -> Vec<u8> {
+    match level() {
+        #[cfg(target_arch = "x86_64")]
+        Level::Sse | Level::Avx2 => unsafe { x86::unpack_ssse3(idx, length) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { neon::unpack(idx, length) },
+        _ => scalar::unpack(idx as u64, length),
+    }
+}
+
+// The pack of `len` bases in reverse order, which sorts like the bases themselves as the first one is now on top
+pub fn reverse_code(code: u64, len: usize) -> u64 {
+    let mut x = code;
+    x = (x >> 2 & 0x3333_3333_3333_3333) | (x & 0x3333_3333_3333_3333) << 2;
+    x = (x >> 4 & 0x0f0f_0f0f_0f0f_0f0f) | (x & 0x0f0f_0f0f_0f0f_0f0f) << 4;
+    x.swap_bytes() >> (64 - 2 * len)
+}
+
+// The pack of the reverse complement of `len` packed bases. A base is complemented by flipping both of its bits
+pub fn revcomp_code(code: u64, len: usize) -> u64 {
+    reverse_code(!code, len)
+}
+
+// Reverse complement of a nucleotide sequence. Anything that is not ACGT (or U) becomes N
+pub fn revcomp(seq: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(seq.len());
+    match level() {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { x86::complement_avx2(seq, &mut out) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Sse => unsafe { x86::complement_sse2(seq, &mut out) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { neon::complement(seq, &mut out) },
+        _ => scalar::complement(seq, &mut out),
+    }
+    out.reverse();
+    out
+}
+
+// Number of differing positions over the shorter of the two sequences
+pub fn hamming(a: &[u8], b: &[u8]) -> usize {
+    match level() {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { x86::hamming_avx2(a, b) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Sse => unsafe { x86::hamming_sse2(a, b) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { neon::hamming(a, b) },
+        _ => scalar::hamming(a, b),
+    }
+}
+
+pub mod scalar {
+    pub fn pack(bases: &[u8]) -> Option<u32> {
+        bases.iter().rev().try_fold(0u32, |idx, c| {
+            let v = match c.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => return None,
+            };
+            Some(idx << 2 | v)
+        })
+    }
+
+    pub fn unpack(mut idx: u64, length: usize) -> Vec<u8> {
+        (0..length).map(|_| {
+            let c = b"ACGT"[(idx & 3) as usize];
+            idx >>= 2;
+            c
+        }).collect()
+    }
+
+    pub fn complement_base(c: u8) -> u8 {
+        match c.to_ascii_uppercase() {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' | b'U' => b'A',
+            _ => b'N',
+        }
+    }
+
+    // Complement in forward order, the caller reverses
+    pub fn complement(seq: &[u8], out: &mut Vec<u8>) {
+        out.extend(seq.iter().map(|&c| complement_base(c)));
+    }
+
+    pub fn hamming(a: &[u8], b: &[u8]) -> usize {
+        a.iter().zip(b.iter()).filter(|(x, y)| x != y).count()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    // Base codes 0-3 are weighted 1, 4, 16 and 64 within each group of four bases, so every group
+    // of four fills one byte of the result
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn pack_ssse3(bases: &[u8]) -> Option<u32> {
+        let mut buf = [b'A'; 16];
+        buf[..bases.len()].copy_from_slice(bases);
+        // Clearing bit 5 uppercases letters and cannot turn anything else into one of ACGT
+        let x = _mm_and_si128(_mm_loadu_si128(buf.as_ptr() as *const __m128i), _mm_set1_epi8(0xDFu8 as i8));
+        let (a, c) = (_mm_cmpeq_epi8(x, _mm_set1_epi8(b'A' as i8)), _mm_cmpeq_epi8(x, _mm_set1_epi8(b'C' as i8)));
+        let (g, t) = (_mm_cmpeq_epi8(x, _mm_set1_epi8(b'G' as i8)), _mm_cmpeq_epi8(x, _mm_set1_epi8(b'T' as i8)));
+        if _mm_movemask_epi8(_mm_or_si128(_mm_or_si128(a, c), _mm_or_si128(g, t))) != 0xFFFF { return None; }
+        let codes = _mm_or_si128(
+            _mm_and_si128(c, _mm_set1_epi8(1)),
+            _mm_or_si128(_mm_and_si128(g, _mm_set1_epi8(2)), _mm_and_si128(t, _mm_set1_epi8(3))),
+        );
+        let pairs = _mm_maddubs_epi16(codes, _mm_set1_epi16(0x0401));
+        let quads = _mm_madd_epi16(pairs, _mm_set1_epi32(0x0010_0001));
+        let words = _mm_packs_epi32(quads, quads);
+        Some(_mm_cvtsi128_si32(_mm_packus_epi16(words, words)) as u32)
+    }
+
+    // Every byte of idx is spread over four lanes, which each keep their own two bits and look up the base
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn unpack_ssse3(idx: u32, length: usize) -> Vec<u8> {
+        let x = _mm_shuffle_epi8(_mm_cvtsi32_si128(idx as i32), _mm_setr_epi8(0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3));
+        let mut codes = _mm_setzero_si128();
+        for (shift, lanes) in [(0, 0x0000_00FFu32), (2, 0x0000_FF00), (4, 0x00FF_0000), (6, 0xFF00_0000)] {
+            let bits = match shift {
+                0 => x,
+                2 => _mm_srli_epi16(x, 2),
+                4 => _mm_srli_epi16(x, 4),
+                _ => _mm_srli_epi16(x, 6),
+            };
+            let mask = _mm_and_si128(_mm_set1_epi32(lanes as i32), _mm_set1_epi8(3));
+            codes = _mm_or_si128(codes, _mm_and_si128(bits, mask));
+        }
+        let bases = _mm_shuffle_epi8(_mm_setr_epi8(b'A' as i8, b'C' as i8, b'G' as i8, b'T' as i8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0), codes);
+        let mut buf = [0u8; 16];
+        _mm_storeu_si128(buf.as_mut_ptr() as *mut __m128i, bases);
+        buf[..length].to_vec()
+    }
+
+    // Select complement where the uppercased byte matches a base, N elsewhere
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn complement_lane(x: __m128i) -> __m128i {
+        let u = _mm_and_si128(x, _mm_set1_epi8(0xDFu8 as i8));
+        let mut r = _mm_set1_epi8(b'N' as i8);
+        for (base, comp) in [(b'A', b'T'), (b'C', b'G'), (b'G', b'C'), (b'T', b'A'), (b'U', b'A')] {
+            let m = _mm_cmpeq_epi8(u, _mm_set1_epi8(base as i8));
+            r = _mm_or_si128(_mm_and_si128(m, _mm_set1_epi8(comp as i8)), _mm_andnot_si128(m, r));
+        }
+        r
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn complement_sse2(seq: &[u8], out: &mut Vec<u8>) {
+        let chunks = seq.chunks_exact(16);
+        let rest = chunks.remainder();
+        for chunk in chunks {
+            let r = complement_lane(_mm_loadu_si128(chunk.as_ptr() as *const __m128i));
+            let mut buf = [0u8; 16];
+            _mm_storeu_si128(buf.as_mut_ptr() as *mut __m128i, r);
+            out.extend_from_slice(&buf);
+        }
+        super::scalar::complement(rest, out);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn complement_avx2(seq: &[u8], out: &mut Vec<u8>) {
+        let chunks = seq.chunks_exact(32);
+        let rest = chunks.remainder();
+        for chunk in chunks {
+            let x = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            let u = _mm256_and_si256(x, _mm256_set1_epi8(0xDFu8 as i8));
+            let mut r = _mm256_set1_epi8(b'N' as i8);
+            for (base, comp) in [(b'A', b'T'), (b'C', b'G'), (b'G', b'C'), (b'T', b'A'), (b'U', b'A')] {
+                let m = _mm256_cmpeq_epi8(u, _mm256_set1_epi8(base as i8));
+                r = _mm256_blendv_epi8(r, _mm256_set1_epi8(comp as i8), m);
+            }
+            let mut buf = [0u8; 32];
+            _mm256_storeu_si256(buf.as_mut_ptr() as *mut __m256i, r);
+            out.extend_from_slice(&buf);
+        }
+        complement_sse2(rest, out);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn hamming_sse2(a: &[u8], b: &[u8]) -> usize {
+        let n = a.len().min(b.len());
+        let (mut i, mut diff) = (0, 0);
+        while i + 16 <= n {
+            let x = _mm_loadu_si128(a.as_ptr().add(i) as *const __m128i);
+            let y = _mm_loadu_si128(b.as_ptr().add(i) as *const __m128i);
+            diff += 16 - (_mm_movemask_epi8(_mm_cmpeq_epi8(x, y)) as u32).count_ones() as usize;
+            i += 16;
+        }
+        diff + super::scalar::hamming(&a[i..n], &b[i..n])
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn hamming_avx2(a: &[u8], b: &[u8]) -> usize {
+        let n = a.len().min(b.len());
+        let (mut i, mut diff) = (0, 0);
+        while i + 32 <= n {
+            let x = _mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i);
+            let y = _mm256_loadu_si256(b.as_ptr().add(i) as *const __m256i);
+            diff += 32 - (_mm256_movemask_epi8(_mm256_cmpeq_epi8(x, y)) as u32).count_ones() as usize;
+            i += 32;
+        }
+        diff + hamming_sse2(&a[i..n], &b[i..n])
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    // Same layout as the x86 version: codes are weighted 1, 4, 16 and 64 and summed pairwise
+    #[target_feature(enable = "neon")]
+    pub unsafe fn pack(bases: &[u8]) -> Option<u32> {
+        let mut buf = [b'A'; 16];
+        buf[..bases.len()].copy_from_slice(bases);
+        let x = vandq_u8(vld1q_u8(buf.as_ptr()), vdupq_n_u8(0xDF));
+        let (a, c) = (vceqq_u8(x, vdupq_n_u8(b'A')), vceqq_u8(x, vdupq_n_u8(b'C')));
+        let (g, t) = (vceqq_u8(x, vdupq_n_u8(b'G')), vceqq_u8(x, vdupq_n_u8(b'T')));
+        if vminvq_u8(vorrq_u8(vorrq_u8(a, c), vorrq_u8(g, t))) == 0 { return None; }
+        let codes = vorrq_u8(
+            vandq_u8(c, vdupq_n_u8(1)),
+            vorrq_u8(vandq_u8(g, vdupq_n_u8(2)), vandq_u8(t, vdupq_n_u8(3))),
+        );
+        const WEIGHTS: [u8; 16] = [1, 4, 16, 64, 1, 4, 16, 64, 1, 4, 16, 64, 1, 4, 16, 64];
+        let weighted = vmulq_u8(codes, vld1q_u8(WEIGHTS.as_ptr()));
+        let quads = vpaddlq_u16(vpaddlq_u8(weighted));
+        let words = vmovn_u32(quads);
+        let bytes = vmovn_u16(vcombine_u16(words, words));
+        Some(vget_lane_u32(vreinterpret_u32_u8(bytes), 0))
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn unpack(idx: u32, length: usize) -> Vec<u8> {
+        const SPREAD: [u8; 16] = [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3];
+        const SHIFTS: [i8; 16] = [0, -2, -4, -6, 0, -2, -4, -6, 0, -2, -4, -6, 0, -2, -4, -6];
+        const BASES: [u8; 16] = [b'A', b'C', b'G', b'T', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let bytes = idx.to_le_bytes();
+        let mut word = [0u8; 16];
+        word[..4].copy_from_slice(&bytes);
+        let x = vqtbl1q_u8(vld1q_u8(word.as_ptr()), vld1q_u8(SPREAD.as_ptr()));
+        let codes = vandq_u8(vshlq_u8(x, vld1q_s8(SHIFTS.as_ptr())), vdupq_n_u8(3));
+        let mut buf = [0u8; 16];
+        vst1q_u8(buf.as_mut_ptr(), vqtbl1q_u8(vld1q_u8(BASES.as_ptr()), codes));
+        buf[..length].to_vec()
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn complement(seq: &[u8], out: &mut Vec<u8>) {
+        let chunks = seq.chunks_exact(16);
+        let rest = chunks.remainder();
+        for chunk in chunks {
+            let u = vandq_u8(vld1q_u8(chunk.as_ptr()), vdupq_n_u8(0xDF));
+            let mut r = vdupq_n_u8(b'N');
+            for (base, comp) in [(b'A', b'T'), (b'C', b'G'), (b'G', b'C'), (b'T', b'A'), (b'U', b'A')] {
+                r = vbslq_u8(vceqq_u8(u, vdupq_n_u8(base)), vdupq_n_u8(comp), r);
+            }
+            let mut buf = [0u8; 16];
+            vst1q_u8(buf.as_mut_ptr(), r);
+            out.extend_from_slice(&buf);
+        }
+        super::scalar::complement(rest, out);
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn hamming(a: &[u8], b: &[u8]) -> usize {
+        let n = a.len().min(b.len());
+        let (mut i, mut diff) = (0, 0);
+        while i + 16 <= n {
+            let eq = vceqq_u8(vld1q_u8(a.as_ptr().add(i)), vld1q_u8(b.as_ptr().add(i)));
+            diff += 16 - vaddvq_u8(vshrq_n_u8(eq, 7)) as usize;
+            i += 16;
+        }
+        diff + super::scalar::hamming(&a[i..n], &b[i..n])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scalar;
+    use crate::utils::SplitMix64;
+
+    // Mostly bases in both cases, with the odd U, N and other byte, of every length up to `max`
+    fn seqs(max: usize) -> Vec<Vec<u8>> {
+        let mut rng = SplitMix64::new(1);
+        const BYTES: &[u8] = b"ACGTACGTACGTacgtacgtUuNn-.\0\xFF\xC1";
+        let mut seqs = vec![];
+        for len in 0..=max {
+            seqs.push(b"ACGTacgt".iter().cycle().take(len).copied().collect());
+            for _ in 0..50 {
+                seqs.push((0..len).map(|_| BYTES[(rng.next_u64() as usize) % BYTES.len()]).collect());
+            }
+        }
+        seqs
+    }
+
+    fn complement(seq: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        scalar::complement(seq, &mut out);
+        out
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn x86_kernels_match_scalar() {
+        use super::x86;
+        if is_x86_feature_detected!("ssse3") {
+            for seq in seqs(16) {
+                assert_eq!(unsafe { x86::pack_ssse3(&seq) }, scalar::pack(&seq), "{:?}", seq);
+            }
+            let mut rng = SplitMix64::new(2);
+            for length in 0..=16 {
+                let idx = rng.next_u64() as u32;
+                assert_eq!(unsafe { x86::unpack_ssse3(idx, length) }, scalar::unpack(idx as u64, length));
+            }
+        }
+        let all = seqs(80);
+        for (seq, other) in all.iter().zip(all.iter().rev()) {
+            let mut out = vec![];
+            unsafe { x86::complement_sse2(seq, &mut out) };
+            assert_eq!(out, complement(seq));
+            assert_eq!(unsafe { x86::hamming_sse2(seq, other) }, scalar::hamming(seq, other));
+            if is_x86_feature_detected!("avx2") {
+                let mut out = vec![];
+                unsafe { x86::complement_avx2(seq, &mut out) };
+                assert_eq!(out, complement(seq));
+                assert_eq!(unsafe { x86::hamming_avx2(seq, other) }, scalar::hamming(seq, other));
+            }
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn neon_kernels_match_scalar() {
+        use super::neon;
+        if !std::arch::is_aarch64_feature_detected!("neon") { return; }
+        for seq in seqs(16) {
+            assert_eq!(unsafe { neon::pack(&seq) }, scalar::pack(&seq), "{:?}", seq);
+        }
+        let mut rng = SplitMix64::new(2);
+        for length in 0..=16 {
+            let idx = rng.next_u64() as u32;
+            assert_eq!(unsafe { neon::unpack(idx, length) }, scalar::unpack(idx as u64, length));
+        }
+        let all = seqs(80);
+        for (seq, other) in all.iter().zip(all.iter().rev()) {
+            let mut out = vec![];
+            unsafe { neon::complement(seq, &mut out) };
+            assert_eq!(out, complement(seq));
+            assert_eq!(unsafe { neon::hamming(seq, other) }, scalar::hamming(seq, other));
+        }
+    }
+
+    #[test]
+    fn rolling_matches_packing_every_window() {
+        let mut rng = SplitMix64::new(3);
+        // Bases with runs of Ns of up to 4 every so often
+        let mut seq = vec![];
+        while seq.len() < 400 {
+            match rng.next_u64() % 12 {
+                0 => seq.extend(std::iter::repeat_n(b'N', 1 + (rng.next_u64() % 4) as usize)),
+                r => seq.push(b"ACGT"[(r % 4) as usize]),
+            }
+        }
+        for len in 1..=32 {
+            let rolled: Vec<Option<(u64, u64)>> = super::rolling(&seq, len).collect();
+            assert_eq!(rolled.len(), seq.len() - len + 1);
+            for (window, codes) in seq.windows(len).zip(rolled) {
+                let expected = super::pack(window).map(|fwd| (fwd, super::pack(&super::revcomp(window)).expect("a base")));
+                assert_eq!(codes, expected, "{} bp window {:?}", len, std::str::from_utf8(window));
+            }
+        }
+    }
+
+    // What the dispatched functions give, whichever level the CPU has
+    #[test]
+    fn dispatch_matches_scalar() {
+        let all = seqs(40);
+        for (seq, other) in all.iter().zip(all.iter().rev()) {
+            let packed = (seq.len() <= 32).then(|| super::pack(seq));
+            if let Some(packed) = packed {
+                let (low, high) = seq.split_at(seq.len().min(16));
+                let expected = scalar::pack(low).and_then(|low| Some(low as u64 | (scalar::pack(high)? as u64) << 32));
+                assert_eq!(packed, expected);
+                if let Some(code) = packed { assert_eq!(super::unpack(code, seq.len()), seq.to_ascii_uppercase()); }
+            }
+            let mut expected = complement(seq);
+            expected.reverse();
+            assert_eq!(super::revcomp(seq), expected);
+            assert_eq!(super::hamming(seq, other), scalar::hamming(seq, other));
+        }
+    }
+}
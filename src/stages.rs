@@ -0,0 +1,133 @@
+use crate::{ config::Config, sbh_assembler::{ Assembler, PathType }, utils };
+use std::collections::HashMap;
+
+// A step of the assembly that can be run by name with --stages
+// Closures taking the assembler and the config implement it, so most stages never need a type of their own
+pub trait AssemblyStage {
+    fn run(&self, ass: &mut Assembler, config: &Config) -> Result<(), String>;
+}
+
+impl<F: Fn(&mut Assembler, &Config) -> Result<(), String>> AssemblyStage for F {
+    fn run(&self, ass: &mut Assembler, config: &Config) -> Result<(), String> {
+        self(ass, config)
+    }
+}
+
+// Named stages that --stages can refer to
+// A custom binary can register its own stages next to the built-in ones and run the same pipeline
+pub struct StageRegistry {
+    stages: HashMap<String, Box<dyn AssemblyStage>>,
+}
+
+impl StageRegistry {
+    pub fn new() -> Self {
+        StageRegistry { stages: HashMap::new() }
+    }
+
+    // A registry with the stages of the default pipeline, in the order it runs them:
+    // min-edge-coverage, clip-tips, pop-bubbles, clean, paths, cycles, contigs, condense. condense alternates
+    // remove-contained and merge, or string-graph with --string-graph, until the contig count stops changing
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register("clean", |ass: &mut Assembler, config: &Config| {
+            ass.iterative_clean(config.clean_iterations.max(1), config.clean_decay, config.clean_min_weight).map_err(|e| e.to_string())?;
+            Ok(())
+        });
+        // Links of fewer than 2 reads unless --min-edge-coverage says otherwise
+        registry.register("min-edge-coverage", |ass: &mut Assembler, config: &Config| {
+            ass.remove_weak_links(config.min_edge_coverage.unwrap_or(2)).map_err(|e| e.to_string())?;
+            Ok(())
+        });
+        // Tips up to 2k bp unless --clip-tips says otherwise
+        registry.register("clip-tips", |ass: &mut Assembler, config: &Config| {
+            let max_len = config.clip_tips.unwrap_or(2 * ass.k);
+            ass.clip_tips(max_len, config.tip_max_cov.unwrap_or(f32::INFINITY)).map_err(|e| e.to_string())?;
+            Ok(())
+        });
+        // Branches up to 2k bp unless --pop-bubbles says otherwise, listed in --bubble-report if given
+        registry.register("pop-bubbles", |ass: &mut Assembler, config: &Config| {
+            let bubbles = ass.pop_bubbles(config.pop_bubbles.unwrap_or(2 * ass.k)).map_err(|e| e.to_string())?;
+            if let Some(report) = &config.bubble_report {
+                utils::bubbles2file(report, &bubbles).map_err(|e| format!("Failed to write {}: {}", report.display(), e))?;
+            }
+            Ok(())
+        });
+        registry.register("paths", |ass: &mut Assembler, _: &Config| {
+            ass.populate_paths_or_cycles(PathType::Path);
+            Ok(())
+        });
+        registry.register("cycles", |ass: &mut Assembler, _: &Config| {
+            ass.populate_paths_or_cycles(PathType::Cycle);
+            Ok(())
+        });
+        registry.register("contigs", |ass: &mut Assembler, _: &Config| {
+            ass.paths_cycles_to_contigs();
+            Ok(())
+        });
+        // paths, cycles and contigs one component at a time
+        registry.register("components", |ass: &mut Assembler, _: &Config| {
+            for ids in ass.components().nodes {
+                if ass.is_cancelled() { break; }
+                ass.assemble_component(&ids);
+            }
+            Ok(())
+        });
+        // components, each condensed on its own, all at once
+        registry.register("parallel-components", |ass: &mut Assembler, config: &Config| {
+            let components = ass.components();
+            ass.assemble_components_par(&components, |sub| sub.condense(config.min_overlap, config.min_overlap_frac, config.string_graph));
+            Ok(())
+        });
+        registry.register("remove-contained", |ass: &mut Assembler, _: &Config| {
+            ass.remove_contained_contigs();
+            Ok(())
+        });
+        registry.register("merge", |ass: &mut Assembler, config: &Config| {
+            ass.merge_contigs(config.min_overlap, config.min_overlap_frac);
+            Ok(())
+        });
+        registry.register("string-graph", |ass: &mut Assembler, config: &Config| {
+            ass.merge_string_graph(config.min_overlap, config.min_overlap_frac);
+            Ok(())
+        });
+        registry.register("condense", |ass: &mut Assembler, config: &Config| {
+            ass.condense(config.min_overlap, config.min_overlap_frac, config.string_graph);
+            Ok(())
+        });
+        registry
+    }
+
+    // Add a stage, replacing any stage with the same name
+    pub fn register(&mut self, name: &str, stage: impl AssemblyStage + 'static) {
+        self.stages.insert(name.to_string(), Box::new(stage));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn AssemblyStage> {
+        self.stages.get(name).map(|s| s.as_ref())
+    }
+
+    // Registered stage names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.stages.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // Run the named stages in order. Every name is checked before anything runs
+    pub fn run(&self, names: &[String], ass: &mut Assembler, config: &Config) -> Result<(), String> {
+        if let Some(unknown) = names.iter().find(|n| !self.stages.contains_key(n.as_str())) {
+            return Err(format!("Unknown stage: '{}'. Available stages: {}.", unknown, self.names().join(", ")));
+        }
+        for name in names {
+            if ass.is_cancelled() { break; }
+            self.stages[name].run(ass, config).map_err(|e| format!("Stage '{}' failed: {}", name, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for StageRegistry {
+    fn default() -> Self {
+        Self::with_builtins()
+    }
+}
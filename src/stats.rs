@@ -0,0 +1,60 @@
+use crate::{ build_info, contig::Contig, kmers::{ self, KmerCounts, Qv }, utils };
+use serde_json::{ json, Value };
+use std::{ fs::File, io::{ BufWriter, Write }, path::Path };
+
+// Largest k-mer size for the QC metrics, long enough to be mostly unique. Smaller nodes use a smaller one, see qc_k
+pub const QC_K: usize = 21;
+
+// QC k-mer size for nodes of length k, so that the longest reads still contain a QC k-mer
+pub fn qc_k(k: usize) -> usize {
+    QC_K.min(2 * k)
+}
+
+// Read k-mers seen fewer times than this are treated as sequencing errors when measuring completeness
+pub const SOLID_MIN: u32 = 2;
+
+// Write the assembly summary and QC metrics as JSON. Contig names match the ones written by cont2file
+// Returns the assembly QV, None if no error k-mer was found, and the completeness
+pub fn write_stats_json(fname: &Path, contigs: &[Contig], reads: &KmerCounts) -> std::io::Result<(Option<f64>, f64)> {
+    let lens: Vec<usize> = contigs.iter().map(|c| c.len()).collect();
+    let qvs: Vec<Qv> = contigs.iter().map(|c| Qv::of(&c.seq, reads)).collect();
+    let total = Qv { kmers: qvs.iter().map(|q| q.kmers).sum(), errors: qvs.iter().map(|q| q.errors).sum() };
+    let (solid, found) = kmers::completeness(reads, contigs.iter().map(|c| c.seq.as_slice()), SOLID_MIN);
+    let completeness = if solid == 0 { 0.0 } else { found as f64 / solid as f64 };
+    let per_contig: Vec<Value> = contigs.iter().zip(qvs.iter()).enumerate().map(|(i, (c, qv))| json!({
+        "name": format!("sequence{}", i + 1),
+        "length": c.len(),
+        "coverage": c.coverage,
+        "edge_coverage": c.edge_coverage,
+        "min_edge_coverage": c.min_edge_coverage,
+        "kmers": qv.kmers,
+        "error_kmers": qv.errors,
+        "qv": qv.qv(reads.k),
+    })).collect();
+    let stats = json!({
+        "contigs": lens.len(),
+        "total": lens.iter().sum::<usize>(),
+        "longest": lens.iter().max().copied().unwrap_or(0),
+        "n50": utils::n50(&lens),
+        "qv": {
+            "k": reads.k,
+            "kmers": total.kmers,
+            "error_kmers": total.errors,
+            "assembly": total.qv(reads.k),
+        },
+        "completeness": {
+            "solid_min": SOLID_MIN,
+            "solid_kmers": solid,
+            "found": found,
+            "fraction": completeness,
+            "missing_contig_kmers_fraction": if total.kmers == 0 { 0.0 } else { total.errors as f64 / total.kmers as f64 },
+        },
+        "per_contig": per_contig,
+        "build": build_info::to_json(),
+    });
+    let mut writer = BufWriter::new(File::create(fname)?);
+    serde_json::to_writer_pretty(&mut writer, &stats)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok((total.qv(reads.k), completeness))
+}
@@ -0,0 +1,244 @@
+// The overlap graph of the contigs for --string-graph. Every contig is a vertex, or two once the strands are joined,
+// one for each strand, and an edge joins two vertices when a suffix of one is a prefix of the other. Myers'
+// transitive reduction drops every edge that two shorter ones imply, which leaves a graph whose unbranched chains
+// are the merges, instead of joining pairs greedily in the order they are found
+use crate::{ contig::Contig, sbh_assembler::{ Assembler, MergeStats } };
+use rayon::prelude::*;
+
+// Vertex 2i is contig i as it is and 2i + 1 its reverse complement
+fn vertex(contig: usize, reverse: bool) -> usize {
+    2 * contig + usize::from(reverse)
+}
+
+// Whether the vertex is the reverse complement of its contig
+fn is_reverse(v: usize) -> bool {
+    v % 2 == 1
+}
+
+// The same contig read from the other strand
+fn twin(v: usize) -> usize {
+    v ^ 1
+}
+
+// A suffix of `from` that is a prefix of `to`, `len` bases long
+#[derive(Copy, Clone, Debug)]
+pub struct Overlap {
+    pub from: usize,
+    pub to: usize,
+    pub len: usize,
+}
+
+pub struct StringGraph {
+    // The sequence of every vertex, empty for reverse vertices without --canonical
+    seqs: Vec<Vec<u8>>,
+    // The overlaps leaving each vertex, shortest overhang first
+    out: Vec<Vec<Overlap>>,
+    pub overlaps: usize,
+    // Edges dropped by reduce
+    pub transitive: usize,
+}
+
+impl StringGraph {
+    // Find the longest overlap of at least the required length between every two contigs, in both orders and, once
+    // the strands are joined, on both strands. Pairs tested are counted in `stats` as merge_contigs counts them
+    pub fn new(contigs: &[Contig], canonical: bool, min_overlap: usize, min_overlap_frac: f32, stats: &MergeStats) -> Self {
+        let seqs: Vec<Vec<u8>> = contigs.iter().flat_map(|c| {
+            [c.seq.clone(), if canonical { c.revcomp().seq } else { vec![] }]
+        }).collect();
+        let strands: &[bool] = if canonical { &[false, true] } else { &[false] };
+        let found: Vec<Overlap> = (0..contigs.len()).into_par_iter().flat_map_iter(|i| {
+            let seqs = &seqs;
+            (i + 1..contigs.len()).flat_map(move |j| {
+                let required = Assembler::<u64>::required_overlap(&contigs[i].seq, &contigs[j].seq, min_overlap, min_overlap_frac);
+                let mut pairs = vec![];
+                for &a in strands {
+                    for &b in strands {
+                        pairs.push((vertex(i, a), vertex(j, b)));
+                        // On one strand j before i has to be looked for too. With both, it is the twin of i' before j'
+                        if !canonical { pairs.push((vertex(j, b), vertex(i, a))); }
+                    }
+                }
+                pairs.into_iter().filter_map(move |(from, to)| {
+                    let len = longest_overlap(&seqs[from], &seqs[to], required, stats);
+                    len.map(|len| Overlap { from, to, len })
+                })
+            })
+        }).collect();
+        let mut out = vec![vec![]; seqs.len()];
+        for overlap in found {
+            if canonical { out[twin(overlap.to)].push(Overlap { from: twin(overlap.to), to: twin(overlap.from), len: overlap.len }); }
+            out[overlap.from].push(overlap);
+        }
+        for edges in out.iter_mut() {
+            edges.sort_unstable_by_key(|o| (seqs[o.to].len() - o.len, o.to));
+        }
+        let overlaps = out.iter().map(|edges| edges.len()).sum();
+        StringGraph { seqs, out, overlaps, transitive: 0 }
+    }
+
+    // Bases of `to` past the end of `from`
+    fn overhang(&self, o: &Overlap) -> usize {
+        self.seqs[o.to].len() - o.len
+    }
+
+    // Myers' transitive reduction (2005) with exact overlaps: v -> x is dropped when v -> w -> x reaches x with
+    // no more overhang than the longest edge out of v. Twins are dropped together, as the graph is symmetric
+    pub fn reduce(&mut self) -> usize {
+        #[derive(Copy, Clone, PartialEq)]
+        enum Mark { Vacant, InPlay, Eliminated }
+        let mut mark = vec![Mark::Vacant; self.seqs.len()];
+        let reduced: Vec<Vec<usize>> = (0..self.seqs.len()).map(|v| {
+            let Some(longest) = self.out[v].iter().map(|o| self.overhang(o)).max() else { return vec![] };
+            for o in &self.out[v] { mark[o.to] = Mark::InPlay; }
+            for o in &self.out[v] {
+                if mark[o.to] != Mark::InPlay { continue; }
+                for next in &self.out[o.to] {
+                    if self.overhang(o) + self.overhang(next) > longest { break; }
+                    if mark[next.to] == Mark::InPlay { mark[next.to] = Mark::Eliminated; }
+                }
+            }
+            let reduced = self.out[v].iter().filter(|o| mark[o.to] == Mark::Eliminated).map(|o| o.to).collect();
+            for o in &self.out[v] { mark[o.to] = Mark::Vacant; }
+            reduced
+        }).collect();
+        let mut dropped = 0;
+        for (edges, reduced) in self.out.iter_mut().zip(reduced) {
+            let before = edges.len();
+            edges.retain(|o| !reduced.contains(&o.to));
+            dropped += before - edges.len();
+        }
+        self.transitive += dropped;
+        dropped
+    }
+
+    // Merge every chain of vertices where one has a single edge out and it goes to a vertex with a single edge in.
+    // Returns the contigs and how many of them went into chains of two or more
+    pub fn merge_chains(&self, contigs: &[Contig], canonical: bool) -> (Vec<Contig>, usize) {
+        let mut indeg = vec![0; self.seqs.len()];
+        let mut pred = vec![usize::MAX; self.seqs.len()];
+        for o in self.out.iter().flatten() {
+            indeg[o.to] += 1;
+            pred[o.to] = o.from;
+        }
+        let joined = |v: usize| (self.out[v].len() == 1 && indeg[self.out[v][0].to] == 1).then(|| self.out[v][0]);
+        let oriented = |v: usize| if is_reverse(v) { contigs[v / 2].revcomp() } else { contigs[v / 2].clone() };
+        let vertices: Vec<usize> = (0..self.seqs.len()).filter(|&v| canonical || !is_reverse(v)).collect();
+        // Chains start where nothing is joined to them. What is left after them are cycles, opened anywhere
+        let starts = vertices.iter().filter(|&&v| indeg[v] != 1 || joined(pred[v]).is_none()).chain(vertices.iter());
+        let mut used = vec![false; contigs.len()];
+        let mut result = vec![];
+        let mut merged = 0;
+        for &start in starts {
+            if used[start / 2] { continue; }
+            used[start / 2] = true;
+            let mut contig = oriented(start);
+            let (mut current, mut chain) = (start, 1);
+            while let Some(o) = joined(current) {
+                if used[o.to / 2] { break; }
+                used[o.to / 2] = true;
+                contig = Contig::merge(&contig, &oriented(o.to), o.len);
+                current = o.to;
+                chain += 1;
+            }
+            if chain > 1 { merged += chain; }
+            result.push(contig);
+        }
+        (result, merged)
+    }
+}
+
+// Longest suffix of `from` that is a prefix of `to`, at least `required` long
+fn longest_overlap(from: &[u8], to: &[u8], required: usize, stats: &MergeStats) -> Option<usize> {
+    let mut bytes = 0;
+    let mut found = None;
+    for len in (required.max(1)..=from.len().min(to.len())).rev() {
+        bytes += len;
+        if from[from.len() - len..] == to[..len] {
+            found = Some(len);
+            break;
+        }
+    }
+    stats.current().record(bytes, found.is_some());
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ vertex, StringGraph };
+    use crate::{ contig::{ Contig, Source }, sbh_assembler::{ Assembler, Graph, Nodes }, utils::{ self, SplitMix64 } };
+
+    fn random(len: usize, seed: u64) -> Vec<u8> {
+        let mut rng = SplitMix64::new(seed);
+        (0..len).map(|_| b"ACGT"[(rng.next_u64() % 4) as usize]).collect()
+    }
+
+    // Merge contigs with the given sequences, overlapping by 10 bp or more, and return the graph after reduce
+    fn merge(seqs: Vec<Vec<u8>>, canonical: bool) -> (Assembler, StringGraph, usize) {
+        let mut ass: Assembler = Assembler::from_graph(Graph::new(vec![], 0), Nodes::default(), 15);
+        ass.canonical = canonical;
+        ass.contigs = seqs.into_iter().enumerate()
+            .map(|(i, seq)| Contig::new(seq, 0.0, false, Source::Path(i), Default::default()))
+            .collect();
+        let (graph, merged) = ass.merge_string_graph(10, 0.0);
+        (ass, graph, merged)
+    }
+
+    fn targets(graph: &StringGraph, v: usize) -> Vec<usize> {
+        graph.out[v].iter().map(|o| o.to).collect()
+    }
+
+    #[test]
+    fn transitive_edge_is_dropped() {
+        let s = random(60, 1);
+        let (ass, graph, merged) = merge(vec![s[..40].to_vec(), s[10..50].to_vec(), s[20..60].to_vec()], false);
+        // A -> B, B -> C and A -> C, of which only the last is implied by the others
+        assert_eq!(graph.overlaps, 3);
+        assert_eq!(graph.transitive, 1);
+        assert_eq!(targets(&graph, vertex(0, false)), vec![vertex(1, false)]);
+        assert_eq!(targets(&graph, vertex(1, false)), vec![vertex(2, false)]);
+        assert_eq!(merged, 3);
+        assert_eq!(ass.contigs.len(), 1);
+        assert_eq!(ass.contigs[0].seq, s);
+    }
+
+    #[test]
+    fn branch_is_not_merged() {
+        let s = random(60, 2);
+        let other = [&s[20..40], &random(20, 3)[..]].concat();
+        let seqs = vec![s[..40].to_vec(), s[20..60].to_vec(), other];
+        let (ass, graph, merged) = merge(seqs.clone(), false);
+        assert_eq!(graph.transitive, 0);
+        assert_eq!(targets(&graph, vertex(0, false)).len(), 2);
+        assert_eq!(merged, 0);
+        assert_eq!(ass.contigs.iter().map(|c| c.seq.clone()).collect::<Vec<_>>(), seqs);
+    }
+
+    #[test]
+    fn cycle_is_merged_once() {
+        // Three contigs around a circular 100 bp genome, each overlapping the next by 10 bp
+        let c = random(100, 4);
+        let seqs = vec![c[..40].to_vec(), c[30..70].to_vec(), [&c[60..], &c[..10]].concat()];
+        let (ass, graph, merged) = merge(seqs, false);
+        assert_eq!(graph.overlaps, 3);
+        assert_eq!(graph.transitive, 0);
+        assert_eq!(merged, 3);
+        assert_eq!(ass.contigs.len(), 1);
+        assert_eq!(ass.contigs[0].seq.len(), 110);
+        // Opened anywhere, it spells the genome around from some point
+        let doubled = [&c[..], &c[..]].concat();
+        assert!(doubled.windows(100).any(|w| w == &ass.contigs[0].seq[..100]));
+    }
+
+    #[test]
+    fn twins_are_merged_once() {
+        let s = random(60, 5);
+        let (ass, graph, merged) = merge(vec![s[..40].to_vec(), utils::revcomp(&s[20..60])], true);
+        // A -> B' and its twin B -> A'
+        assert_eq!(graph.overlaps, 2);
+        assert_eq!(targets(&graph, vertex(0, false)), vec![vertex(1, true)]);
+        assert_eq!(targets(&graph, vertex(1, false)), vec![vertex(0, true)]);
+        assert_eq!(merged, 2);
+        assert_eq!(ass.contigs.len(), 1);
+        assert!(ass.contigs[0].seq == s || ass.contigs[0].seq == utils::revcomp(&s));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::contig::Contig;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{ BufRead, BufReader, BufWriter, Error, ErrorKind, Write },
+    path::Path,
+};
+
+// A user supplied database mapping k-mers to taxon labels
+// The database is a TSV with one `kmer<TAB>taxon` pair per line. Lines starting with '#' are ignored
+pub struct KmerTaxonomy {
+    pub k: usize,
+    pub taxa: Vec<String>,
+    kmers: HashMap<Vec<u8>, usize>,
+}
+
+// The majority taxon of a single contig
+pub struct Classification {
+    pub taxon: Option<usize>,
+    pub hits: usize,
+    pub kmers: usize,
+}
+
+impl KmerTaxonomy {
+    // Load a k-mer database from a TSV file. All k-mers must have the same length
+    pub fn load(fname: &Path) -> std::io::Result<Self> {
+        let reader = BufReader::new(File::open(fname)?);
+        let mut k = 0;
+        let mut taxa: Vec<String> = vec![];
+        let mut taxon_ids: HashMap<String, usize> = HashMap::new();
+        let mut kmers: HashMap<Vec<u8>, usize> = HashMap::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let (kmer, taxon) = match line.split_once('\t') {
+                Some((kmer, taxon)) => (kmer.trim().to_ascii_uppercase(), taxon.trim()),
+                None => return Err(Error::new(ErrorKind::InvalidData,
+                    format!("{}:{}: expected 'kmer<TAB>taxon'", fname.display(), i + 1))),
+            };
+            if k == 0 { k = kmer.len(); }
+            if kmer.len() != k || k == 0 {
+                return Err(Error::new(ErrorKind::InvalidData,
+                    format!("{}:{}: k-mer length {} does not match {}", fname.display(), i + 1, kmer.len(), k)));
+            }
+            let id = *taxon_ids.entry(taxon.to_string()).or_insert_with(|| {
+                taxa.push(taxon.to_string());
+                taxa.len() - 1
+            });
+            kmers.insert(kmer.into_bytes(), id);
+        }
+        Ok(KmerTaxonomy { k, taxa, kmers })
+    }
+
+    // Assign the taxon that the most k-mers of the contig hit
+    pub fn classify(&self, contig: &[u8]) -> Classification {
+        let mut counts = vec![0; self.taxa.len()];
+        let mut total = 0;
+        if self.k > 0 && contig.len() >= self.k {
+            for kmer in contig.windows(self.k) {
+                total += 1;
+                if let Some(&id) = self.kmers.get(kmer) { counts[id] += 1; }
+            }
+        }
+        let best = counts.iter().enumerate()
+            .filter(|(_, &c)| c > 0)
+            .max_by_key(|(_, &c)| c);
+        Classification {
+            taxon: best.map(|(id, _)| id),
+            hits: best.map(|(_, &c)| c).unwrap_or(0),
+            kmers: total,
+        }
+    }
+
+    pub fn name(&self, taxon: Option<usize>) -> &str {
+        match taxon {
+            Some(id) => &self.taxa[id],
+            None => "unclassified",
+        }
+    }
+
+    // Classify every contig and write a per-contig TSV report
+    // Returns the composition as (taxon, contig count, total nucleotides) sorted by nucleotides
+    pub fn annotate(&self, fname: &Path, contigs: &[Contig]) -> std::io::Result<Vec<(String, usize, usize)>> {
+        let mut writer = BufWriter::new(File::create(fname)?);
+        writeln!(writer, "contig\tlength\ttaxon\tkmer_hits\tkmers")?;
+        let mut composition: HashMap<Option<usize>, (usize, usize)> = HashMap::new();
+        for (i, contig) in contigs.iter().enumerate() {
+            let class = self.classify(&contig.seq);
+            writeln!(writer, "sequence{}\t{}\t{}\t{}\t{}",
+                i + 1, contig.len(), self.name(class.taxon), class.hits, class.kmers)?;
+            let entry = composition.entry(class.taxon).or_default();
+            entry.0 += 1;
+            entry.1 += contig.len();
+        }
+        let mut composition: Vec<(String, usize, usize)> = composition.into_iter()
+            .map(|(taxon, (n, len))| (self.name(taxon).to_string(), n, len))
+            .collect();
+        composition.sort_unstable_by_key(|c| std::cmp::Reverse(c.2));
+        Ok(composition)
+    }
+}
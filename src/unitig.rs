@@ -0,0 +1,125 @@
+// Unitigs for --unitig-gfa and the preview: maximal chains of nodes where each one has a single way out, into a node
+// with a single way in. A chain spells one sequence whatever walk goes through it, so it can be written as one
+// segment, which takes a bacterial graph from millions of k-mer segments to a few thousand
+use crate::{ kmer::KmerRepr, sbh_assembler::{ Assembler, NodeId } };
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{ self, BufWriter, Write },
+    path::Path,
+};
+
+pub struct Unitig {
+    // The nodes of the chain in order
+    pub nodes: Vec<NodeId>,
+    pub seq: Vec<u8>,
+    // Mean coverage of the nodes
+    pub cov: f32,
+}
+
+// The edges with one span from the last node of a unitig to the first node of the next
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+    pub span: usize,
+    pub count: u32,
+    pub reads: usize,
+}
+
+pub struct Unitigs {
+    pub unitigs: Vec<Unitig>,
+    pub links: Vec<Link>,
+    // The unitig every node is in
+    pub of: Vec<usize>,
+    // Once the strands are joined, the earlier unitig each one is the reverse complement of
+    pub twin: Vec<Option<usize>>,
+}
+
+impl Unitigs {
+    // Number of unitigs, counting one and its reverse complement once
+    pub fn segments(&self) -> usize {
+        self.twin.iter().filter(|twin| twin.is_none()).count()
+    }
+}
+
+// Split the graph into unitigs. Links are followed by distinct successor, so copies of a read do not break a chain,
+// but a link whose edges have different spans does, as its overlap is not one length
+pub fn compact<K: KmerRepr>(ass: &Assembler<K>) -> Unitigs {
+    let n = ass.nodes.len();
+    let mut preds = vec![0usize; n];
+    for edges in ass.graph.links() {
+        preds[edges[0].suffix.index()] += 1;
+    }
+    // The node each node is joined to and the span of the edges between them
+    let next: Vec<Option<(NodeId, usize)>> = (0..n).map(|i| {
+        let mut successors = ass.graph.successors(NodeId(i as u32));
+        let edges = successors.next()?;
+        let s = edges[0].suffix;
+        let one_span = edges.iter().all(|e| e.span == edges[0].span);
+        (successors.next().is_none() && s.index() != i && preds[s.index()] == 1 && one_span).then_some((s, edges[0].span))
+    }).collect();
+    // Whether some node is joined to each node
+    let mut joined = vec![false; n];
+    for (s, _) in next.iter().flatten() { joined[s.index()] = true; }
+    let mut unitigs = Unitigs { unitigs: vec![], links: vec![], of: vec![usize::MAX; n], twin: vec![] };
+    // Chains start at nodes nothing is joined to. What is left after them are cycles of joined nodes
+    let starts = (0..n).filter(|&i| !joined[i]).chain((0..n).filter(|&i| joined[i]));
+    for start in starts {
+        if unitigs.of[start] != usize::MAX { continue; }
+        let id = unitigs.unitigs.len();
+        let mut current = NodeId(start as u32);
+        let mut unitig = Unitig { nodes: vec![current], seq: ass.nodes[current].idx.unpack(ass.k), cov: 0.0 };
+        unitigs.of[start] = id;
+        while let Some((s, span)) = next[current.index()] {
+            if unitigs.of[s.index()] != usize::MAX { break; }
+            unitigs.of[s.index()] = id;
+            unitig.seq.extend_from_slice(&ass.nodes[s].idx.unpack(ass.k)[2 * ass.k - span..]);
+            unitig.nodes.push(s);
+            current = s;
+        }
+        unitig.cov = unitig.nodes.iter().map(|&id| ass.nodes[id].cov).sum::<usize>() as f32 / unitig.nodes.len() as f32;
+        unitigs.unitigs.push(unitig);
+    }
+    unitigs.twin = unitigs.unitigs.iter().enumerate().map(|(u, unitig)| {
+        if !ass.canonical { return None; }
+        let last = &ass.nodes[*unitig.nodes.last().expect("a unitig has a node")].idx;
+        ass.nodes.id(&last.revcomp(ass.k)).map(|id| unitigs.of[id.index()]).filter(|&twin| twin < u)
+    }).collect();
+    for (from, unitig) in unitigs.unitigs.iter().enumerate() {
+        let last = *unitig.nodes.last().expect("a unitig has a node");
+        for edges in ass.graph.successors(last) {
+            let to = unitigs.of[edges[0].suffix.index()];
+            for spans in edges.chunk_by(|a, b| a.span == b.span) {
+                let count = spans.iter().map(|e| e.count).sum();
+                let reads = spans.iter().map(|e| e.reads).sum();
+                unitigs.links.push(Link { from, to, span: spans[0].span, count, reads });
+            }
+        }
+    }
+    unitigs
+}
+
+// Write the unitigs as GFA 1, named by their index, with RC:i: and EC:i: as in utils::graph2gfa. Once the strands
+// are joined a unitig and its reverse complement are one segment, named by the first of the two
+pub fn write_gfa<K: KmerRepr>(fname: &Path, ass: &Assembler<K>, unitigs: &Unitigs) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(fname)?);
+    writeln!(writer, "H\tVN:Z:1.0")?;
+    // The segment a unitig is written as and whether it is the reverse complement of it
+    let side = |u: usize| unitigs.twin[u].map_or((u, false), |twin| (twin, true));
+    for (u, unitig) in unitigs.unitigs.iter().enumerate() {
+        if side(u).1 { continue; }
+        let rc = (unitig.cov * unitig.seq.len() as f32).round() as usize;
+        writeln!(writer, "S\tu{}\t{}\tRC:i:{}\tLN:i:{}", u, String::from_utf8_lossy(&unitig.seq), rc, unitig.seq.len())?;
+    }
+    let orientation = |reverse: bool| if reverse { "-" } else { "+" };
+    let mut written = HashSet::new();
+    for link in &unitigs.links {
+        let (from, to) = (side(link.from), side(link.to));
+        // A link and its twin on the other strand are the same link read backwards
+        let backwards = (to.0, !to.1, from.0, !from.1, link.span);
+        if written.contains(&backwards) || !written.insert((from.0, from.1, to.0, to.1, link.span)) { continue; }
+        writeln!(writer, "L\tu{}\t{}\tu{}\t{}\t{}M\tRC:i:{}\tEC:i:{}", from.0, orientation(from.1), to.0, orientation(to.1),
+            2 * ass.k - link.span, link.reads, link.count)?;
+    }
+    writer.flush()
+}
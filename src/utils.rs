@@ -1,77 +1,1738 @@
-use crate::sbh_assembler::NodeType;
-use std::io::{ BufRead, BufReader, BufWriter, Write };
+use crate::{ config::{ NPolicy, SoftMask }, contig::Contig, counting::KmerCounter, kmer::KmerRepr, sbh_assembler::{ Assembler, Bubble, Edge, Eulerian, Graph, MergeStats, Node, Nodes, NodeType, NearMiss, Strand }, simd };
+use rayon::prelude::*;
+use std::io::{ BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write };
+use flate2::read::MultiGzDecoder;
+use rustc_hash::FxBuildHasher;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::fs::File;
+use std::ops::{ Range, RangeInclusive };
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicUsize, Ordering };
 
-// Read a fasta file
-// TODO: lazy format checking. Ensure input is a fasta file
-pub fn fasta_reader(fname: &str) -> Vec<Vec<u8>> {
-    let file = match File::open(fname) {
-        Ok(f) => f,
-        Err(_) => {
-            println!("\x1b[31mFATAL: Failed to open file: '{}'.\x1b[0m", fname);
+// Append a suffix to a file name without going through a String, e.g. cont.fasta -> cont.fasta.fai
+// Paths stay OsStr based everywhere so non-UTF8 file names work
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// A directory name under `dir` no other scratch directory of any process uses, e.g. sbh-kmers-4242-0. The process
+// id keeps runs apart and the counter keeps apart the jobs of one process
+pub fn scratch_dir(dir: &Path, prefix: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    dir.join(format!("{}-{}-{}", prefix, std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)))
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    // Detect the compression from the first bytes of a file
+    pub fn sniff(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+
+    // Wrap a reader of compressed data in a decoder. zstd and bzip2 need the feature of the same name
+    fn decoder<'a, R: BufRead + Send + 'a>(self, reader: R) -> std::io::Result<Box<dyn BufRead + Send + 'a>> {
+        match self {
+            Compression::None => Ok(Box::new(reader)),
+            Compression::Gzip => Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader)))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?))),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Ok(Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader)))),
+            #[allow(unreachable_patterns)]
+            other => Err(Error::new(ErrorKind::Unsupported,
+                format!("input is {} compressed, rebuild with --features {}", other.as_str(), other.as_str()))),
+        }
+    }
+}
+
+// A file name of - stands for stdin
+pub fn is_stdin(fname: &Path) -> bool {
+    fname.as_os_str() == "-"
+}
+
+// An infile given as an http:// or https:// URL
+pub fn is_url(fname: &Path) -> bool {
+    fname.to_str().is_some_and(|f| f.starts_with("http://") || f.starts_with("https://"))
+}
+
+// Open an input file, stdin for - or a URL, decompressing it on the fly if it is compressed
+// Compression is detected from the magic bytes, not the file name
+pub fn open_input(fname: &Path) -> std::io::Result<Box<dyn BufRead + Send>> {
+    if is_stdin(fname) {
+        let mut stdin = BufReader::new(std::io::stdin());
+        let compression = Compression::sniff(stdin.fill_buf()?);
+        return compression.decoder(stdin);
+    }
+    if is_url(fname) {
+        #[cfg(feature = "http")]
+        {
+            let mut body = BufReader::new(crate::http::open(&fname.to_string_lossy())?);
+            let compression = Compression::sniff(body.fill_buf()?);
+            return compression.decoder(body);
+        }
+        #[cfg(not(feature = "http"))]
+        return Err(Error::new(ErrorKind::Unsupported, "URL infiles need a build with --features http"));
+    }
+    let mut file = BufReader::new(File::open(fname)?);
+    let compression = Compression::sniff(file.fill_buf()?);
+    compression.decoder(file)
+}
+
+// Whether a file is compressed, from its magic bytes
+pub fn is_compressed(fname: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(fname).and_then(|mut f| f.read(&mut magic))
+        .is_ok_and(|n| Compression::sniff(&magic[..n]) != Compression::None)
+}
+
+// Decompress input that was read into memory, if it is compressed
+pub fn decompress(bytes: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let compression = Compression::sniff(&bytes);
+    if compression == Compression::None { return Ok(bytes); }
+    let mut out = vec![];
+    compression.decoder(&bytes[..])?.read_to_end(&mut out)?;
+    Ok(out)
+}
+
+// Open an input file or exit
+fn open_or_exit(fname: &Path) -> Box<dyn BufRead + Send> {
+    match open_input(fname) {
+        Ok(reader) => reader,
+        Err(e) => {
+            println!("\x1b[31mFATAL: Failed to open file: '{}': {}.\x1b[0m", fname.display(), e);
             std::process::exit(1);
         }
+    }
+}
+
+// A malformed input record, located by file, line and column (both starting at 1)
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file.display(), self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub(crate) fn parse_error(file: &Path, line: usize, column: usize, message: String) -> ParseError {
+    ParseError { file: file.to_path_buf(), line, column, message }
+}
+
+// How to read the input
+pub struct ReadOptions {
+    // Reads with a length outside this range are dropped
+    pub lens: RangeInclusive<usize>,
+    // Stop at the first malformed record instead of skipping it
+    pub strict: bool,
+    // Keep the record names, which pairing mates needs
+    pub names: bool,
+    // Read every input as this format instead of detecting it
+    pub format: Option<Format>,
+}
+
+// Reads dropped for having a length outside ReadOptions::lens
+#[derive(Default, Clone, Copy)]
+pub struct Rejected {
+    pub short: usize,
+    pub long: usize,
+}
+
+impl Rejected {
+    // Whether a read of length len is kept, counting it if not
+    pub fn keep(&mut self, len: usize, lens: &RangeInclusive<usize>) -> bool {
+        if len < *lens.start() {
+            self.short += 1;
+        } else if len > *lens.end() {
+            self.long += 1;
+        }
+        lens.contains(&len)
+    }
+
+    pub fn add(&mut self, other: Rejected) {
+        self.short += other.short;
+        self.long += other.long;
+    }
+}
+
+// The reads and quality strings of an input, the malformed records that were skipped and the reads rejected by length
+// The quality strings are empty for FASTA, the names are empty unless ReadOptions::names is set
+#[derive(Default)]
+pub struct Parsed {
+    pub reads: Vec<Vec<u8>>,
+    pub quals: Vec<Vec<u8>>,
+    pub names: Vec<String>,
+    pub malformed: Vec<ParseError>,
+    pub rejected: Rejected,
+    // Offset the FASTQ quality strings were found to have, see phred_offset. They are always stored as Phred+33
+    pub phred: Option<u8>,
+}
+
+impl Parsed {
+    // Skip a malformed record, or fail with it in strict mode
+    pub(crate) fn malformed(&mut self, error: ParseError, strict: bool) -> Result<(), ParseError> {
+        if strict { return Err(error); }
+        self.malformed.push(error);
+        Ok(())
+    }
+
+    // Add the reads of another input after these ones
+    // When only one side has quality strings the other side's reads get empty ones, so they stay aligned
+    pub fn append(&mut self, mut other: Parsed) {
+        if self.quals.is_empty() && !other.quals.is_empty() {
+            self.quals.resize(self.reads.len(), vec![]);
+        }
+        if other.quals.is_empty() && !self.quals.is_empty() {
+            other.quals.resize(other.reads.len(), vec![]);
+        }
+        self.reads.append(&mut other.reads);
+        self.quals.append(&mut other.quals);
+        self.names.append(&mut other.names);
+        self.malformed.append(&mut other.malformed);
+        self.rejected.add(other.rejected);
+        self.phred = self.phred.or(other.phred);
+    }
+
+    // Cut every read at the first window of `size` bases whose mean Phred+33 quality is below min_qual, like
+    // Trimmomatic's SLIDINGWINDOW. Reads shorter than a window are checked as one. Returns the number of reads cut
+    pub fn trim_quality(&mut self, size: usize, min_qual: u8) -> usize {
+        let mut trimmed = 0;
+        for (read, qual) in self.reads.iter_mut().zip(&mut self.quals) {
+            // FASTA reads in a mix of inputs have no quality string
+            if qual.is_empty() { continue; }
+            let size = size.min(qual.len());
+            let min_sum = size * min_qual as usize;
+            let mut sum: usize = qual[..size].iter().map(|&q| (q - 33) as usize).sum();
+            let mut cut = None;
+            for start in 0..=qual.len() - size {
+                if start > 0 { sum = sum + (qual[start + size - 1] - 33) as usize - (qual[start - 1] - 33) as usize; }
+                if sum < min_sum {
+                    cut = Some(start);
+                    break;
+                }
+            }
+            if let Some(cut) = cut {
+                read.truncate(cut);
+                qual.truncate(cut);
+                trimmed += 1;
+            }
+        }
+        trimmed
+    }
+
+    // Remove adapters from both ends of every read, see adapter_free. Returns the number of reads cut
+    pub fn trim_adapters(&mut self, adapters: &[Vec<u8>]) -> usize {
+        let mut trimmed = 0;
+        for (i, read) in self.reads.iter_mut().enumerate() {
+            let keep = adapter_free(read, adapters);
+            if keep.len() == read.len() { continue; }
+            read.truncate(keep.end);
+            read.drain(..keep.start);
+            if let Some(qual) = self.quals.get_mut(i).filter(|q| !q.is_empty()) {
+                qual.truncate(keep.end);
+                qual.drain(..keep.start);
+            }
+            trimmed += 1;
+        }
+        trimmed
+    }
+
+    // Detect the offset of the quality strings and store them as Phred+33, see phred_offset
+    pub(crate) fn detect_phred(&mut self) {
+        self.phred = phred_offset(&self.quals);
+        if self.phred == Some(64) {
+            // Solexa scores below 0 become 0
+            self.quals.iter_mut().flatten().for_each(|q| *q = (*q - 31).max(b'!'));
+        }
+    }
+
+    // Drop the reads with a length outside lens, counting them as rejected
+    pub fn keep_lens(&mut self, lens: &RangeInclusive<usize>) {
+        let kept: Vec<bool> = self.reads.iter().map(|r| self.rejected.keep(r.len(), lens)).collect();
+        for list in [&mut self.reads, &mut self.quals] {
+            let mut kept = kept.iter();
+            list.retain(|_| *kept.next().unwrap_or(&true));
+        }
+        let mut kept = kept.iter();
+        self.names.retain(|_| *kept.next().unwrap_or(&true));
+    }
+}
+
+// Lines of an input that keep track of the line number for errors
+struct Lines<'a, R: BufRead> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    file: &'a Path,
+}
+
+impl<'a, R: BufRead> Lines<'a, R> {
+    fn new(reader: R, file: &'a Path) -> Self {
+        Lines { lines: reader.lines(), line: 0, file }
+    }
+
+    fn next(&mut self) -> Result<Option<String>, ParseError> {
+        match self.lines.next() {
+            None => Ok(None),
+            Some(Ok(line)) => {
+                self.line += 1;
+                Ok(Some(line.trim_end_matches('\r').to_string()))
+            }
+            Some(Err(e)) => Err(self.error(self.line + 1, 1, format!("failed to read: {}", e))),
+        }
+    }
+
+    fn error(&self, line: usize, column: usize, message: String) -> ParseError {
+        parse_error(self.file, line, column, message)
+    }
+}
+
+// Letters cover IUPAC codes, which resolve_ns deals with. Gaps and stops are allowed too
+pub(crate) fn is_sequence_char(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'-' || c == b'*'
+}
+
+// Column and message for the first character of a sequence line that cannot be a base
+fn check_sequence(seq: &str) -> Option<(usize, String)> {
+    seq.bytes().position(|c| !is_sequence_char(c))
+        .map(|i| (i + 1, format!("unexpected character {:?} in sequence", seq[i..].chars().next().unwrap_or('?'))))
+}
+
+// The name of a record is its header up to the first whitespace
+pub(crate) fn read_name(header: &str) -> String {
+    header.split_whitespace().next().unwrap_or_default().to_string()
+}
+
+// Pair up the mates of an interleaved input: a record named x/1 directly followed by one named x/2
+// Records that do not fit, including mates whose partner was dropped, are left unpaired
+// Returns the indices of the pairs and the number of unpaired records
+pub fn pair_mates(names: &[String]) -> (Vec<(usize, usize)>, usize) {
+    let (mut pairs, mut unpaired) = (vec![], 0);
+    let mut i = 0;
+    while i < names.len() {
+        let stem = names[i].strip_suffix("/1");
+        if stem.is_some() && names.get(i + 1).and_then(|n| n.strip_suffix("/2")) == stem {
+            pairs.push((i, i + 1));
+            i += 2;
+        } else {
+            unpaired += 1;
+            i += 1;
+        }
+    }
+    (pairs, unpaired)
+}
+
+// The sequences of paired-end mates, first and second
+pub type Mates = Vec<(Vec<u8>, Vec<u8>)>;
+
+// A mate's name without its /1 or /2, so the names of both mates match
+pub fn mate_name(name: &str) -> &str {
+    name.strip_suffix("/1").or_else(|| name.strip_suffix("/2")).unwrap_or(name)
+}
+
+// Pair the records of separate first and second mate files, read with all lengths and their names
+// Record n of one file has to be the mate of record n of the other. Mates with a length outside lens are
+// dropped along with the pairing. Returns the reads of both files and the pairs
+pub fn pair_files(first: Parsed, second: Parsed, lens: &RangeInclusive<usize>)
+    -> Result<(Parsed, Mates), String> {
+    if first.reads.len() != second.reads.len() {
+        return Err(format!("--pe1 has {} records but --pe2 has {}.", first.reads.len(), second.reads.len()));
+    }
+    if let Some(i) = (0..first.names.len()).find(|&i| mate_name(&first.names[i]) != mate_name(&second.names[i])) {
+        return Err(format!("Record {} is '{}' in --pe1 but '{}' in --pe2.", i + 1, first.names[i], second.names[i]));
+    }
+    let mates = first.reads.iter().zip(&second.reads)
+        .filter(|(a, b)| lens.contains(&a.len()) && lens.contains(&b.len()))
+        .map(|(a, b)| (a.clone(), b.clone()))
+        .collect();
+    let mut parsed = Parsed::default();
+    for mut mate in [first, second] {
+        if mate.quals.is_empty() { mate.quals.resize(mate.reads.len(), vec![]); }
+        for (read, qual) in mate.reads.into_iter().zip(mate.quals) {
+            if !parsed.rejected.keep(read.len(), lens) { continue; }
+            parsed.reads.push(read);
+            parsed.quals.push(qual);
+        }
+        parsed.malformed.append(&mut mate.malformed);
+    }
+    Ok((parsed, mates))
+}
+
+// Read a fasta file
+pub fn fasta_reader(fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    fasta_reads(open_or_exit(fname), fname, options)
+}
+
+// An adapter has to cover this many bases at a read end to be trimmed, and twice as many to be found with a mismatch
+const ADAPTER_MIN_OVERLAP: usize = 5;
+
+fn is_adapter(bases: &[u8], adapter: &[u8]) -> bool {
+    let mismatches = bases.iter().zip(adapter).filter(|(b, a)| !b.eq_ignore_ascii_case(a)).count();
+    mismatches == 0 || (mismatches == 1 && bases.len() >= 2 * ADAPTER_MIN_OVERLAP)
+}
+
+// The part of a read left once adapters are removed from its ends. At the 5' end the longest read prefix that is the
+// end of an adapter is cut. After it, the read is cut from the first place an adapter starts, running off the 3' end
+// or not, with at most one mismatch
+fn adapter_free(read: &[u8], adapters: &[Vec<u8>]) -> Range<usize> {
+    let start = adapters.iter().filter_map(|adapter| {
+        (ADAPTER_MIN_OVERLAP..=adapter.len().min(read.len())).rev()
+            .find(|&m| is_adapter(&read[..m], &adapter[adapter.len() - m..]))
+    }).max().unwrap_or(0);
+    let end = adapters.iter().filter_map(|adapter| {
+        (start..read.len()).find(|&p| {
+            let m = adapter.len().min(read.len() - p);
+            m >= ADAPTER_MIN_OVERLAP && is_adapter(&read[p..p + m], &adapter[..m])
+        })
+    }).min().unwrap_or(read.len());
+    start..end
+}
+
+// Reads need a prefix and a distinct suffix k-mer, so they can be k+1 to 2k bp long
+// Reads shorter than 2k have overlapping prefix and suffix nodes
+pub fn read_lens(k: usize) -> RangeInclusive<usize> {
+    k + 1..=2 * k
+}
+
+// Read the reads of fasta data that is already open or in memory. fname is only used in errors
+// Sequences wrapped over several lines are joined up to the next header. Lines before the first header
+// are taken as one read each. Reads with a length outside the allowed lengths are dropped
+pub fn fasta_reads(reader: impl BufRead, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let mut lines = Lines::new(reader, fname);
+    let mut parsed = Parsed::default();
+    // Header line, name, sequence so far and whether the record is malformed
+    let mut record: Option<(usize, String, Vec<u8>, bool)> = None;
+    let finish = |parsed: &mut Parsed, record: Option<(usize, String, Vec<u8>, bool)>| match record {
+        Some((line, _, seq, false)) if seq.is_empty() =>
+            parsed.malformed(parse_error(fname, line, 1, "record has no sequence".to_string()), options.strict),
+        Some((_, name, seq, false)) => {
+            if parsed.rejected.keep(seq.len(), &options.lens) {
+                parsed.reads.push(seq);
+                if options.names { parsed.names.push(name); }
+            }
+            Ok(())
+        }
+        _ => Ok(()),
     };
-    let reader = BufReader::new(file);
-    let mut reads: Vec<Vec<u8>> = vec![];
-    for line in reader.lines() {
-        let line = line.unwrap();
-        if line.starts_with('>') { continue; }
-        if line.len() != 30 {
+    while let Some(line) = lines.next()? {
+        if let Some(header) = line.strip_prefix('>') {
+            finish(&mut parsed, record.replace((lines.line, read_name(header), vec![], false)))?;
+            continue;
+        }
+        if let Some((column, message)) = check_sequence(&line) {
+            parsed.malformed(lines.error(lines.line, column, message), options.strict)?;
+            if let Some(record) = record.as_mut() { record.3 = true; }
             continue;
         }
-        reads.push(line.as_bytes().to_vec());
+        match record.as_mut() {
+            Some((_, _, seq, _)) => seq.extend_from_slice(line.as_bytes()),
+            None => if parsed.rejected.keep(line.len(), &options.lens) {
+                parsed.reads.push(line.into_bytes());
+                if options.names { parsed.names.push(String::new()); }
+            },
+        }
     }
-    reads
+    finish(&mut parsed, record)?;
+    Ok(parsed)
 }
 
-// Convert a sequence vec to an index
-pub fn vec2idx(read: &Vec<u8>, t: NodeType) -> u32 {
-    let iter: Box<dyn Iterator<Item=&u8>> = match t {
-        NodeType::Prefix => Box::new(read.iter().take(15)),
-        NodeType::Suffix => Box::new(read.iter().skip(read.len()-15)),
+// FASTA is read in blocks of this size, each parsed in parallel chunks, so memory stays bounded on large files
+const FASTA_BLOCK: usize = 64 << 20;
+// Chunks smaller than this are not worth a task of their own
+const FASTA_CHUNK: usize = 1 << 20;
+
+// Parse FASTA data that holds whole records, in chunks of at least min_chunk bytes cut at record starts and parsed
+// in parallel. first_line is the line number of the data's first line in the file, so errors point at the right line
+fn fasta_block(block: &[u8], first_line: usize, min_chunk: usize, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let chunk = (block.len() / (4 * rayon::current_num_threads())).max(min_chunk);
+    let mut cuts = vec![0];
+    while let Some(&last) = cuts.last() {
+        if last + chunk >= block.len() { break; }
+        match block[last + chunk..].windows(2).position(|w| w == b"\n>") {
+            Some(i) => cuts.push(last + chunk + i + 1),
+            None => break,
+        }
+    }
+    cuts.push(block.len());
+    let chunks: Vec<&[u8]> = cuts.windows(2).map(|w| &block[w[0]..w[1]]).collect();
+    let lines: Vec<usize> = chunks.par_iter().map(|c| c.iter().filter(|&&b| b == b'\n').count()).collect();
+    let results: Vec<Result<Parsed, ParseError>> = chunks.par_iter().map(|c| fasta_reads(*c, fname, options)).collect();
+    let mut parsed = Parsed::default();
+    let mut line = first_line - 1;
+    for (result, n) in results.into_iter().zip(lines) {
+        let mut chunk = result.map_err(|mut e| { e.line += line; e })?;
+        chunk.malformed.iter_mut().for_each(|e| e.line += line);
+        parsed.append(chunk);
+        line += n;
+    }
+    Ok(parsed)
+}
+
+// Read FASTA data in blocks of `sizes.0` bytes, cutting each block before its last record, which may be incomplete,
+// and parsing it in chunks of at least `sizes.1` bytes. The sizes are FASTA_BLOCK and FASTA_CHUNK but in tests
+fn fasta_blocks(mut reader: impl Read, sizes: (usize, usize), fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let mut parsed = Parsed::default();
+    let mut buf = vec![];
+    let mut line = 1;
+    loop {
+        let n = reader.by_ref().take(sizes.0 as u64).read_to_end(&mut buf)
+            .map_err(|e| parse_error(fname, line, 1, format!("read failed: {}", e)))?;
+        let cut = if n == 0 {
+            buf.len()
+        } else {
+            match buf.windows(2).rposition(|w| w == b"\n>") {
+                Some(i) => i + 1,
+                None => continue,
+            }
+        };
+        parsed.append(fasta_block(&buf[..cut], line, sizes.1, fname, options)?);
+        line += buf[..cut].iter().filter(|&&b| b == b'\n').count();
+        buf.drain(..cut);
+        if n == 0 { return Ok(parsed); }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+    Fasta,
+    Fastq,
+    // SAM and BAM need the bam feature
+    Sam,
+    Bam,
+}
+
+impl Format {
+    // The format a file name says, looking through a compression extension such as .gz
+    pub fn by_name(fname: &Path) -> Option<Self> {
+        let name = fname.file_name().map(|n| n.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        let name = [".gz", ".zst", ".bz2"].iter().find_map(|ext| name.strip_suffix(ext)).unwrap_or(&name);
+        match name.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("fq" | "fastq") => Some(Format::Fastq),
+            Some("fa" | "fasta" | "fna" | "fas") => Some(Format::Fasta),
+            Some("sam") => Some(Format::Sam),
+            Some("bam") => Some(Format::Bam),
+            _ => None,
+        }
+    }
+
+    // Detect the format from the first bytes of decompressed data. BAM has a magic number, SAM usually
+    // starts with an @HD header line, FASTQ records start with '@' and FASTA ones with '>'
+    pub fn sniff(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(b"BAM\x01") {
+            Some(Format::Bam)
+        } else if magic.starts_with(b"@HD\t") || magic.starts_with(b"@SQ\t") {
+            Some(Format::Sam)
+        } else if magic.starts_with(b"@") {
+            Some(Format::Fastq)
+        } else if magic.starts_with(b">") {
+            Some(Format::Fasta)
+        } else {
+            None
+        }
+    }
+
+    // The content decides, and only data that does not start like any format, such as headerless SAM or FASTA
+    // with bare sequence lines, falls back to the file name and then to FASTA
+    pub fn detect(fname: &Path, magic: &[u8]) -> Self {
+        Format::sniff(magic).or_else(|| Format::by_name(fname)).unwrap_or(Format::Fasta)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Format::Fasta => "FASTA",
+            Format::Fastq => "FASTQ",
+            Format::Sam => "SAM",
+            Format::Bam => "BAM",
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "fasta" => Ok(Format::Fasta),
+            "fastq" => Ok(Format::Fastq),
+            "sam" => Ok(Format::Sam),
+            "bam" => Ok(Format::Bam),
+            _ => Err(()),
+        }
+    }
+}
+
+// The format of a file, from its decompressed content, see Format::detect
+pub fn format(fname: &Path) -> Format {
+    let mut magic = vec![];
+    let _ = open_input(fname).map(|f| f.take(4).read_to_end(&mut magic));
+    Format::detect(fname, &magic)
+}
+
+// Read a FASTA, FASTQ, SAM or BAM file, or stdin for -, whichever it is unless ReadOptions::format says
+// The input is opened once and sniffed without consuming it, so this works on pipes too
+pub fn read_reads(fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let mut reader = open_or_exit(fname);
+    let format = match options.format {
+        Some(format) => format,
+        None => reader.fill_buf().map(|magic| Format::detect(fname, magic)).unwrap_or(Format::Fasta),
     };
-    let mut idx = 0;
-    for (i, c) in iter.enumerate() {
-        let v = match c {
-            65 => 0,
-            67 => 1,
-            71 => 2,
-            84 => 3,
-            _ => panic!("VALS CAN ONLY BE A, C, G, or T"),
+    parse(reader, format, fname, options)
+}
+
+// Same as read_reads for data of fname that is already in memory
+pub fn parse_reads(bytes: &[u8], format: Format, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    parse(bytes, format, fname, options)
+}
+
+fn parse(reader: impl BufRead + Send, format: Format, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    // needletail wants a record first, so FASTA that starts with bare sequence lines stays with the built-in reader
+    #[cfg(feature = "fast-parse")]
+    let mut reader = reader;
+    #[cfg(feature = "fast-parse")]
+    if matches!(format, Format::Fasta | Format::Fastq)
+        && reader.fill_buf().is_ok_and(|b| b.is_empty() || b[0] == b'>' || b[0] == b'@') {
+        return crate::fast_parse::fastx_reads(reader, fname, options);
+    }
+    match format {
+        // With one thread the chunks would only add a copy of every block, so the lines are streamed instead
+        Format::Fasta if rayon::current_num_threads() > 1 => fasta_blocks(reader, (FASTA_BLOCK, FASTA_CHUNK), fname, options),
+        Format::Fasta => fasta_reads(reader, fname, options),
+        Format::Fastq => fastq_reads(reader, fname, options),
+        #[cfg(feature = "bam")]
+        Format::Sam => crate::bam::sam_reads(reader, fname, options),
+        #[cfg(feature = "bam")]
+        Format::Bam => crate::bam::bam_reads(reader, fname, options),
+        #[allow(unreachable_patterns)]
+        other => {
+            println!("\x1b[31mFATAL: '{}' is {}, rebuild with --features bam to read it.\x1b[0m", fname.display(), other.as_str());
+            std::process::exit(1);
+        }
+    }
+}
+
+// Read a fastq file, keeping the reads with an allowed length and their quality strings
+pub fn fastq_reader(fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    fastq_reads(open_or_exit(fname), fname, options)
+}
+
+// Offset of quality strings, 64 if none has a character below ';' and some have one above 'J', and 33 otherwise
+// Phred+64 starts at ';' for the old Solexa scores, and Phred+33 ends at 'J' for a score of 41, so data of only
+// high Phred+33 scores between '@' and 'J' is not taken for Phred+64. None without quality strings
+pub fn phred_offset(quals: &[Vec<u8>]) -> Option<u8> {
+    let (lowest, highest) = quals.iter().flatten().fold(None, |range, &q| match range {
+        None => Some((q, q)),
+        Some((lowest, highest)) => Some((q.min(lowest), q.max(highest))),
+    })?;
+    Some(if lowest >= b';' && highest > b'J' { 64 } else { 33 })
+}
+
+// Read the reads and quality strings of fastq data that is already open or in memory. fname is only used in errors
+// After a malformed record, reading picks up again at the next line starting with '@'
+// Phred+64 quality strings are detected, see phred_offset, and turned into Phred+33 ones
+pub fn fastq_reads(reader: impl BufRead, fname: &Path, options: &ReadOptions) -> Result<Parsed, ParseError> {
+    let mut lines = Lines::new(reader, fname);
+    let mut parsed = Parsed::default();
+    while let Some(header) = lines.next()? {
+        if header.is_empty() { continue; }
+        if !header.starts_with('@') {
+            parsed.malformed(lines.error(lines.line, 1, "expected a header line starting with '@'".to_string()), options.strict)?;
+            continue;
+        }
+        let start = lines.line;
+        let (Some(seq), Some(plus), Some(qual)) = (lines.next()?, lines.next()?, lines.next()?) else {
+            parsed.malformed(lines.error(start, 1, "record is truncated".to_string()), options.strict)?;
+            break;
+        };
+        let error = if let Some((column, message)) = check_sequence(&seq) {
+            Some(lines.error(start + 1, column, message))
+        } else if !plus.starts_with('+') {
+            Some(lines.error(start + 2, 1, "expected a separator line starting with '+'".to_string()))
+        } else if qual.len() != seq.len() {
+            Some(lines.error(start + 3, qual.len().min(seq.len()) + 1,
+                format!("quality length {} does not match sequence length {}", qual.len(), seq.len())))
+        } else {
+            qual.bytes().position(|c| !(b'!'..=b'~').contains(&c))
+                .map(|i| lines.error(start + 3, i + 1, "quality characters must be between '!' and '~'".to_string()))
         };
-        idx += v * 4u32.pow(i as u32);
+        if let Some(error) = error {
+            parsed.malformed(error, options.strict)?;
+            continue;
+        }
+        if parsed.rejected.keep(seq.len(), &options.lens) {
+            parsed.reads.push(seq.into_bytes());
+            parsed.quals.push(qual.into_bytes());
+            if options.names { parsed.names.push(read_name(&header[1..])); }
+        }
     }
-    idx
+    parsed.detect_phred();
+    Ok(parsed)
 }
 
-// Convert a index to a sequence vector
-pub fn idx2vec(mut idx: u32, length: usize) -> Vec<u8> {
-    let mut result = Vec::with_capacity(length);
-    for _ in 0..length {
-        let c = match idx % 4 {
-            0 => 65_u8,
-            1 => 67_u8,
-            2 => 71_u8,
-            3 => 84_u8,
-            _ => panic!("IMPOSSIBLE VALUE FOR MOD 4"),
+// Uppercase soft-masked bases, or drop the reads that have any with SoftMask::Drop
+// Quality strings, if any, follow their reads. Returns the reads and qualities that are left and the number dropped
+pub fn soft_mask(reads: Vec<Vec<u8>>, quals: Vec<Vec<u8>>, policy: SoftMask) -> (Vec<Vec<u8>>, Vec<Vec<u8>>, usize) {
+    let has_quals = !quals.is_empty();
+    let mut quals = quals.into_iter();
+    let (mut kept, mut kept_quals, mut dropped) = (vec![], vec![], 0);
+    for mut read in reads {
+        let qual = quals.next();
+        if read.iter().any(u8::is_ascii_lowercase) {
+            if policy == SoftMask::Drop {
+                dropped += 1;
+                continue;
+            }
+            read.make_ascii_uppercase();
+        }
+        kept.push(read);
+        if has_quals { kept_quals.push(qual.unwrap_or_default()); }
+    }
+    (kept, kept_quals, dropped)
+}
+
+// Split reads into overlapping (k+1)-mers, the edges of a de Bruijn graph with k-mer nodes
+// Windows with bases other than ACGT are left out. Identical windows are merged and their weights summed
+// The reads may be borrowed or owned, so they can be streamed; only distinct windows are copied
+pub fn kmerize<R: AsRef<[u8]>>(reads: impl IntoIterator<Item = (R, usize)>, k: usize) -> Vec<(Vec<u8>, usize)> {
+    // Windows that fit a word are counted by their rolled code, without hashing or copying their bases
+    if k < 32 {
+        let mut counts: HashMap<u64, usize, FxBuildHasher> = HashMap::default();
+        for (read, weight) in reads {
+            for (code, _) in simd::rolling(read.as_ref(), k + 1).flatten() {
+                *counts.entry(code).or_default() += weight;
+            }
+        }
+        return counts.into_iter().map(|(code, n)| (simd::unpack(code, k + 1), n)).collect();
+    }
+    let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+    for (read, weight) in reads {
+        for window in windows(read.as_ref(), k) {
+            count_window(&mut counts, window, weight);
+        }
+    }
+    counts.into_iter().collect()
+}
+
+// The (k+1)-mers of a read that kmerize keeps
+pub(crate) fn windows(read: &[u8], k: usize) -> impl Iterator<Item = &[u8]> {
+    read.windows(k + 1).filter(|window| window.iter().all(|c| matches!(c, b'A' | b'C' | b'G' | b'T')))
+}
+
+fn count_window(counts: &mut HashMap<Vec<u8>, usize>, window: &[u8], weight: usize) {
+    match counts.get_mut(window) {
+        Some(n) => *n += weight,
+        None => { counts.insert(window.to_vec(), weight); }
+    }
+}
+
+// A k-mer as it is counted by --min-kmer-count, joined with its reverse complement if both strands are assembled
+fn strand_key(kmer: &[u8], canonical: bool) -> Cow<'_, [u8]> {
+    if !canonical { return Cow::Borrowed(kmer); }
+    let rc = revcomp(kmer);
+    if rc.as_slice() < kmer { Cow::Owned(rc) } else { Cow::Borrowed(kmer) }
+}
+
+// Reads or windows with the number of copies each stands for, and how many were left out
+pub type Filtered = (Vec<(Vec<u8>, usize)>, usize);
+
+// How many k-mers kmerize_solid or drop_rare_ends count, to size the counter for
+pub fn kmer_occurrences<R: AsRef<[u8]>>(reads: &[(R, usize)], k: usize, debruijn: bool) -> usize {
+    if debruijn { reads.iter().map(|(read, _)| read.as_ref().len().saturating_sub(k)).sum() } else { 2 * reads.len() }
+}
+
+// Same as kmerize, leaving out windows seen fewer than `min` times, for --min-kmer-count and --drop-singletons
+// A first pass records the windows in `counter` and the second only counts the ones it has seen `min` times, so
+// with an approximate counter the rare windows never enter the table. Returns the windows and how many occurrences
+// were left out
+pub fn kmerize_solid<R: AsRef<[u8]>>(reads: &[(R, usize)], k: usize, canonical: bool, counter: &mut dyn KmerCounter, min: usize) -> Filtered {
+    solid(reads, canonical, counter, min, |read| windows(read, k).collect())
+}
+
+// kmerize_solid with the windows of a read given by `split`
+fn solid<R: AsRef<[u8]>>(reads: &[(R, usize)], canonical: bool, counter: &mut dyn KmerCounter, min: usize,
+    split: impl Fn(&[u8]) -> Vec<&[u8]>) -> Filtered {
+    for (read, weight) in reads {
+        for window in split(read.as_ref()) {
+            counter.add(&strand_key(window, canonical), *weight);
+        }
+    }
+    let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut dropped = 0;
+    for (read, weight) in reads {
+        for window in split(read.as_ref()) {
+            if counter.count(&strand_key(window, canonical)) >= min {
+                count_window(&mut counts, window, *weight);
+            } else {
+                dropped += weight;
+            }
+        }
+    }
+    // An approximate counter lets some rare windows through, which the exact counts catch
+    let seen = |window: &[u8], n: usize| n + if canonical { counts.get(revcomp(window).as_slice()).copied().unwrap_or(0) } else { 0 };
+    let mut kept = vec![];
+    for (window, &n) in &counts {
+        if seen(window, n) >= min { kept.push((window.clone(), n)); } else { dropped += n; }
+    }
+    (kept, dropped)
+}
+
+// Start positions of the k-mers of a read that are (w, k) minimizers: in every w consecutive k-mers, all of those
+// with the lowest hash. Ties are all kept and with `canonical` a k-mer hashes as its canonical form, so the same
+// k-mers are picked on both strands. Bases other than ACGT split the read, and the positions are listed for each
+// stretch between them, where consecutive ones are at most w apart
+pub fn minimizers(read: &[u8], k: usize, w: usize, canonical: bool) -> Vec<Vec<usize>> {
+    let mut stretches = vec![];
+    let mut start = 0;
+    for run in read.split(|c| !matches!(c, b'A' | b'C' | b'G' | b'T')) {
+        // Fx keeps the low bits of similar k-mers close, which would pick neighbours together
+        let mix = |h: u64| (h ^ h >> 29).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        // K-mers that fit a word hash their rolled code, the lower of the two strands with `canonical`
+        let hashes: Vec<u64> = if k <= 32 {
+            simd::rolling(run, k).flatten().map(|(fwd, rev)| mix(FxBuildHasher.hash_one(if canonical { fwd.min(rev) } else { fwd }))).collect()
+        } else {
+            run.windows(k).map(|kmer| mix(FxBuildHasher.hash_one(strand_key(kmer, canonical)))).collect()
         };
-        result.push(c);
-        idx /= 4;
+        let mut picked = vec![false; hashes.len()];
+        for (offset, window) in hashes.windows(w.min(hashes.len()).max(1)).enumerate() {
+            let lowest = window.iter().min().copied().unwrap_or(0);
+            for (i, &h) in window.iter().enumerate() {
+                if h == lowest { picked[offset + i] = true; }
+            }
+        }
+        let positions: Vec<usize> = (0..picked.len()).filter(|&i| picked[i]).map(|i| start + i).collect();
+        if !positions.is_empty() { stretches.push(positions); }
+        start += run.len() + 1;
     }
-    result
+    stretches
+}
+
+// The edges of a sparse de Bruijn graph for --sparse: the stretch of a read from one minimizer to the end of the
+// next, k+1 to w+k long. Nodes are only the minimizers, and the edge between two of them spans the bases skipped
+// between them, so with w at most k every base between the first and the last minimizer is still spelled
+pub fn sparse_windows(read: &[u8], k: usize, w: usize, canonical: bool) -> Vec<&[u8]> {
+    minimizers(read, k, w, canonical).iter()
+        .flat_map(|positions| positions.windows(2).map(|pair| &read[pair[0]..pair[1] + k]))
+        .collect()
+}
+
+// Same as kmerize with the windows of sparse_windows, leaving out windows seen fewer than `min` times in `counter`
+// if one is given, as kmerize_solid does
+pub fn kmerize_sparse<R: AsRef<[u8]>>(reads: &[(R, usize)], k: usize, w: usize, canonical: bool,
+    counter: Option<(&mut dyn KmerCounter, usize)>) -> Filtered {
+    match counter {
+        Some((counter, min)) => solid(reads, canonical, counter, min, |read| sparse_windows(read, k, w, canonical)),
+        None => {
+            let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+            for (read, weight) in reads {
+                for window in sparse_windows(read.as_ref(), k, w, canonical) {
+                    count_window(&mut counts, window, *weight);
+                }
+            }
+            (counts.into_iter().collect(), 0)
+        }
+    }
+}
+
+// Leave out reads whose prefix or suffix node is seen fewer than `min` times among the ends of all reads, for
+// --min-kmer-count and --drop-singletons without --debruijn. Those are the reads an error near either end makes
+// Ends are only checked against `counter`, so with an approximate one some of them get through. Returns the reads
+// kept and how many were dropped
+pub fn drop_rare_ends(reads: Vec<(Vec<u8>, usize)>, k: usize, canonical: bool, counter: &mut dyn KmerCounter, min: usize) -> Filtered {
+    for (read, weight) in &reads {
+        for end in [&read[..k], &read[read.len() - k..]] {
+            counter.add(&strand_key(end, canonical), *weight);
+        }
+    }
+    let total = reads.len();
+    let kept: Vec<(Vec<u8>, usize)> = reads.into_iter()
+        .filter(|(read, _)| [&read[..k], &read[read.len() - k..]].iter().all(|end| counter.count(&strand_key(end, canonical)) >= min))
+        .collect();
+    let dropped = total - kept.len();
+    (kept, dropped)
+}
+
+// Bases an IUPAC ambiguity code stands for. Anything that is not a code stands for any base
+pub fn iupac(c: u8) -> &'static [u8] {
+    match c.to_ascii_uppercase() {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        _ => b"ACGT",
+    }
+}
+
+// The pieces of a read between the positions where cut is true, with their quality strings
+// Only pieces with a length in lens are kept
+pub fn split_read(read: &[u8], qual: Option<&[u8]>, cut: impl Fn(usize) -> bool, lens: &RangeInclusive<usize>)
+    -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+    let mut pieces = vec![];
+    let mut start = 0;
+    for end in (0..=read.len()).filter(|&i| i == read.len() || cut(i)) {
+        if lens.contains(&(end - start)) {
+            pieces.push((read[start..end].to_vec(), qual.map(|q| q.get(start..end).unwrap_or_default().to_vec())));
+        }
+        start = end + 1;
+    }
+    pieces
+}
+
+// Break reads at runs of at least min_run Ns into the pieces between them that have a length in lens
+// Shorter runs are left for resolve_ns. Quality strings, if any, follow their reads
+// Returns the reads and qualities that are left and the number of reads that were split
+pub fn split_n_runs(reads: Vec<Vec<u8>>, quals: Vec<Vec<u8>>, min_run: usize, lens: &RangeInclusive<usize>) -> (Vec<Vec<u8>>, Vec<Vec<u8>>, usize) {
+    let has_quals = !quals.is_empty();
+    let mut quals = quals.into_iter();
+    let (mut kept, mut kept_quals, mut split) = (vec![], vec![], 0);
+    for read in reads {
+        let qual = quals.next();
+        // Mark the Ns that are part of a long enough run
+        let mut cut = vec![false; read.len()];
+        let mut start = 0;
+        for i in 0..=read.len() {
+            if i < read.len() && read[i].eq_ignore_ascii_case(&b'N') { continue; }
+            if i - start >= min_run { cut[start..i].fill(true); }
+            start = i + 1;
+        }
+        if !cut.contains(&true) {
+            kept.push(read);
+            if let Some(q) = qual { kept_quals.push(q); }
+            continue;
+        }
+        split += 1;
+        for (piece, piece_qual) in split_read(&read, qual.as_deref(), |i| cut[i], lens) {
+            kept.push(piece);
+            if has_quals { kept_quals.push(piece_qual.unwrap_or_default()); }
+        }
+    }
+    (kept, kept_quals, split)
+}
+
+// Reads, qualities, dropped reads and resolved reads
+pub type Resolved = (Vec<Vec<u8>>, Vec<Vec<u8>>, usize, usize);
+
+// Deal with reads with bases other than A, C, G and T, which the graph cannot index, according to policy
+// NPolicy::Expand completes a read with exactly one ambiguous base with the bases it may stand for whose k-mer
+// occurs most often in the unambiguous reads, up to max variants. Bases never seen there are not used, and
+// reads with more ambiguous bases are dropped. NPolicy::Split keeps the pieces between ambiguous bases that
+// have a length in lens, and NPolicy::Random draws every ambiguous base from the bases it stands for, seeded
+// with seed. Quality strings, if any, follow their reads
+// Returns the reads and qualities that are left, the number of dropped reads and the number of resolved reads,
+// or a message naming the first ambiguous base with NPolicy::Error
+pub fn resolve_ns(reads: Vec<Vec<u8>>, quals: Vec<Vec<u8>>, policy: NPolicy, k: usize, lens: RangeInclusive<usize>, seed: u64)
+    -> Result<Resolved, String> {
+    let is_base = |c: &u8| matches!(c, b'A' | b'C' | b'G' | b'T');
+    let has_quals = !quals.is_empty();
+    let mut quals = quals.into_iter();
+    let (mut clean, mut clean_quals, mut unresolved) = (vec![], vec![], vec![]);
+    for (i, read) in reads.into_iter().enumerate() {
+        let qual = quals.next();
+        if read.iter().all(is_base) {
+            clean.push(read);
+            if let Some(q) = qual { clean_quals.push(q); }
+        } else {
+            unresolved.push((i, read, qual));
+        }
+    }
+    let (mut skipped, mut resolved) = (0, 0);
+    let mut extra: Vec<(Vec<u8>, Option<Vec<u8>>)> = vec![];
+    match policy {
+        NPolicy::Skip => skipped = unresolved.len(),
+        NPolicy::Error => if let Some((i, read, _)) = unresolved.first() {
+            let pos = read.iter().position(|c| !is_base(c)).unwrap_or(0);
+            return Err(format!("Read {} has the ambiguous base '{}' at position {}.", i + 1, read[pos] as char, pos + 1));
+        },
+        NPolicy::Split => for (_, read, qual) in unresolved {
+            let pieces = split_read(&read, qual.as_deref(), |i| !is_base(&read[i]), &lens);
+            if pieces.is_empty() { skipped += 1; } else { resolved += 1; }
+            extra.extend(pieces);
+        },
+        NPolicy::Random => {
+            let mut rng = SplitMix64::new(seed);
+            for (_, mut read, qual) in unresolved {
+                for c in read.iter_mut().filter(|c| !is_base(c)) {
+                    let bases = iupac(*c);
+                    *c = bases[(rng.next_u64() % bases.len() as u64) as usize];
+                }
+                resolved += 1;
+                extra.push((read, qual));
+            }
+        }
+        NPolicy::Expand(max) => {
+            // How often each k-mer occurs in the unambiguous reads
+            let mut counts: HashMap<u64, usize> = HashMap::new();
+            for read in clean.iter() {
+                *counts.entry(vec2idx(read, NodeType::Prefix, k)).or_default() += 1;
+                *counts.entry(vec2idx(read, NodeType::Suffix, k)).or_default() += 1;
+            }
+            for (_, read, qual) in unresolved {
+                let odd: Vec<usize> = (0..read.len()).filter(|&i| !is_base(&read[i])).collect();
+                if odd.len() != 1 {
+                    skipped += 1;
+                    continue;
+                }
+                let pos = odd[0];
+                let half = if pos < k { NodeType::Prefix } else { NodeType::Suffix };
+                let mut variants: Vec<(usize, Vec<u8>)> = iupac(read[pos]).iter().map(|&b| {
+                    let mut variant = read.clone();
+                    variant[pos] = b;
+                    (counts.get(&vec2idx(&variant, half, k)).copied().unwrap_or(0), variant)
+                }).filter(|(n, _)| *n > 0).collect();
+                if variants.is_empty() {
+                    skipped += 1;
+                    continue;
+                }
+                variants.sort_by_key(|(n, _)| std::cmp::Reverse(*n));
+                resolved += 1;
+                for (_, variant) in variants.into_iter().take(max) {
+                    extra.push((variant, qual.clone()));
+                }
+            }
+        }
+    }
+    for (read, qual) in extra {
+        clean.push(read);
+        if has_quals { clean_quals.push(qual.unwrap_or_default()); }
+    }
+    Ok((clean, clean_quals, skipped, resolved))
+}
+
+// Collapse exact duplicate reads, such as PCR duplicates, into one read weighted by how many times it was seen
+// The reads are kept in the order they first appear, so the graph does not depend on hashing
+pub fn dedup_reads<R: AsRef<[u8]>>(reads: impl IntoIterator<Item = R>) -> Vec<(R, usize)> {
+    let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut uniq: Vec<(R, usize)> = vec![];
+    for read in reads {
+        match seen.get(read.as_ref()) {
+            Some(&i) => uniq[i].1 += 1,
+            None => {
+                seen.insert(read.as_ref().to_vec(), uniq.len());
+                uniq.push((read, 1));
+            }
+        }
+    }
+    uniq
+}
+
+// Collapse duplicate reads into consensus reads weighted by how many reads they stand for
+// Reads within max_mismatches of a more frequent read are folded into it. Each position of the consensus
+// is the base with the highest summed Phred quality. Without quality strings every base counts the same
+pub fn consensus_reads(reads: Vec<Vec<u8>>, quals: &[Vec<u8>], max_mismatches: usize) -> Vec<(Vec<u8>, usize)> {
+    // Per position quality sums for A, C, G, T
+    type Profile = Vec<[u32; 4]>;
+    let base = |c: u8| match c { b'A' => Some(0), b'C' => Some(1), b'G' => Some(2), b'T' => Some(3), _ => None };
+    let mut groups: HashMap<Vec<u8>, (usize, Profile)> = HashMap::new();
+    for (i, read) in reads.into_iter().enumerate() {
+        let qual = quals.get(i);
+        let entry = groups.entry(read.clone()).or_insert_with(|| (0, vec![[0; 4]; read.len()]));
+        entry.0 += 1;
+        for (pos, &c) in read.iter().enumerate() {
+            let q = qual.and_then(|q| q.get(pos)).map(|&q| q.saturating_sub(33) as u32 + 1).unwrap_or(1);
+            if let Some(b) = base(c) { entry.1[pos][b] += q; }
+        }
+    }
+    let mut uniq: Vec<(Vec<u8>, usize, Profile)> = groups.into_iter().map(|(r, (n, p))| (r, n, p)).collect();
+    // Heaviest first so near-duplicates fold into the most supported read
+    uniq.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    if max_mismatches > 0 {
+        // Reads within max_mismatches share at least one of max_mismatches + 1 segments exactly
+        let segments = max_mismatches + 1;
+        let mut buckets: HashMap<(usize, Vec<u8>), Vec<usize>> = HashMap::new();
+        for i in 0..uniq.len() {
+            let read = uniq[i].0.clone();
+            let bounds: Vec<(usize, usize)> = (0..segments)
+                .map(|s| (s * read.len() / segments, (s + 1) * read.len() / segments))
+                .collect();
+            let found = bounds.iter().enumerate()
+                .flat_map(|(s, &(a, b))| buckets.get(&(s, read[a..b].to_vec())).into_iter().flatten())
+                .copied()
+                .find(|&j| uniq[j].0.len() == read.len()
+                    && simd::hamming(&uniq[j].0, &read) <= max_mismatches);
+            match found {
+                Some(j) => {
+                    let (n, profile) = (uniq[i].1, std::mem::take(&mut uniq[i].2));
+                    uniq[j].1 += n;
+                    for (acc, p) in uniq[j].2.iter_mut().zip(profile) {
+                        for b in 0..4 { acc[b] += p[b]; }
+                    }
+                    uniq[i].1 = 0;
+                }
+                None => for (s, &(a, b)) in bounds.iter().enumerate() {
+                    buckets.entry((s, read[a..b].to_vec())).or_default().push(i);
+                },
+            }
+        }
+    }
+    uniq.into_iter().filter(|(_, n, _)| *n > 0).map(|(mut read, n, profile)| {
+        for (c, p) in read.iter_mut().zip(profile.iter()) {
+            let (best, &q) = p.iter().enumerate().max_by_key(|(_, &q)| q).unwrap();
+            if q > 0 { *c = b"ACGT"[best]; }
+        }
+        (read, n)
+    }).collect()
+}
+
+// A named record of an arbitrary fasta file
+pub struct FastaRecord {
+    pub name: String,
+    pub seq: Vec<u8>,
+}
+
+// Read every record of a fasta file, joining wrapped sequence lines
+pub fn read_fasta(fname: &Path) -> std::io::Result<Vec<FastaRecord>> {
+    let reader = open_input(fname)?;
+    let mut records: Vec<FastaRecord> = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix('>') {
+            records.push(FastaRecord { name: header.to_string(), seq: vec![] });
+        } else if let Some(record) = records.last_mut() {
+            record.seq.extend(line.bytes().filter(|c| !c.is_ascii_whitespace()));
+        } else if !line.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, format!("{}:{}: sequence before the first header", fname.display(), i + 1)));
+        }
+    }
+    Ok(records)
+}
+
+// Write records in fasta format, wrapping sequences at width columns. A width of 0 disables wrapping
+pub fn write_fasta(writer: &mut impl Write, records: &[FastaRecord], width: usize) -> std::io::Result<()> {
+    for record in records {
+        writeln!(writer, ">{}", record.name)?;
+        let width = if width == 0 { record.seq.len().max(1) } else { width };
+        for line in record.seq.chunks(width) {
+            writer.write_all(line)?;
+            writeln!(writer)?;
+        }
+    }
+    writer.flush()
+}
+
+// Reverse complement of a nucleotide sequence. Anything that is not ACGT becomes N
+pub fn revcomp(seq: &[u8]) -> Vec<u8> {
+    simd::revcomp(seq)
+}
+
+// Translate a nucleotide sequence to protein with the standard genetic code, starting at frame 0
+// Codons with anything other than ACGT translate to X and stop codons to '*'
+pub fn translate(seq: &[u8]) -> Vec<u8> {
+    const CODE: &[u8; 64] = b"KNKNTTTTRSRSIIMIQHQHPPPPRRRRLLLLEDEDAAAAGGGGVVVV*Y*YSSSS*CWCLFLF";
+    seq.chunks_exact(3).map(|codon| {
+        codon.iter().try_fold(0, |acc, c| match c.to_ascii_uppercase() {
+            b'A' => Some(acc * 4),
+            b'C' => Some(acc * 4 + 1),
+            b'G' => Some(acc * 4 + 2),
+            b'T' | b'U' => Some(acc * 4 + 3),
+            _ => None,
+        }).map(|i| CODE[i]).unwrap_or(b'X')
+    }).collect()
+}
+
+// Convert a sequence vec to an index
+pub fn vec2idx<K: KmerRepr>(read: &[u8], t: NodeType, k: usize) -> K {
+    let bases = match t {
+        NodeType::Prefix => &read[..k],
+        NodeType::Suffix => &read[read.len()-k..],
+    };
+    K::pack(bases).expect("VALS CAN ONLY BE A, C, G, or T")
+}
+
+// Convert a index to a sequence vector
+pub fn idx2vec(idx: u64, length: usize) -> Vec<u8> {
+    simd::unpack(idx, length)
 }
 
 // Write contigs to file in fasta format
-pub fn cont2file(fname: &str, contigs: Vec<Vec<u8>>) -> std::io::Result<()> {
+pub fn cont2file(fname: &Path, contigs: &[Contig]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(fname)?);
+    write_contigs(&mut writer, contigs.iter().enumerate(), None)
+}
+
+// Like cont2file, with the coverage and estimated copy number of every contig in its header
+pub fn cont2file_copy_number(fname: &Path, contigs: &[Contig], modal_coverage: f32) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(fname)?);
+    write_contigs(&mut writer, contigs.iter().enumerate(), Some(modal_coverage))
+}
+
+// The fasta file cont2file or cont2file_copy_number would write, in memory
+pub fn contigs2fasta(contigs: &[Contig], modal_coverage: Option<f32>) -> std::io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    write_contigs(&mut bytes, contigs.iter().enumerate(), modal_coverage)?;
+    Ok(bytes)
+}
+
+// Write some of the contigs, named by their index in the full contig list
+fn write_contigs<'a>(writer: &mut impl Write, contigs: impl Iterator<Item = (usize, &'a Contig)>, modal_coverage: Option<f32>) -> std::io::Result<()> {
+    for (i, cont) in contigs {
+        match modal_coverage {
+            Some(modal) => writeln!(writer, ">sequence{} cov={:.2} cn={:.2}", i + 1, cont.coverage, cont.copy_number(modal))?,
+            None => writeln!(writer, ">sequence{}", i + 1)?,
+        }
+        let cont_str = String::from_utf8(cont.seq.clone())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        writeln!(writer, "{}", cont_str)?;
+    }
+    writer.flush()
+}
+
+// Write the contigs into one fasta file per size class in dir. Boundaries must be ascending, a contig
+// belongs to the first class whose upper boundary it is shorter than. Two boundaries give small.fasta,
+// medium.fasta and large.fasta, one gives small.fasta and large.fasta and more give size1.fasta, size2.fasta...
+// Contig names match the ones written by cont2file
+// Returns the file and contig count of every class
+pub fn split_by_length(dir: &Path, contigs: &[Contig], boundaries: &[usize]) -> std::io::Result<Vec<(PathBuf, usize)>> {
+    let names: Vec<String> = match boundaries.len() {
+        1 => vec!["small".into(), "large".into()],
+        2 => vec!["small".into(), "medium".into(), "large".into()],
+        n => (1..=n + 1).map(|i| format!("size{}", i)).collect(),
+    };
+    let class = |c: &Contig| boundaries.iter().position(|&b| c.len() < b).unwrap_or(boundaries.len());
+    let mut written = vec![];
+    for (k, name) in names.iter().enumerate() {
+        let fname = dir.join(format!("{}.fasta", name));
+        let members = contigs.iter().enumerate().filter(|(_, c)| class(c) == k);
+        write_contigs(&mut BufWriter::new(File::create(&fname)?), members.clone(), None)?;
+        written.push((fname, members.count()));
+    }
+    Ok(written)
+}
+
+// Write near-miss merges to a TSV file. Contig names match the ones written by cont2file
+pub fn near_misses2file(fname: &Path, near_misses: &[NearMiss]) -> std::io::Result<()> {
+    let file = File::create(fname)?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "left\tright\toverlap\tmismatches\trequired\treason")?;
+    for m in near_misses {
+        let reason = if m.mismatches > 0 { "mismatches" } else { "short_overlap" };
+        writeln!(writer, "sequence{}\tsequence{}\t{}\t{}\t{}\t{}",
+            m.left + 1, m.right + 1, m.overlap, m.mismatches, m.required, reason)?;
+    }
+    Ok(())
+}
+
+// Write the bubbles popped by Assembler::pop_bubbles to a TSV file, one row per popped branch
+pub fn bubbles2file(fname: &Path, bubbles: &[Bubble]) -> std::io::Result<()> {
     let file = File::create(fname)?;
     let mut writer = BufWriter::new(file);
+    writeln!(writer, "kept\tpopped\tkept_cov\tpopped_cov")?;
+    for b in bubbles {
+        writeln!(writer, "{}\t{}\t{:.2}\t{:.2}", String::from_utf8_lossy(&b.kept), String::from_utf8_lossy(&b.popped), b.kept_cov, b.popped_cov)?;
+    }
+    Ok(())
+}
+
+// Write the degree balance of every component with edges to a TSV file, largest first
+// Components are numbered like Assembler::components, so those left out are single nodes without edges
+pub fn eulerian2file(fname: &Path, eulerian: &Eulerian) -> std::io::Result<()> {
+    let file = File::create(fname)?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "component\tnodes\tedges\tunbalanced\tmin_walks\teulerian")?;
+    for (i, b) in eulerian.components.iter().enumerate().filter(|(_, b)| b.edges > 0) {
+        writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}", i, b.nodes, b.edges, b.unbalanced, b.min_walks(), b.walk().as_str())?;
+    }
+    Ok(())
+}
+
+// Write the node and stop reason of both ends of every contig to a TSV file
+// Contig names match the ones written by cont2file
+pub fn break_points2file(fname: &Path, contigs: &[Contig], k: usize) -> std::io::Result<()> {
+    let file = File::create(fname)?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "contig\tlength\tend\tnode\tkmer\treason")?;
     for (i, cont) in contigs.iter().enumerate() {
-        writeln!(writer, ">sequence{}", i + 1)?;
-        let cont_str = String::from_utf8(cont.to_vec())
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        writeln!(writer, "{}", cont_str)?;
+        for (end, bp) in ["left", "right"].iter().zip(cont.ends.iter()) {
+            writeln!(writer, "sequence{}\t{}\t{}\t{}\t{}\t{}", i + 1, cont.len(), end, bp.node,
+                String::from_utf8_lossy(&idx2vec(bp.node, k)), bp.reason.as_str())?;
+        }
+    }
+    Ok(())
+}
+
+// Write the samples that have every edge of each contig and those that have only some, see Assembler::add_colors,
+// named as in `samples`
+pub fn colors2file(fname: &Path, contigs: &[Contig], samples: &[String]) -> std::io::Result<()> {
+    let file = File::create(fname)?;
+    let mut writer = BufWriter::new(file);
+    let names = |colors: u64| {
+        let names: Vec<&str> = samples.iter().enumerate()
+            .filter(|(color, _)| colors >> color & 1 == 1)
+            .map(|(_, name)| name.as_str())
+            .collect();
+        if names.is_empty() { "-".to_string() } else { names.join(",") }
+    };
+    writeln!(writer, "contig\tlength\tsamples\tpartial")?;
+    for (i, cont) in contigs.iter().enumerate() {
+        writeln!(writer, "sequence{}\t{}\t{}\t{}", i + 1, cont.len(), names(cont.colors), names(cont.any_colors & !cont.colors))?;
     }
+    Ok(())
+}
 
+// Write where each read is along each contig, one row per stretch of edges it has, see Assembler::read_spans. Starts
+// are 0-based and ends exclusive, as in BED
+pub fn reads2file(fname: &Path, contigs: &[Contig], names: &[String]) -> std::io::Result<()> {
+    let file = File::create(fname)?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "contig\tstart\tend\tread")?;
+    for (i, cont) in contigs.iter().enumerate() {
+        for span in &cont.reads {
+            writeln!(writer, "sequence{}\t{}\t{}\t{}", i + 1, span.start, span.end, names[span.read as usize])?;
+        }
+    }
     Ok(())
 }
+
+// Append one row per thread for a merge pass to a metrics CSV
+pub fn merge_stats2csv(writer: &mut impl Write, pass: usize, stats: &MergeStats) -> std::io::Result<()> {
+    for (thread, counters) in stats.threads.iter().enumerate() {
+        let (pairs, overlaps, bytes) = counters.get();
+        if pairs == 0 { continue; }
+        writeln!(writer, "{},{},{},{},{}", pass, thread, pairs, overlaps, bytes)?;
+    }
+    writer.flush()
+}
+
+// A record of a samtools style .fai index
+pub struct FaiEntry {
+    pub name: String,
+    pub len: usize,
+    // Byte offset of the first base of the record
+    pub offset: u64,
+    pub line_bases: usize,
+    // Bases per line plus the line terminator
+    pub line_width: usize,
+}
+
+// Index a fasta file by scanning it once
+pub fn fasta_index(fname: &Path) -> std::io::Result<Vec<FaiEntry>> {
+    let mut reader = BufReader::new(File::open(fname)?);
+    let mut entries: Vec<FaiEntry> = vec![];
+    let mut offset = 0u64;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        if n == 0 { break; }
+        offset += n as u64;
+        let bases = line.iter().filter(|c| !c.is_ascii_whitespace()).count();
+        if line.starts_with(b">") {
+            let header = String::from_utf8_lossy(&line[1..]);
+            let name = header.split_whitespace().next().unwrap_or("").to_string();
+            entries.push(FaiEntry { name, len: 0, offset, line_bases: 0, line_width: 0 });
+        } else if let Some(entry) = entries.last_mut() {
+            if entry.line_bases == 0 {
+                entry.line_bases = bases;
+                entry.line_width = n;
+            }
+            entry.len += bases;
+        }
+    }
+    Ok(entries)
+}
+
+// Write the .fai sidecar for a fasta file next to it
+pub fn write_fai(fname: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(with_suffix(fname, ".fai"))?);
+    for e in fasta_index(fname)? {
+        writeln!(writer, "{}\t{}\t{}\t{}\t{}", e.name, e.len, e.offset, e.line_bases, e.line_width)?;
+    }
+    writer.flush()
+}
+
+// Read the .fai sidecar of a fasta file, or index the file if there is none
+pub fn read_fai(fname: &Path) -> std::io::Result<Vec<FaiEntry>> {
+    let file = match File::open(with_suffix(fname, ".fai")) {
+        Ok(f) => f,
+        Err(_) => return fasta_index(fname),
+    };
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let f: Vec<&str> = line.split('\t').collect();
+        let invalid = || Error::new(ErrorKind::InvalidData, format!("malformed .fai line: '{}'", line));
+        if f.len() < 5 { return Err(invalid()); }
+        entries.push(FaiEntry {
+            name: f[0].to_string(),
+            len: f[1].parse().map_err(|_| invalid())?,
+            offset: f[2].parse().map_err(|_| invalid())?,
+            line_bases: f[3].parse().map_err(|_| invalid())?,
+            line_width: f[4].parse().map_err(|_| invalid())?,
+        });
+    }
+    Ok(entries)
+}
+
+// Read the bases of a single record, or a range of it, without loading the whole file
+pub fn fasta_fetch(fname: &Path, name: &str, range: Option<Range<usize>>) -> std::io::Result<Vec<u8>> {
+    let entries = read_fai(fname)?;
+    let entry = entries.iter().find(|e| e.name == name)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no record named '{}' in {}", name, fname.display())))?;
+    let range = range.unwrap_or(0..entry.len);
+    if range.start > range.end || range.end > entry.len {
+        return Err(Error::new(ErrorKind::InvalidInput,
+            format!("range {:?} is out of bounds for '{}' of length {}", range, name, entry.len)));
+    }
+    if range.is_empty() { return Ok(vec![]); }
+    // Translate base positions to byte positions, skipping line terminators
+    let byte_pos = |base: usize| {
+        let line_bases = entry.line_bases.max(1);
+        entry.offset + ((base / line_bases) * entry.line_width + base % line_bases) as u64
+    };
+    let start = byte_pos(range.start);
+    let end = byte_pos(range.end - 1) + 1;
+    let mut file = File::open(fname)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut buf = vec![0; (end - start) as usize];
+    file.read_exact(&mut buf)?;
+    buf.retain(|c| !c.is_ascii_whitespace());
+    Ok(buf)
+}
+
+// Small seeded generator (SplitMix64) so sampling is reproducible without pulling in a crate
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Keep each read with probability frac, then at most max of the ones left, chosen at random
+// The reads keep their order and quality strings, if any, follow them
+pub fn subsample(reads: Vec<Vec<u8>>, quals: Vec<Vec<u8>>, frac: Option<f64>, max: Option<usize>, seed: u64) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+    let mut rng = SplitMix64::new(seed);
+    let mut picked: Vec<usize> = (0..reads.len()).filter(|_| frac.is_none_or(|f| rng.next_f64() < f)).collect();
+    if let Some(max) = max.filter(|&max| max < picked.len()) {
+        // Partial Fisher-Yates shuffle of the first max picks
+        for i in 0..max {
+            let j = i + (rng.next_u64() % (picked.len() - i) as u64) as usize;
+            picked.swap(i, j);
+        }
+        picked.truncate(max);
+        picked.sort_unstable();
+    }
+    let mut keep = vec![false; reads.len()];
+    picked.into_iter().for_each(|i| keep[i] = true);
+    let mut kept = keep.iter();
+    let reads = reads.into_iter().filter(|_| *kept.next().unwrap()).collect();
+    let mut kept = keep.iter();
+    let quals = quals.into_iter().filter(|_| *kept.next().unwrap_or(&false)).collect();
+    (reads, quals)
+}
+
+// Keep each read with probability frac
+pub fn sample_reads(reads: Vec<Vec<u8>>, frac: f64, seed: u64) -> Vec<Vec<u8>> {
+    let mut rng = SplitMix64::new(seed);
+    reads.into_iter().filter(|_| rng.next_f64() < frac).collect()
+}
+
+// Write the graph in GFA 1 format. Every node is a segment and every prefix/suffix pair a link
+// Adjacent nodes of a 2k bp read do not overlap, shorter reads overlap them by 2k - read length
+// A link has the number of reads as RC:i: and the number of edges as EC:i:, so gfa2graph can rebuild it
+// Once the strands are joined the graph is written bidirected: a segment per canonical vertex, and a link per edge
+// and its twin with the sides it joins as its orientations
+pub fn graph2gfa(fname: &Path, ass: &Assembler) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(fname)?);
+    writeln!(writer, "H\tVN:Z:1.0")?;
+    let vertex = |idx: u64| if ass.canonical { idx.canonical(ass.k) } else { idx };
+    let mut idxs: Vec<u64> = ass.nodes.iter().map(|(_, node)| vertex(node.idx)).collect();
+    idxs.sort_unstable();
+    idxs.dedup();
+    for idx in idxs {
+        let node = ass.nodes.get(&idx).or_else(|| ass.nodes.get(&idx.revcomp(ass.k))).expect("a side of every vertex is a node");
+        let seq = String::from_utf8_lossy(&idx2vec(idx, ass.k)).into_owned();
+        writeln!(writer, "S\t{}\t{}\tRC:i:{}", idx, seq, node.cov * ass.k)?;
+    }
+    let mut pairs: Vec<(u64, u64, [Strand; 2], &[Edge])> = ass.graph.links()
+        .map(|edges| (ass.nodes[edges[0].prefix].idx, ass.nodes[edges[0].suffix].idx, edges))
+        .filter(|&(p, s, _)| !ass.is_twin(&p, &s))
+        .map(|(p, s, edges)| {
+            let strands = if ass.canonical { edges[0].orientation(&ass.nodes, ass.k) } else { [Strand::Forward; 2] };
+            (p, s, strands, edges)
+        })
+        .collect();
+    pairs.sort_unstable_by_key(|&(p, s, ..)| (vertex(p), vertex(s), p, s));
+    for (p, s, [from, to], edges) in pairs {
+        writeln!(writer, "L\t{}\t{}\t{}\t{}\t{}M\tRC:i:{}\tEC:i:{}", vertex(p), from.as_str(), vertex(s), to.as_str(),
+            2 * ass.k - ass.span(edges[0].prefix, edges[0].suffix), edges.iter().map(|e| e.reads).sum::<usize>(), edges.iter().map(|e| e.count).sum::<u32>())?;
+    }
+    writer.flush()
+}
+
+// Observed k-mers and how often each was seen, and the malformed lines that were skipped
+#[derive(Default)]
+pub struct Spectrum {
+    pub kmers: Vec<(Vec<u8>, usize)>,
+    pub malformed: Vec<ParseError>,
+}
+
+// Read k-mer spectra, one k-mer per line optionally followed by a tab and its count, as sequencing by
+// hybridization reports them. Blank lines and lines starting with '#' are skipped. Every k-mer must be as
+// long as the first one. A k-mer listed more than once, in one file or several, has its counts summed
+pub fn read_spectrum(fnames: &[PathBuf], strict: bool) -> Result<Spectrum, ParseError> {
+    let mut spectrum = Spectrum::default();
+    let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut len = 0;
+    for fname in fnames {
+        let mut lines = Lines::new(open_or_exit(fname), fname);
+        while let Some(line) = lines.next()? {
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let (kmer, count) = line.split_once('\t').unwrap_or((&line, "1"));
+            // The k-mers go straight into the graph, so there is no N policy to resolve ambiguous bases
+            let error = if let Some(i) = kmer.bytes().position(|c| !b"ACGTacgt".contains(&c)) {
+                Some(lines.error(lines.line, i + 1, "k-mers must be ACGT".to_string()))
+            } else if !(2..=17).contains(&kmer.len()) {
+                Some(lines.error(lines.line, 1, format!("k-mers must be 2 to 17 bp long, not {}", kmer.len())))
+            } else if len != 0 && kmer.len() != len {
+                Some(lines.error(lines.line, 1, format!("k-mer is {} bp but the first one is {} bp", kmer.len(), len)))
+            } else {
+                count.trim().parse::<usize>().err()
+                    .map(|_| lines.error(lines.line, kmer.len() + 2, format!("invalid count '{}'", count)))
+            };
+            if let Some(error) = error {
+                if strict { return Err(error); }
+                spectrum.malformed.push(error);
+                continue;
+            }
+            len = kmer.len();
+            let count: usize = count.trim().parse().unwrap_or(1);
+            if count > 0 {
+                *counts.entry(kmer.as_bytes().to_ascii_uppercase()).or_default() += count;
+            }
+        }
+    }
+    spectrum.kmers = counts.into_iter().collect();
+    spectrum.kmers.sort_unstable();
+    Ok(spectrum)
+}
+
+// Load a GFA 1 graph written by graph2gfa, or any graph of equally long segments
+// Segments become nodes and must be k bp of ACGT, k being the length of the first one. A link becomes EC:i: edges,
+// one without it, which share the RC:i: reads of the link, and its overlap sets their span. RC:i: of a segment is its
+// k-mer count times k, as in graph2gfa; without it coverage is counted from the links. Other record types are ignored
+// A link with a - orientation makes the graph bidirected: a - end is the reverse complement of its segment, and
+// every link gets its twin on the other strand as with Assembler::make_canonical
+pub fn gfa2graph(fname: &Path) -> Result<Assembler, ParseError> {
+    let mut lines = Lines::new(open_or_exit(fname), fname);
+    // Segment name to node index and coverage, and the links with their line numbers
+    let mut segments: HashMap<String, (u64, Option<usize>)> = HashMap::new();
+    // A link end is a segment name and the side of it the link is on
+    type End = (String, Strand);
+    let mut links: Vec<(usize, End, End, usize, usize, usize)> = vec![];
+    let mut k = 0;
+    let tag = |fields: &[&str], name: &str| fields.iter().find_map(|f| f.strip_prefix(name)).and_then(|v| v.parse::<usize>().ok());
+    while let Some(line) = lines.next()? {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields[0] {
+            "S" if fields.len() >= 3 => {
+                let seq = fields[2].as_bytes();
+                if k == 0 {
+                    if !(1..=31).contains(&seq.len()) {
+                        return Err(lines.error(lines.line, 1, format!("segments must be 1 to 31 bp long, not {}", seq.len())));
+                    }
+                    k = seq.len();
+                }
+                if seq.len() != k {
+                    return Err(lines.error(lines.line, 1, format!("segment is {} bp but the first one is {} bp", seq.len(), k)));
+                }
+                let Some(idx) = simd::pack(seq) else {
+                    return Err(lines.error(lines.line, 1, "segment sequence must be ACGT".to_string()));
+                };
+                segments.insert(fields[1].to_string(), (idx, tag(&fields[3..], "RC:i:").map(|rc| rc / k)));
+            }
+            "L" if fields.len() >= 6 => {
+                let strand = |orientation: &str| match orientation {
+                    "+" => Some(Strand::Forward),
+                    "-" => Some(Strand::Reverse),
+                    _ => None,
+                };
+                let (Some(from), Some(to)) = (strand(fields[2]), strand(fields[4])) else {
+                    return Err(lines.error(lines.line, 1, "link orientations must be + or -".to_string()));
+                };
+                let overlap = match fields[5] {
+                    "*" => Some(0),
+                    cigar => cigar.strip_suffix('M').and_then(|o| o.parse().ok()),
+                };
+                let Some(overlap) = overlap else {
+                    return Err(lines.error(lines.line, 1, format!("unsupported overlap '{}', expected <n>M", fields[5])));
+                };
+                let edges = tag(&fields[6..], "EC:i:").unwrap_or(1).max(1);
+                let reads = tag(&fields[6..], "RC:i:").unwrap_or(edges).max(edges);
+                links.push((lines.line, (fields[1].to_string(), from), (fields[3].to_string(), to), overlap, edges, reads));
+            }
+            "S" | "L" => return Err(lines.error(lines.line, 1, "record has too few fields".to_string())),
+            _ => {}
+        }
+    }
+    let mut nodes: Nodes = Nodes::default();
+    for &(idx, cov) in segments.values() {
+        nodes.insert(Node::new(idx, 0, 0, cov.unwrap_or(0)));
+    }
+    let mut graph: Vec<Edge> = vec![];
+    let bidirected = links.iter().any(|(_, (_, from), (_, to), ..)| *from == Strand::Reverse || *to == Strand::Reverse);
+    for (line, (from, pstrand), (to, sstrand), overlap, count, reads) in links {
+        let (Some(&(pidx, pcov)), Some(&(sidx, scov))) = (segments.get(&from), segments.get(&to)) else {
+            return Err(lines.error(line, 1, "link to a segment that is not in the file".to_string()));
+        };
+        if overlap >= k {
+            return Err(lines.error(line, 1, format!("overlap of {} bp leaves no new base on a {} bp segment", overlap, k)));
+        }
+        let side = |idx: u64, strand: Strand| if strand == Strand::Reverse { idx.revcomp(k) } else { idx };
+        let (pidx, sidx) = (side(pidx, pstrand), side(sidx, sstrand));
+        let (prefix, suffix) = (nodes.id_or_insert(&pidx), nodes.id_or_insert(&sidx));
+        nodes[prefix].odeg += count;
+        nodes[suffix].ideg += count;
+        if pcov.is_none() { nodes[prefix].cov += reads; }
+        if scov.is_none() { nodes[suffix].cov += reads; }
+        let mut edge = Edge::new(prefix, suffix, 2 * k - overlap);
+        edge.count = u32::try_from(count).map_err(|_| lines.error(line, 1, format!("edge count {} is too large", count)))?;
+        edge.reads = reads;
+        graph.push(edge);
+    }
+    let graph = Graph::new(graph, nodes.len());
+    let mut ass = Assembler::from_graph(graph, nodes, k);
+    // The graph is still in memory, which always takes the edges
+    if bidirected { ass.make_canonical().expect("graph in memory"); }
+    Ok(ass)
+}
+
+// Length such that contigs at least this long cover half of the total length
+pub fn n50(lens: &[usize]) -> usize {
+    let mut lens = lens.to_vec();
+    lens.sort_unstable_by(|a, b| b.cmp(a));
+    let total: usize = lens.iter().sum();
+    let mut acc = 0;
+    lens.into_iter().find(|&l| { acc += l; acc * 2 >= total }).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quals(strings: &[&str]) -> Vec<Vec<u8>> {
+        strings.iter().map(|q| q.as_bytes().to_vec()).collect()
+    }
+
+    // Bare lines and then records wrapped at random widths, with empty records and lines of bad characters among them
+    fn fasta(seed: u64) -> String {
+        let mut rng = SplitMix64::new(seed);
+        let mut text = String::from("ACGTACGTACGTAC\nGGGGCCCCAAAATTTT\n");
+        for i in 0..300 {
+            text += &format!(">r{} sample {}\n", i, i % 7);
+            let seq: String = (0..rng.next_u64() % 120).map(|_| "ACGTN".as_bytes()[(rng.next_u64() % 5) as usize] as char).collect();
+            let width = 1 + (rng.next_u64() % 60) as usize;
+            for line in seq.as_bytes().chunks(width) {
+                text += std::str::from_utf8(line).expect("bases");
+                text.push('\n');
+            }
+            if rng.next_u64().is_multiple_of(15) { text += "ACG*TT\n"; }
+        }
+        // The last record has no newline at its end
+        text + ">last\nACGTACGTACGTACGTACGTAC"
+    }
+
+    type Outcome = Result<(Vec<Vec<u8>>, Vec<String>, Vec<(usize, usize, String)>, (usize, usize)), (usize, usize, String)>;
+
+    fn outcome(result: Result<Parsed, ParseError>) -> Outcome {
+        let located = |e: ParseError| (e.line, e.column, e.message);
+        result.map(|p| (p.reads, p.names, p.malformed.into_iter().map(located).collect(), (p.rejected.short, p.rejected.long)))
+            .map_err(located)
+    }
+
+    #[test]
+    fn fasta_blocks_match_fasta_reads() {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let fname = Path::new("test.fa");
+        for seed in [1, 2] {
+            let text = fasta(seed);
+            for strict in [false, true] {
+                let options = ReadOptions { lens: 20..=90, strict, names: true, format: None };
+                let expected = outcome(fasta_reads(text.as_bytes(), fname, &options));
+                match &expected {
+                    Ok((_, _, malformed, (short, long))) => assert!(!malformed.is_empty() && *short > 0 && *long > 0),
+                    Err(_) => assert!(strict),
+                }
+                // From a block per byte, which cuts every record, to a single block, in chunks of one record or more
+                for sizes in [(1, 1), (16, 1), (100, 40), (1000, 1), (1000, 300), (1 << 20, 1), (1 << 20, 1 << 20)] {
+                    let blocks = pool.install(|| outcome(fasta_blocks(text.as_bytes(), sizes, fname, &options)));
+                    assert_eq!(blocks, expected, "seed {} strict {} sizes {:?}", seed, strict, sizes);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn phred_offset_needs_scores_above_phred_33() {
+        assert_eq!(phred_offset(&[]), None);
+        assert_eq!(phred_offset(&quals(&["", ""])), None);
+        // Low Phred+33 scores
+        assert_eq!(phred_offset(&quals(&["IIII#", "5555"])), Some(33));
+        // Only high Phred+33 scores, which are between '@' and 'J' as Phred+64 scores of 0 to 10 would be
+        assert_eq!(phred_offset(&quals(&["JJJJ", "@FFJ"])), Some(33));
+        // Phred+64 scores above 10, and Solexa scores down to -5
+        assert_eq!(phred_offset(&quals(&["hhhh", "BBBh"])), Some(64));
+        assert_eq!(phred_offset(&quals(&[";;hh"])), Some(64));
+        // Below ';' cannot be Phred+64, whatever else there is
+        assert_eq!(phred_offset(&quals(&[":hhh"])), Some(33));
+        // Stored as Phred+33, with the Solexa scores below 0 as 0
+        let mut parsed = Parsed { quals: quals(&[";@h"]), ..Parsed::default() };
+        parsed.detect_phred();
+        assert_eq!((parsed.phred, parsed.quals), (Some(64), quals(&["!!I"])));
+    }
+}
@@ -0,0 +1,244 @@
+// Cycle traversal on small crafted graphs
+use sbh_assembler::{ sbh_assembler::{ is_closed, Assembler, EulerianWalk, NodeId, PathType, TraversalStrategy }, utils };
+
+// A distinct 15-mer for every id
+fn kmer(id: u64) -> Vec<u8> {
+    utils::idx2vec(id, 15)
+}
+
+// A 30bp read giving an edge from node a to node b
+fn read(a: u64, b: u64) -> Vec<u8> {
+    [kmer(a), kmer(b)].concat()
+}
+
+// The k-mers of the nodes a walk goes through
+fn idxs(ass: &Assembler, walk: &[NodeId]) -> Vec<u64> {
+    walk.iter().map(|&id| ass.nodes[id].idx).collect()
+}
+
+#[test]
+fn closed_triangle_is_a_cycle() {
+    let mut ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 3), read(3, 1)], 15);
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    assert_eq!(ass.cycles.len(), 1);
+    assert!(ass.abandoned.is_empty());
+    let cycle = idxs(&ass, &ass.cycles[0]);
+    assert_eq!(cycle.len(), 4);
+    assert_eq!(cycle.first(), cycle.last());
+}
+
+#[test]
+fn walk_that_dead_ends_is_abandoned() {
+    // 1 -> 2 -> 3 -> 4 with 2 and 3 the only nodes that can start a cycle
+    let mut ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 3), read(3, 4)], 15);
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    assert!(ass.cycles.is_empty());
+    assert!(!ass.abandoned.is_empty());
+    for walk in ass.abandoned.iter() {
+        assert!(!is_closed(walk));
+        assert!(walk.len() >= 2);
+    }
+}
+
+#[test]
+fn start_without_successor_is_not_counted() {
+    // Once the first cycle is taken, the other start nodes have no unused successor left
+    let mut ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 1)], 15);
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    assert!(ass.cycles.iter().all(|c| is_closed(c)));
+    assert!(ass.abandoned.is_empty());
+    assert_eq!(ass.cycles.len(), 1);
+}
+
+#[test]
+fn cycle_with_tail_keeps_only_closed_walks() {
+    // Triangle 1 -> 2 -> 3 -> 1 with a tail 3 -> 4 -> 5
+    let mut ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 3), read(3, 1), read(3, 4), read(4, 5)], 15);
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    assert!(ass.cycles.iter().all(|c| is_closed(c)));
+    assert!(ass.abandoned.iter().all(|w| !is_closed(w)));
+    // No edge is consumed by more than one walk
+    let edges: usize = ass.cycles.iter().chain(ass.abandoned.iter()).map(|w| w.len() - 1).sum();
+    assert!(edges <= 5);
+}
+
+#[test]
+fn sub_cycle_is_spliced_into_the_walk() {
+    // Triangles 1 -> 2 -> 3 -> 1 and 2 -> 4 -> 5 -> 2 share node 2, so one walk takes all six edges
+    let mut ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 3), read(3, 1), read(2, 4), read(4, 5), read(5, 2)], 15);
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    assert_eq!(ass.cycles.len(), 1);
+    assert!(ass.abandoned.is_empty());
+    let cycle = idxs(&ass, &ass.cycles[0]);
+    assert_eq!(cycle.len(), 7);
+    assert_eq!(cycle.iter().filter(|&&idx| idx == 2).count(), 2);
+}
+
+#[test]
+fn long_sub_cycle_is_left_for_its_own_walk() {
+    // Triangle 1 -> 2 -> 3 -> 1 and a loop from 2 through `len` other nodes. Detours give up after 2048 nodes, so
+    // the long loop is not spliced in but walked as a cycle of its own
+    for (len, cycles) in [(1000, 1), (3000, 2)] {
+        let mut reads = vec![read(1, 2), read(2, 3), read(3, 1), read(2, 100), read(99 + len, 2)];
+        reads.extend((100..99 + len).map(|id| read(id, id + 1)));
+        let mut ass: Assembler = Assembler::new(reads, 15);
+        ass.populate_paths_or_cycles(PathType::Cycle);
+        assert_eq!(ass.cycles.len(), cycles, "loop of {}", len);
+        assert!(ass.abandoned.is_empty());
+        let edges: usize = ass.cycles.iter().map(|c| c.len() - 1).sum();
+        assert_eq!(edges, len as usize + 4);
+    }
+}
+
+#[test]
+fn eulerian_conditions_per_component() {
+    let ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 3), read(3, 1)], 15);
+    assert_eq!(ass.eulerian().walk, EulerianWalk::Circuit);
+    let ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 3), read(3, 4)], 15);
+    let eulerian = ass.eulerian();
+    assert_eq!(eulerian.walk, EulerianWalk::Path);
+    assert_eq!(eulerian.unbalanced(), 2);
+    // Two chains apart each have a path, but no walk takes both
+    let ass: Assembler = Assembler::new(vec![read(1, 2), read(2, 3), read(4, 5), read(6, 5)], 15);
+    let eulerian = ass.eulerian();
+    assert_eq!(eulerian.walk, EulerianWalk::Neither);
+    assert_eq!(eulerian.components.len(), 2);
+    assert_eq!(eulerian.components[0].walk(), EulerianWalk::Path);
+    assert_eq!(eulerian.components[1].walk(), EulerianWalk::Neither);
+    assert_eq!(eulerian.min_walks(), 3);
+}
+
+#[test]
+fn strategy_picks_among_successors() {
+    // 1 branches to a dead end at 2 and to the chain 3 -> 4 -> 5 -> 6, which two reads support
+    let reads = vec![read(1, 2), read(1, 3), read(1, 3), read(3, 4), read(4, 5), read(5, 6)];
+    let mut ass: Assembler = Assembler::new(reads.clone(), 15);
+    ass.populate_paths_or_cycles(PathType::Path);
+    assert!(ass.paths.is_empty());
+    for strategy in [TraversalStrategy::HighestCoverage, TraversalStrategy::LongestExtensionLookahead(4)] {
+        let mut ass: Assembler = Assembler::new(reads.clone(), 15);
+        ass.traversal = strategy;
+        ass.populate_paths_or_cycles(PathType::Path);
+        assert_eq!(ass.paths.len(), 1);
+        assert_eq!(idxs(&ass, &ass.paths[0]), vec![1, 3, 4, 5, 6]);
+    }
+}
+
+#[test]
+fn coverage_match_follows_the_copy_it_came_from() {
+    // 2 branches to 4, which three more reads cover, and to 3, as thinly covered as 1 before the branch
+    let reads = vec![read(1, 2), read(2, 4), read(4, 8), read(4, 8), read(4, 8), read(2, 3), read(3, 9), read(9, 10)];
+    let mut ass: Assembler = Assembler::new(reads, 15);
+    ass.traversal = TraversalStrategy::CoverageMatch;
+    ass.populate_paths_or_cycles(PathType::Path);
+    assert_eq!(idxs(&ass, &ass.paths[0]), vec![1, 2, 3, 9, 10]);
+}
+
+#[test]
+fn backtracking_leaves_a_dead_end() {
+    // 2 branches to the dead end 3 before the chain 4 -> 5 -> 6
+    let reads = vec![read(1, 2), read(2, 3), read(2, 4), read(4, 5), read(5, 6)];
+    let mut ass: Assembler = Assembler::new(reads.clone(), 15);
+    ass.populate_paths_or_cycles(PathType::Path);
+    assert!(ass.paths.is_empty());
+    let mut ass: Assembler = Assembler::new(reads, 15);
+    ass.backtrack = 1;
+    ass.populate_paths_or_cycles(PathType::Path);
+    assert_eq!(idxs(&ass, &ass.paths[0]), vec![1, 2, 4, 5, 6]);
+    ass.check_degrees();
+}
+
+#[test]
+fn attempts_keep_the_longest_walk() {
+    let reads = vec![read(1, 2), read(2, 3), read(2, 4), read(4, 5), read(5, 6)];
+    let mut ass: Assembler = Assembler::new(reads, 15);
+    ass.attempts = 8;
+    ass.seed = 7;
+    ass.populate_paths_or_cycles(PathType::Path);
+    assert_eq!(idxs(&ass, &ass.paths[0]), vec![1, 2, 4, 5, 6]);
+    assert_eq!(ass.traversal, TraversalStrategy::FirstAvailable);
+    ass.check_degrees();
+}
+
+#[test]
+fn parallel_walks_take_every_link_once() {
+    // Two triangles through 2 and a chain out of 5
+    let reads = vec![read(1, 2), read(2, 3), read(3, 1), read(2, 4), read(4, 5), read(5, 2), read(5, 6), read(6, 7)];
+    let mut ass: Assembler = Assembler::new(reads, 15);
+    ass.parallel = true;
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    ass.check_degrees();
+    assert!(ass.cycles.iter().all(|c| is_closed(c)));
+    let mut steps: Vec<(u64, u64)> = ass.cycles.iter().chain(ass.abandoned.iter())
+        .flat_map(|w| w.windows(2).map(|pair| (ass.nodes[pair[0]].idx, ass.nodes[pair[1]].idx)).collect::<Vec<_>>())
+        .collect();
+    let taken = steps.len();
+    steps.sort_unstable();
+    steps.dedup();
+    assert_eq!(steps.len(), taken);
+}
+
+#[test]
+fn deterministic_walks_do_not_depend_on_threads() {
+    let reads = vec![read(1, 2), read(2, 3), read(3, 1), read(2, 4), read(4, 5), read(5, 2), read(5, 6), read(6, 7), read(8, 6)];
+    let walks = |threads: usize| {
+        let mut ass: Assembler = Assembler::new(reads.clone(), 15);
+        ass.parallel = true;
+        ass.deterministic = true;
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| {
+            ass.populate_paths_or_cycles(PathType::Path);
+            ass.populate_paths_or_cycles(PathType::Cycle);
+        });
+        ass.check_degrees();
+        [&ass.paths, &ass.cycles, &ass.abandoned].map(|walks| walks.iter()
+            .map(|w| w.iter().map(|&id| ass.nodes[id].idx).collect::<Vec<_>>())
+            .collect::<Vec<_>>())
+    };
+    assert_eq!(walks(1), walks(4));
+}
+
+#[test]
+fn deterministic_walks_repeat_on_a_contested_graph() {
+    // A genome strung together from a few blocks, so most nodes branch and many walks want the same links, and
+    // 20 sources into a hub with 20 ways out, where a round commits only one walk of those it proposes
+    let mut rng = utils::SplitMix64::new(21);
+    let blocks: Vec<Vec<u8>> = (0..6).map(|_| (0..24).map(|_| b"ACGT"[(rng.next_u64() % 4) as usize]).collect()).collect();
+    let genome: Vec<u8> = (0..300).flat_map(|_| blocks[(rng.next_u64() % 6) as usize].clone()).collect();
+    let mut reads: Vec<Vec<u8>> = genome.windows(16).map(|w| w.to_vec()).collect();
+    reads.extend((0..20).flat_map(|i| [read(100 + i, 1), read(1, 200 + i)]));
+    let run = |threads: usize| {
+        let mut ass: Assembler = Assembler::new(reads.clone(), 15);
+        ass.parallel = true;
+        ass.deterministic = true;
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| {
+            ass.populate_paths_or_cycles(PathType::Path);
+            ass.populate_paths_or_cycles(PathType::Cycle);
+            ass.paths_cycles_to_contigs();
+        });
+        ass.check_degrees();
+        ass.contigs.iter().map(|c| c.seq.clone()).collect::<Vec<_>>().concat()
+    };
+    let first = run(1);
+    assert!(!first.is_empty());
+    for threads in [1, 2, 4, 8, 8] {
+        assert_eq!(run(threads), first, "{} threads", threads);
+    }
+}
+
+#[test]
+fn parallel_components_find_the_walks_of_each_component() {
+    // A triangle apart from a chain
+    let reads = vec![read(1, 2), read(2, 3), read(3, 1), read(4, 5), read(5, 6), read(6, 7), read(7, 8)];
+    let mut serial: Assembler = Assembler::new(reads.clone(), 15);
+    for ids in serial.components().nodes { serial.assemble_component(&ids); }
+    let mut parallel: Assembler = Assembler::new(reads, 15);
+    let components = parallel.components();
+    assert_eq!(parallel.assemble_components_par(&components, |_| {}), 2);
+    parallel.check_degrees();
+    assert_eq!(parallel.paths, serial.paths);
+    assert_eq!(parallel.cycles, serial.cycles);
+    assert_eq!(parallel.contigs.iter().map(|c| (&c.seq, &c.provenance)).collect::<Vec<_>>(),
+        serial.contigs.iter().map(|c| (&c.seq, &c.provenance)).collect::<Vec<_>>());
+}
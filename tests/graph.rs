@@ -0,0 +1,243 @@
+// Building and changing the graph, on reads cut from a random genome
+use rayon::prelude::*;
+use sbh_assembler::{ kmer::Kmer, sbh_assembler::{ Assembler, NodeId, Nodes, NodeType, PathType }, utils::{ self, SplitMix64 } };
+use std::{ fs, io, path::{ Path, PathBuf } };
+#[cfg(feature = "mmap")]
+use sbh_assembler::disk_graph::DiskGraph;
+
+// Every (k+1)-mer of a random genome, with a few repeated
+fn reads(len: usize, k: usize, seed: u64) -> Vec<(Vec<u8>, usize)> {
+    let mut rng = SplitMix64::new(seed);
+    let genome: Vec<u8> = (0..len).map(|_| b"ACGT"[(rng.next_u64() % 4) as usize]).collect();
+    genome.windows(k + 1).map(|w| (w.to_vec(), 1 + (rng.next_u64() % 3) as usize)).collect()
+}
+
+// The nodes in id order and the edges, with the k-mers of their ends in place of ids
+type Summary = (Vec<(u64, usize, usize, usize)>, Vec<(u64, u64, u32, usize, usize)>);
+
+fn summary(ass: &Assembler) -> Summary {
+    let nodes = ass.nodes.iter().map(|(_, n)| (n.idx, n.ideg, n.odeg, n.cov)).collect();
+    let mut edges: Vec<_> = ass.graph.iter()
+        .map(|e| (ass.nodes[e.prefix].idx, ass.nodes[e.suffix].idx, e.count, e.reads, e.span))
+        .collect();
+    edges.sort_unstable();
+    (nodes, edges)
+}
+
+#[test]
+fn parallel_build_matches_serial_build() {
+    let reads = reads(2000, 15, 3);
+    let serial: Assembler = Assembler::from_slices_in(Nodes::default(), reads.iter().map(|(r, w)| (r, *w)), 15);
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let parallel: Assembler = pool.install(|| Assembler::from_slices_par(Nodes::default(), reads.par_iter().map(|(r, w)| (r, *w)), 15));
+    assert_eq!(summary(&parallel), summary(&serial));
+}
+
+#[test]
+fn parallel_build_adds_to_a_table_with_nodes() {
+    let reads = reads(2000, 15, 5);
+    // A node the reads share and one they do not
+    let nodes = || {
+        let mut nodes: Nodes = Nodes::default();
+        nodes.id_or_insert(&utils::vec2idx(&reads[0].0, NodeType::Prefix, 15));
+        nodes.id_or_insert(&0);
+        nodes
+    };
+    let serial: Assembler = Assembler::from_slices_in(nodes(), reads.iter().map(|(r, w)| (r, *w)), 15);
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let parallel: Assembler = pool.install(|| Assembler::from_slices_par(nodes(), reads.par_iter().map(|(r, w)| (r, *w)), 15));
+    assert_eq!(summary(&parallel), summary(&serial));
+    parallel.check_degrees();
+}
+
+// Every node is found by its k-mer under its own id
+fn check_lookups(ass: &Assembler) {
+    for (id, node) in ass.nodes.iter() {
+        assert_eq!(ass.nodes.id(&node.idx), Some(id));
+    }
+}
+
+#[test]
+fn pruning_keeps_degrees_and_lookups() {
+    for (k, dense) in [(15, false), (9, true)] {
+        for canonical in [false, true] {
+            let reads = reads(3000, k, 7);
+            let nodes = if dense { Nodes::dense(k).expect("k fits a dense table") } else { Nodes::default() };
+            let mut ass: Assembler = Assembler::from_slices_in(nodes, reads.iter().map(|(r, w)| (r, *w)), k);
+            if canonical { ass.make_canonical().unwrap(); }
+            ass.populate_paths_or_cycles(PathType::Path);
+            let before = summary(&ass);
+            ass.prune_edges(|e| e.reads < 2).unwrap();
+            ass.check_degrees();
+            let pruned = ass.prune_nodes(|n| n.cov < 3).unwrap();
+            assert!(pruned > 0);
+            ass.check_degrees();
+            check_lookups(&ass);
+            // What is left are the nodes of at least 3 reads and the edges of at least 2 between them
+            let kept: Vec<u64> = before.0.iter().filter(|n| n.3 >= 3).map(|n| n.0).collect();
+            assert_eq!(ass.nodes.iter().map(|(_, n)| n.idx).collect::<Vec<_>>(), kept);
+            let edges: Vec<_> = before.1.iter()
+                .filter(|e| e.3 >= 2 && kept.contains(&e.0) && kept.contains(&e.1))
+                .map(|e| (e.0, e.1, e.4))
+                .collect();
+            assert_eq!(summary(&ass).1.iter().map(|e| (e.0, e.1, e.4)).collect::<Vec<_>>(), edges);
+            for walk in ass.paths.iter().chain(&ass.cycles).chain(&ass.abandoned) {
+                assert!(walk.windows(2).all(|pair| !ass.graph.link(pair[0], pair[1]).is_empty()));
+            }
+        }
+    }
+}
+
+#[test]
+fn retain_and_renumber_keep_lookups() {
+    for (k, dense) in [(15, false), (9, true)] {
+        let reads = reads(1000, k, 9);
+        let nodes = if dense { Nodes::dense(k).expect("k fits a dense table") } else { Nodes::default() };
+        let mut ass: Assembler = Assembler::from_slices_in(nodes, reads.iter().map(|(r, w)| (r, *w)), k);
+        let before: Vec<u64> = ass.nodes.iter().map(|(_, n)| n.idx).collect();
+        // Every third node and the edges at them
+        let removed = |id: NodeId| id.index().is_multiple_of(3);
+        ass.graph.retain(|e| !removed(e.prefix) && !removed(e.suffix)).unwrap();
+        let edges = summary(&ass).1;
+        let ids = ass.nodes.retain(|id, _| !removed(id));
+        ass.graph.renumber(&ids).unwrap();
+        check_lookups(&ass);
+        for (old, idx) in before.iter().enumerate() {
+            assert_eq!(ass.nodes.id(idx), ids[old]);
+            assert_eq!(ids[old].is_none(), old.is_multiple_of(3));
+        }
+        assert_eq!(summary(&ass).1, edges);
+    }
+}
+
+#[test]
+fn added_reads_build_the_same_graph() {
+    let reads = reads(3000, 15, 11);
+    let (first, second) = reads.split_at(reads.len() / 2);
+    for canonical in [false, true] {
+        let build = |reads: &[(Vec<u8>, usize)]| {
+            let mut ass: Assembler = Assembler::from_slices(reads.iter().map(|(r, w)| (r, *w)), 15);
+            if canonical { ass.make_canonical().unwrap(); }
+            ass
+        };
+        let full = build(&reads);
+        let mut topped = build(first);
+        // Walks through the nodes of the new reads are dropped, so their edges can be walked again. The first half of
+        // the genome is one path, which ends at the node the second half goes on from
+        topped.populate_paths_or_cycles(PathType::Path);
+        let walks = topped.paths.len();
+        assert_eq!(topped.add_reads(second.iter().map(|(r, w)| (r, *w))).unwrap(), walks);
+        topped.check_degrees();
+        let sorted = |ass: &Assembler| {
+            let (mut nodes, edges) = summary(ass);
+            nodes.sort_unstable();
+            (nodes, edges)
+        };
+        assert_eq!(sorted(&topped), sorted(&full), "canonical {}", canonical);
+    }
+}
+
+// Nodes too long for a u64, as --kmer-size above 31 builds them, still spell the genome back
+#[test]
+fn long_nodes_assemble_the_genome() {
+    let k = 45;
+    let mut rng = SplitMix64::new(23);
+    let genome: Vec<u8> = (0..2000).map(|_| b"ACGT"[(rng.next_u64() % 4) as usize]).collect();
+    let windows: Vec<(&[u8], usize)> = genome.windows(k + 1).map(|w| (w, 1)).collect();
+    let mut ass: Assembler<Kmer> = Assembler::from_slices_par(Nodes::default(), windows.into_par_iter(), k);
+    assert_eq!(ass.nodes.len(), genome.len() - k + 1);
+    ass.check_degrees();
+    ass.populate_paths_or_cycles(PathType::Path);
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    ass.paths_cycles_to_contigs();
+    ass.condense(20, 0.0, false);
+    let contigs: Vec<&[u8]> = ass.contigs.iter().map(|c| c.seq.as_slice()).collect();
+    assert_eq!(contigs, [genome.as_slice()]);
+}
+
+// Everything a graph file keeps of the nodes, edges and mates
+type Details = (Vec<(u64, usize, usize, usize)>, Vec<(u64, u64, u32, u32, u32, usize, usize, u64)>, Vec<[u64; 4]>);
+
+fn details(ass: &Assembler) -> Details {
+    let mut edges: Vec<_> = ass.graph.iter()
+        .map(|e| (ass.nodes[e.prefix].idx, ass.nodes[e.suffix].idx, e.count, e.used, e.weight.to_bits(), e.reads, e.span, e.colors))
+        .collect();
+    edges.sort_unstable();
+    let mates = ass.mates.iter().map(|m| [m.first.0, m.first.1, m.second.0, m.second.1]).collect();
+    (summary(ass).0, edges, mates)
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("sbh-test-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn saved_graph_loads_back() {
+    let reads = reads(1000, 15, 13);
+    let mut ass: Assembler = Assembler::from_weighted(reads.clone(), 15);
+    ass.make_canonical().unwrap();
+    ass.add_colors(reads.iter().step_by(2).map(|(r, _)| r), 3);
+    ass.add_mates(&[(reads[0].0.clone(), reads[500].0.clone())]);
+    ass.populate_paths_or_cycles(PathType::Path);
+    ass.graph.links_mut().flatten().step_by(7).for_each(|e| e.weight = 0.5);
+    let path = scratch("round-trip.graph");
+    ass.save_graph(&path).unwrap();
+    let loaded: Assembler = Assembler::load_graph(&path).unwrap();
+    assert_eq!((loaded.k, loaded.canonical), (15, true));
+    assert_eq!(details(&loaded), details(&ass));
+    loaded.check_degrees();
+    check_lookups(&loaded);
+
+    // Every file cut short and one with the wrong magic or a later version are refused
+    let bytes = fs::read(&path).unwrap();
+    for len in (0..bytes.len()).step_by(bytes.len() / 200 + 1).chain([bytes.len() - 1]) {
+        fs::write(&path, &bytes[..len]).unwrap();
+        let e = Assembler::<u64>::load_graph(&path).err().expect("a truncated file");
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("ends in the middle"), "{} bytes: {}", len, e);
+    }
+    let mut wrong = bytes.clone();
+    wrong[0] = b'X';
+    fs::write(&path, &wrong).unwrap();
+    assert!(Assembler::<u64>::load_graph(&path).err().expect("wrong magic").to_string().contains("not a graph file"));
+    let mut later = bytes;
+    later[8] = 3;
+    fs::write(&path, &later).unwrap();
+    assert!(Assembler::<u64>::load_graph(&path).err().expect("later version").to_string().contains("version 3"));
+    fs::remove_file(&path).unwrap();
+}
+
+// A file of version 1, from before edges had colors, written by --save-graph --canonical from the reads next to it
+#[test]
+fn version_1_file_loads() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let loaded: Assembler = Assembler::load_graph(&dir.join("graph_v1.graph")).unwrap();
+    let reads: Vec<Vec<u8>> = utils::read_fasta(&dir.join("graph_v1.fasta")).unwrap().into_iter().map(|r| r.seq).collect();
+    let mut built: Assembler = Assembler::new(reads, 15);
+    built.make_canonical().unwrap();
+    assert_eq!((loaded.k, loaded.canonical), (15, true));
+    assert!(loaded.graph.iter().all(|e| e.colors == 0));
+    assert_eq!(details(&loaded), details(&built));
+    loaded.check_degrees();
+}
+
+// Two graphs on disk in the same directory keep their own files, and a graph that cannot be stored again says so
+#[cfg(feature = "mmap")]
+#[test]
+fn disk_graphs_keep_apart_and_report_failed_stores() {
+    let dir = scratch("disk");
+    let on_disk = |seed: u64| {
+        let mut ass: Assembler = Assembler::from_weighted(reads(1000, 15, seed), 15);
+        let expected = summary(&ass);
+        ass.graph.move_to(Box::new(DiskGraph::new(&dir).unwrap())).unwrap();
+        (ass, expected)
+    };
+    let (mut first, first_expected) = on_disk(17);
+    let (second, second_expected) = on_disk(19);
+    assert_eq!(summary(&first), first_expected);
+    assert_eq!(summary(&second), second_expected);
+    drop(second);
+    assert_eq!(summary(&first), first_expected);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(first.prune_edges(|e| e.reads < 2).is_err());
+}
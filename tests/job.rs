@@ -0,0 +1,59 @@
+// Background assembly jobs, on small crafted graphs
+use sbh_assembler::{
+    config::Config,
+    job::{ AssemblyJob, JobError },
+    sbh_assembler::{ Assembler, PathType, TraversalStrategy },
+    utils,
+};
+
+// A 30bp read giving an edge from the 15-mer of id a to that of id b
+fn read(a: u64, b: u64) -> Vec<u8> {
+    [utils::idx2vec(a, 15), utils::idx2vec(b, 15)].concat()
+}
+
+// 1 branches to a dead end at 2 and to the chain 3 -> 4 -> 5 -> 6, which two reads support
+fn reads() -> Vec<Vec<u8>> {
+    vec![read(1, 2), read(1, 3), read(1, 3), read(3, 4), read(4, 5), read(5, 6)]
+}
+
+fn sorted(contigs: impl IntoIterator<Item = Vec<u8>>) -> Vec<Vec<u8>> {
+    let mut seqs: Vec<Vec<u8>> = contigs.into_iter().collect();
+    seqs.sort_unstable();
+    seqs
+}
+
+// What the command line makes of the reads with a traversal strategy
+fn assembled(traversal: TraversalStrategy) -> Vec<Vec<u8>> {
+    let config = Config::default();
+    let mut ass: Assembler = Assembler::new(reads(), 15);
+    ass.traversal = traversal;
+    ass.populate_paths_or_cycles(PathType::Path);
+    ass.populate_paths_or_cycles(PathType::Cycle);
+    ass.paths_cycles_to_contigs();
+    ass.condense(config.min_overlap, config.min_overlap_frac, false);
+    sorted(ass.contigs.into_iter().map(|c| c.seq))
+}
+
+#[test]
+fn job_walks_with_the_traversal_strategy() {
+    let config = Config { traversal: TraversalStrategy::HighestCoverage, ..Config::default() };
+    let contigs = AssemblyJob::spawn(config, reads()).join().unwrap();
+    let expected = assembled(TraversalStrategy::HighestCoverage);
+    assert_ne!(expected, assembled(TraversalStrategy::FirstAvailable));
+    assert_eq!(sorted(contigs.into_iter().map(|c| c.seq)), expected);
+}
+
+#[test]
+fn job_runs_the_named_stages() {
+    let stages = |names: &[&str]| Some(names.iter().map(|s| s.to_string()).collect());
+    let config = Config { stages: stages(&["paths", "contigs"]), ..Config::default() };
+    let contigs = AssemblyJob::spawn(config, reads()).join().unwrap();
+    let mut ass: Assembler = Assembler::new(reads(), 15);
+    ass.populate_paths_or_cycles(PathType::Path);
+    ass.paths_cycles_to_contigs();
+    assert_eq!(sorted(contigs.into_iter().map(|c| c.seq)), sorted(ass.contigs.into_iter().map(|c| c.seq)));
+
+    let config = Config { stages: stages(&["paths", "no-such-stage"]), ..Config::default() };
+    let e = AssemblyJob::spawn(config, reads()).join().expect_err("an unknown stage");
+    assert!(matches!(&e, JobError::Stage(msg) if msg.contains("no-such-stage")), "{}", e);
+}
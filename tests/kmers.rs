@@ -0,0 +1,96 @@
+// Splitting reads into windows, on small generated reads
+use sbh_assembler::{ external, utils::{ self, SplitMix64 } };
+use std::collections::HashMap;
+
+// Reads of random bases from a few templates, so windows repeat on both strands, with an N here and there
+fn reads(n: usize, len: usize, seed: u64) -> Vec<(Vec<u8>, usize)> {
+    let mut rng = SplitMix64::new(seed);
+    let base = |rng: &mut SplitMix64| b"ACGT"[(rng.next_u64() % 4) as usize];
+    let templates: Vec<Vec<u8>> = (0..3).map(|_| (0..len + 20).map(|_| base(&mut rng)).collect()).collect();
+    (0..n).map(|i| {
+        let template = &templates[i % templates.len()];
+        let start = (rng.next_u64() % 20) as usize;
+        let mut read = template[start..start + len].to_vec();
+        if rng.next_u64().is_multiple_of(4) { read[(rng.next_u64() as usize) % len] = b'N'; }
+        if i % 2 == 1 { read = utils::revcomp(&read); }
+        (read, 1 + (rng.next_u64() % 3) as usize)
+    }).collect()
+}
+
+fn sorted(mut windows: Vec<(Vec<u8>, usize)>) -> Vec<(Vec<u8>, usize)> {
+    windows.sort_unstable();
+    windows
+}
+
+#[test]
+fn disk_counts_match_memory_counts() {
+    let reads = reads(60, 40, 7);
+    let dir = std::env::temp_dir();
+    for k in [5, 15, 30] {
+        for canonical in [false, true] {
+            for min in [1, 2, 4] {
+                let (memory, memory_dropped) = utils::kmerize_solid(&reads, k, canonical, &mut HashMap::new(), min);
+                // A few hundred bytes, so most windows go through a run on disk
+                let (disk, disk_dropped) = external::kmerize(&reads, k, canonical, min, &dir, 256).unwrap();
+                assert_eq!(sorted(disk), sorted(memory), "k {} canonical {} min {}", k, canonical, min);
+                assert_eq!(disk_dropped, memory_dropped);
+            }
+        }
+    }
+}
+
+#[test]
+fn jobs_of_one_process_count_apart() {
+    let reads = reads(60, 40, 11);
+    let dir = std::env::temp_dir();
+    let expected = sorted(utils::kmerize_solid(&reads, 9, true, &mut HashMap::new(), 1).0);
+    std::thread::scope(|scope| {
+        let jobs: Vec<_> = (0..4).map(|_| scope.spawn(|| external::kmerize(&reads, 9, true, 1, &dir, 64).unwrap().0)).collect();
+        for job in jobs {
+            assert_eq!(sorted(job.join().unwrap()), expected);
+        }
+    });
+}
+
+// A k-mer or its reverse complement, whichever sorts first
+fn canonical(kmer: &[u8]) -> Vec<u8> {
+    kmer.to_vec().min(utils::revcomp(kmer))
+}
+
+#[test]
+fn minimizers_are_the_same_on_both_strands() {
+    for (read, _) in reads(20, 120, 13) {
+        let rc = utils::revcomp(&read);
+        for (k, w) in [(5, 3), (15, 8), (15, 15), (33, 10)] {
+            let picked = |read: &[u8]| {
+                let mut kmers: Vec<Vec<u8>> = utils::minimizers(read, k, w, true).into_iter().flatten()
+                    .map(|p| canonical(&read[p..p + k]))
+                    .collect();
+                kmers.sort_unstable();
+                kmers
+            };
+            assert_eq!(picked(&read), picked(&rc), "k {} w {}", k, w);
+        }
+    }
+}
+
+#[test]
+fn minimizers_are_at_most_w_apart() {
+    let reads = reads(20, 120, 17);
+    for (k, w) in [(5, 3), (15, 8)] {
+        let (windows, _) = utils::kmerize_sparse(&reads, k, w, true, None);
+        assert!(windows.iter().all(|(window, _)| (k + 1..=w + k).contains(&window.len())));
+    }
+    for (read, _) in reads {
+        for (k, w) in [(5, 3), (15, 1), (15, 8), (15, 15), (33, 10)] {
+            for canonical in [false, true] {
+                for positions in utils::minimizers(&read, k, w, canonical) {
+                    assert!(positions.windows(2).all(|pair| pair[0] < pair[1] && pair[1] - pair[0] <= w), "k {} w {}", k, w);
+                }
+                for window in utils::sparse_windows(&read, k, w, canonical) {
+                    assert!((k + 1..=w + k).contains(&window.len()), "k {} w {}: {} bp", k, w, window.len());
+                }
+            }
+        }
+    }
+}
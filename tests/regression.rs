@@ -0,0 +1,125 @@
+// Golden dataset regression suite
+// Runs the assembler on every dataset in tests/golden/datasets.tsv, with the flags given for it, and asserts that
+// the assembly metrics stay within the tolerance bands recorded there. The small phage and plasmid read sets are
+// bundled there. Extra datasets that are too large to bundle can be supplied by pointing SBH_GOLDEN_DIR at a
+// directory with its own datasets.tsv in the same format. Measured metrics are appended to
+// target/regression-metrics.tsv
+#![cfg(feature = "regression")]
+
+use std::{
+    env,
+    fs::{ self, OpenOptions },
+    io::Write,
+    path::{ Path, PathBuf },
+    process::Command,
+    time::{ Instant, SystemTime, UNIX_EPOCH },
+};
+
+struct Dataset {
+    name: String,
+    reads: PathBuf,
+    contigs: (usize, usize),
+    longest: (usize, usize),
+    total: (usize, usize),
+    // Extra command line flags, such as --debruijn
+    flags: Vec<String>,
+}
+
+struct Metrics {
+    contigs: usize,
+    longest: usize,
+    total: usize,
+    n50: usize,
+    seconds: f32,
+}
+
+fn load_manifest(dir: &Path) -> Vec<Dataset> {
+    let manifest = fs::read_to_string(dir.join("datasets.tsv"))
+        .unwrap_or_else(|e| panic!("failed to read manifest in {}: {}", dir.display(), e));
+    manifest.lines()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let f: Vec<&str> = l.split('\t').collect();
+            assert!(f.len() == 8 || f.len() == 9, "malformed manifest line: '{}'", l);
+            let n = |i: usize| f[i].parse::<usize>().unwrap_or_else(|_| panic!("bad number '{}'", f[i]));
+            Dataset {
+                name: f[0].to_string(),
+                reads: dir.join(f[1]),
+                contigs: (n(2), n(3)),
+                longest: (n(4), n(5)),
+                total: (n(6), n(7)),
+                flags: f.get(8).map_or(vec![], |flags| flags.split_whitespace().map(str::to_string).collect()),
+            }
+        })
+        .collect()
+}
+
+fn assemble(dataset: &Dataset, out: &Path) -> Metrics {
+    let start = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_sbh_assembler"))
+        .args(&dataset.flags)
+        .arg(&dataset.reads)
+        .arg(out)
+        .output()
+        .expect("failed to run the assembler");
+    assert!(status.status.success(), "{}: assembler failed:\n{}",
+        dataset.name, String::from_utf8_lossy(&status.stderr));
+    let seconds = start.elapsed().as_secs_f32();
+    let fasta = fs::read_to_string(out).expect("failed to read assembler output");
+    let mut lens: Vec<usize> = fasta.lines()
+        .filter(|l| !l.starts_with('>'))
+        .map(|l| l.trim().len())
+        .collect();
+    lens.sort_unstable_by(|a, b| b.cmp(a));
+    let total: usize = lens.iter().sum();
+    let mut acc = 0;
+    let n50 = lens.iter().find(|&&l| { acc += l; acc * 2 >= total }).copied().unwrap_or(0);
+    Metrics { contigs: lens.len(), longest: lens.first().copied().unwrap_or(0), total, n50, seconds }
+}
+
+fn record(dataset: &Dataset, m: &Metrics) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("regression-metrics.tsv");
+    let new = !path.exists();
+    let mut file = match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(f) => f,
+        Err(_) => return,
+    };
+    if new { let _ = writeln!(file, "timestamp\tdataset\tcontigs\tlongest\ttotal\tn50\tseconds"); }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let _ = writeln!(file, "{}\t{}\t{}\t{}\t{}\t{}\t{:.3}",
+        now, dataset.name, m.contigs, m.longest, m.total, m.n50, m.seconds);
+}
+
+fn within(val: usize, band: (usize, usize)) -> bool {
+    band.0 <= val && val <= band.1
+}
+
+#[test]
+fn golden_datasets_within_tolerance() {
+    let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")];
+    if let Ok(extra) = env::var("SBH_GOLDEN_DIR") { dirs.push(PathBuf::from(extra)); }
+    let out_dir = env::temp_dir();
+    let mut failures = vec![];
+    for dataset in dirs.iter().flat_map(|d| load_manifest(d)) {
+        if !dataset.reads.exists() {
+            println!("skipping {}: {} not found", dataset.name, dataset.reads.display());
+            continue;
+        }
+        let out = out_dir.join(format!("sbh-regression-{}-{}.fasta", dataset.name, std::process::id()));
+        let m = assemble(&dataset, &out);
+        let _ = fs::remove_file(&out);
+        record(&dataset, &m);
+        println!("{}: contigs={} longest={} total={} n50={} ({:.2}s)",
+            dataset.name, m.contigs, m.longest, m.total, m.n50, m.seconds);
+        for (metric, val, band) in [
+            ("contigs", m.contigs, dataset.contigs),
+            ("longest", m.longest, dataset.longest),
+            ("total", m.total, dataset.total),
+        ] {
+            if !within(val, band) {
+                failures.push(format!("{}: {} = {} outside [{}, {}]", dataset.name, metric, val, band.0, band.1));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "regression metrics out of tolerance:\n{}", failures.join("\n"));
+}